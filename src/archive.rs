@@ -0,0 +1,322 @@
+//! Browsing and editing archives. An archive is extracted to a temporary
+//! directory when entered, and if anything in it was added, deleted or renamed
+//! by the time it's left, it's packed up again from that directory.
+//!
+//! Files compressed on their own, like `server.log.gz`, are previewed and
+//! opened through a decompressed copy instead.
+
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{BuildHasher, Hasher, RandomState},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    time::SystemTime,
+};
+
+#[derive(Clone, Copy)]
+enum Format {
+    Zip,
+    /// A tarball, with the flag of its compression, if any.
+    Tar(Option<&'static str>),
+}
+impl Format {
+    fn of(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        let format = if name.ends_with(".zip") || name.ends_with(".jar") {
+            Format::Zip
+        } else if name.ends_with(".tar") {
+            Format::Tar(None)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Format::Tar(Some("-z"))
+        } else if name.ends_with(".tar.bz2") || name.ends_with(".tbz2") {
+            Format::Tar(Some("-j"))
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Format::Tar(Some("-J"))
+        } else {
+            return None;
+        };
+        Some(format)
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    Format::of(path).is_some()
+}
+
+/// Extensions of files compressed on their own, and the commands writing them
+/// out decompressed.
+const COMPRESSIONS: &[(&str, &[&str])] = &[
+    ("gz", &["gzip", "-dc"]),
+    ("bz2", &["bzip2", "-dc"]),
+    ("xz", &["xz", "-dc"]),
+    ("zst", &["zstd", "-dc"]),
+];
+
+/// The command writing `path` to stdout decompressed, if it's a file compressed
+/// on its own rather than a compressed tarball.
+pub fn decompress_command(path: &Path) -> Option<Command> {
+    if is_archive(path) {
+        return None;
+    }
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    let (_, command) = COMPRESSIONS
+        .iter()
+        .find(|(compressed, _)| *compressed == extension)?;
+    let mut command_line = Command::new(command[0]);
+    command_line.args(&command[1..]).arg(path);
+    Some(command_line)
+}
+
+pub fn is_compressed(path: &Path) -> bool {
+    decompress_command(path).is_some()
+}
+
+/// Makes a new directory in the temporary directory, which other users share,
+/// under a name they can't guess to make first, and only the user can get into.
+fn private_temp_dir(prefix: &str) -> io::Result<PathBuf> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    let mut error = io::Error::from(io::ErrorKind::AlreadyExists);
+    for _ in 0..16 {
+        // hashers are keyed randomly
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        let name = format!("{}-{:016x}", prefix, hasher.finish());
+        let directory = std::env::temp_dir().join(name);
+        match builder.create(&directory) {
+            Ok(()) => return Ok(directory),
+            Err(other) if other.kind() == io::ErrorKind::AlreadyExists => error = other,
+            Err(other) => return Err(other),
+        }
+    }
+    Err(error)
+}
+
+/// A file in a temporary directory of its own, removed when it's dropped, like
+/// a decompressed copy of a compressed file.
+pub struct TempFile {
+    pub path: PathBuf,
+    directory: PathBuf,
+}
+impl TempFile {
+    /// A file named `name` holding `contents`.
+    pub fn with_contents(name: &str, contents: &[u8]) -> io::Result<Self> {
+        let directory = private_temp_dir("fee")?;
+        let file = TempFile {
+            path: directory.join(name),
+            directory,
+        };
+        fs::File::create_new(&file.path)?.write_all(contents)?;
+        Ok(file)
+    }
+    /// Decompresses all of `compressed`, into a copy named like it without the
+    /// compression's extension.
+    pub fn decompressed(compressed: &Path) -> io::Result<Self> {
+        let mut command =
+            decompress_command(compressed).ok_or(io::Error::other("not compressed"))?;
+        let copy = TempFile::empty_decompressed(compressed)?;
+        let output = command
+            .stdin(Stdio::null())
+            .stdout(fs::File::create_new(&copy.path)?)
+            .stderr(Stdio::piped())
+            .output()?;
+        check(output)?;
+        Ok(copy)
+    }
+    /// A copy holding `contents`, as decompressed from `compressed` already, like
+    /// the start of it.
+    pub fn with_decompressed(compressed: &Path, contents: &[u8]) -> io::Result<Self> {
+        let copy = TempFile::empty_decompressed(compressed)?;
+        fs::File::create_new(&copy.path)?.write_all(contents)?;
+        Ok(copy)
+    }
+    fn empty_decompressed(compressed: &Path) -> io::Result<Self> {
+        let directory = private_temp_dir("fee-decompressed")?;
+        let name = compressed.file_stem().unwrap_or(compressed.as_os_str());
+        Ok(TempFile {
+            path: directory.join(name),
+            directory,
+        })
+    }
+}
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+/// The size and modification time of every file and directory in an extracted
+/// archive, by their path inside it.
+type Snapshot = HashMap<PathBuf, (u64, Option<SystemTime>)>;
+
+/// An archive extracted to a temporary directory.
+pub struct Mounted {
+    pub archive: PathBuf,
+    /// The temporary directory it's extracted to.
+    pub root: PathBuf,
+    format: Format,
+    snapshot: Snapshot,
+}
+impl Mounted {
+    pub fn mount(archive: &Path) -> io::Result<Self> {
+        let format = Format::of(archive).ok_or(io::Error::other("not an archive"))?;
+        let root = private_temp_dir("fee-archive")?;
+        let mut command = match format {
+            Format::Zip => {
+                let mut command = Command::new("unzip");
+                command.arg("-q").arg(archive).arg("-d").arg(&root);
+                command
+            }
+            Format::Tar(compression) => {
+                let mut command = Command::new("tar");
+                command
+                    .args(compression)
+                    .arg("-xf")
+                    .arg(archive)
+                    .arg("-C")
+                    .arg(&root);
+                command
+            }
+        };
+        if let Err(error) = run(&mut command) {
+            let _ = fs::remove_dir_all(&root);
+            return Err(error);
+        }
+        Ok(Mounted {
+            archive: archive.to_path_buf(),
+            snapshot: snapshot(&root),
+            root,
+            format,
+        })
+    }
+
+    /// Describes what was changed since it was extracted, or returns `None` if
+    /// nothing was.
+    pub fn changes(&self) -> Option<String> {
+        let current = snapshot(&self.root);
+        let mut added: Vec<&PathBuf> = current
+            .keys()
+            .filter(|path| !self.snapshot.contains_key(*path))
+            .collect();
+        let mut deleted: Vec<&PathBuf> = self
+            .snapshot
+            .keys()
+            .filter(|path| !current.contains_key(*path))
+            .collect();
+        let modified = current
+            .iter()
+            .filter(|(path, stat)| self.snapshot.get(*path).is_some_and(|old| old != *stat))
+            .count();
+        // a renamed file keeps its size and modification time
+        let mut renamed = 0;
+        added.retain(|path| {
+            let stat = &current[*path];
+            match deleted.iter().position(|old| self.snapshot[*old] == *stat) {
+                Some(index) => {
+                    deleted.remove(index);
+                    renamed += 1;
+                    false
+                }
+                None => true,
+            }
+        });
+        let counts = [
+            (added.len(), "added"),
+            (deleted.len(), "deleted"),
+            (renamed, "renamed"),
+            (modified, "modified"),
+        ];
+        let parts: Vec<String> = counts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, what)| format!("{} {}", count, what))
+            .collect();
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(", "))
+        }
+    }
+
+    /// Packs the extracted files back up into the archive.
+    pub fn repack(&self) -> io::Result<()> {
+        let name = self
+            .archive
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let packed = self.archive.with_file_name(format!(".{}.fee-repack", name));
+        let _ = fs::remove_file(&packed);
+        let mut command = match self.format {
+            Format::Zip => {
+                let mut command = Command::new("zip");
+                command.args(["-q", "-r", "-y"]).arg(&packed).arg(".");
+                command
+            }
+            Format::Tar(compression) => {
+                let mut command = Command::new("tar");
+                command.args(compression).arg("-cf").arg(&packed).arg(".");
+                command
+            }
+        };
+        command.current_dir(&self.root);
+        if let Err(error) = run(&mut command) {
+            let _ = fs::remove_file(&packed);
+            return Err(error);
+        }
+        fs::rename(&packed, &self.archive)
+    }
+}
+impl Drop for Mounted {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn run(command: &mut Command) -> io::Result<()> {
+    let output = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()?;
+    check(output)
+}
+
+/// Fails with the first line the command wrote to stderr, unless it succeeded.
+fn check(output: Output) -> io::Result<()> {
+    if output.status.success() {
+        return Ok(());
+    }
+    let error = String::from_utf8_lossy(&output.stderr);
+    Err(io::Error::other(
+        error.lines().next().unwrap_or("failed").to_string(),
+    ))
+}
+
+fn snapshot(root: &Path) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&directory) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            // directories only count as being there, their times change with their contents
+            if metadata.is_dir() {
+                snapshot.insert(relative, (0, None));
+                pending.push(path);
+            } else {
+                snapshot.insert(relative, (metadata.len(), metadata.modified().ok()));
+            }
+        }
+    }
+    snapshot
+}
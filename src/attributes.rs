@@ -0,0 +1,175 @@
+//! Changing the permissions of every marked item at once, from the properties
+//! view. Changes are written like chmod's: `644`, or clauses like `u+x` or
+//! `go-w,a+r`. Only the read only attribute exists on Windows, which follows
+//! the `w` bits.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{prompt::Prompt, properties};
+
+/// A parsed change of permissions.
+pub enum Change {
+    /// Replaces the permission bits.
+    Absolute(u32),
+    /// Each clause as who it applies to, the operator and the bits, as masks.
+    Symbolic(Vec<(u32, char, u32)>),
+}
+impl Change {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if !text.is_empty() && text.chars().all(|char| ('0'..='7').contains(&char)) {
+            return u32::from_str_radix(text, 8)
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .map(Change::Absolute);
+        }
+        let mut clauses = vec![];
+        for clause in text.split(',') {
+            let operator = clause.find(['+', '-', '='])?;
+            let (who, bits) = clause.split_at(operator);
+            let mut who_mask = 0;
+            for char in who.chars() {
+                who_mask |= match char {
+                    'u' => 0o700,
+                    'g' => 0o070,
+                    'o' => 0o007,
+                    'a' => 0o777,
+                    _ => return None,
+                };
+            }
+            if who_mask == 0 {
+                who_mask = 0o777;
+            }
+            let mut bits_mask = 0;
+            for char in bits[1..].chars() {
+                bits_mask |= match char {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    _ => return None,
+                };
+            }
+            clauses.push((who_mask, bits.chars().next()?, bits_mask));
+        }
+        Some(Change::Symbolic(clauses))
+    }
+    /// Returns `mode` with the change applied.
+    pub fn apply(&self, mode: u32) -> u32 {
+        match self {
+            Change::Absolute(bits) => (mode & !0o7777) | bits,
+            Change::Symbolic(clauses) => {
+                clauses
+                    .iter()
+                    .fold(mode, |mode, (who, operator, bits)| match operator {
+                        '+' => mode | (who & bits),
+                        '-' => mode & !(who & bits),
+                        _ => (mode & !who) | (who & bits),
+                    })
+            }
+        }
+    }
+}
+
+/// The marked items and their permissions, with the change being typed.
+pub struct Batch {
+    /// Items with the permissions they had when the view was opened.
+    pub items: Vec<(PathBuf, u32)>,
+    pub prompt: Prompt,
+}
+impl Batch {
+    /// Reads the permissions of `paths`, leaving out ones that can't be read.
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let items = paths
+            .into_iter()
+            .filter_map(|path| {
+                let metadata = fs::metadata(&path).ok()?;
+                Some((path, mode(&metadata)))
+            })
+            .collect();
+        Batch {
+            items,
+            prompt: Prompt::default(),
+        }
+    }
+    /// Lines describing how many items have each set of permissions.
+    pub fn summary(&self) -> Vec<String> {
+        let mut counts: Vec<(u32, usize)> = vec![];
+        for (_, mode) in &self.items {
+            match counts.iter_mut().find(|(counted, _)| counted == mode) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((*mode, 1)),
+            }
+        }
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let mut lines = vec![format!("{} marked items", self.items.len()), String::new()];
+        for (mode, count) in counts {
+            lines.push(format!(
+                "  {} ({:o}): {}",
+                properties::mode_bits(mode),
+                mode,
+                count
+            ));
+        }
+        lines
+    }
+    /// Describes what the typed change would do.
+    pub fn preview(&self) -> String {
+        let text = self.prompt.text();
+        if text.trim().is_empty() {
+            return "type a change like 644, u+x or go-w, and press enter".to_string();
+        }
+        let Some(change) = Change::parse(&text) else {
+            return format!("'{}' isn't a valid change", text);
+        };
+        format!(
+            "{} would change {} of {} items",
+            text.trim(),
+            self.affected(&change).count(),
+            self.items.len()
+        )
+    }
+    /// The items `change` would change, with their new permissions.
+    pub fn affected<'a>(
+        &'a self,
+        change: &'a Change,
+    ) -> impl Iterator<Item = (&'a PathBuf, u32)> + 'a {
+        self.items.iter().filter_map(|(path, mode)| {
+            let changed = change.apply(*mode);
+            (changed != *mode).then_some((path, changed))
+        })
+    }
+}
+
+/// Sets the permission bits of `path` to `mode`.
+#[cfg(unix)]
+pub fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+pub fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o222 == 0);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.mode() & 0o7777
+}
+
+/// Windows only has the read only attribute, so it's shown as whether
+/// everyone can write.
+#[cfg(not(unix))]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o666
+    }
+}
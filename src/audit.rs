@@ -0,0 +1,29 @@
+//! A log of every change made to files, so what happened during a session can
+//! be pieced together afterwards. Kept in `audit.log` next to the config.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    time::SystemTime,
+};
+
+use crate::{properties, shared};
+
+/// Appends `actions` to the audit log, timestamped and tagged with the process
+/// id so interleaved sessions can be told apart.
+pub fn record(actions: &[String]) -> io::Result<()> {
+    let Some(directory) = shared::config_dir() else {
+        return Err(io::Error::other("couldn't get config directory"));
+    };
+    let time = properties::format_time(SystemTime::now());
+    let pid = std::process::id();
+    let mut lines = String::new();
+    for action in actions {
+        lines.push_str(&format!("{} [{}] {}\n", time, pid, action));
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(directory.join("fee").join("audit.log"))?
+        .write_all(lines.as_bytes())
+}
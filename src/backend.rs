@@ -0,0 +1,236 @@
+//! Where the interface is drawn and where its input comes from. Normally that's
+//! the terminal, but tests draw to a screen kept in memory and type a list of
+//! events instead.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, IsTerminal, Write},
+    process::Stdio,
+    time::Duration,
+};
+
+use crossterm::{
+    event::{self, Event},
+    terminal,
+};
+
+/// Something the interface can be drawn on, with crossterm's commands.
+pub trait Backend: Write {
+    /// The size of the screen, as columns and rows.
+    fn size(&self) -> io::Result<(u16, u16)>;
+    /// Takes over the screen, before drawing.
+    fn enter(&mut self) -> io::Result<()>;
+    /// Gives the screen back, like before running another program in it.
+    fn leave(&mut self) -> io::Result<()>;
+    /// Where a program run on the screen should print.
+    fn child_output(&self) -> Stdio {
+        Stdio::inherit()
+    }
+}
+
+/// The terminal fee runs in. When stdout isn't it, like when a chosen file is
+/// printed for `$(fee --choosefile=-)`, it's drawn on the controlling terminal.
+pub struct Terminal(Box<dyn Write>);
+impl Terminal {
+    pub fn new() -> Self {
+        if !io::stdout().is_terminal() {
+            if let Ok(tty) = OpenOptions::new().write(true).open("/dev/tty") {
+                return Terminal(Box::new(tty));
+            }
+        }
+        Terminal(Box::new(io::stdout()))
+    }
+}
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+impl Backend for Terminal {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        terminal::size()
+    }
+    fn enter(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()
+    }
+    fn leave(&mut self) -> io::Result<()> {
+        terminal::disable_raw_mode()
+    }
+    /// The controlling terminal when stdout isn't it, so what programs print
+    /// isn't taken for fee's output.
+    fn child_output(&self) -> Stdio {
+        if !io::stdout().is_terminal() {
+            if let Ok(tty) = OpenOptions::new().write(true).open("/dev/tty") {
+                return tty.into();
+            }
+        }
+        Stdio::inherit()
+    }
+}
+
+/// Where input comes from. Any iterator of events is one, which ends the
+/// program once it runs out.
+pub trait Events {
+    /// Whether an event is ready to be read within `timeout`.
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool>;
+    /// Waits for the next event, or returns `None` if there will be no more.
+    fn read(&mut self) -> io::Result<Option<Event>>;
+}
+
+/// Keys, mouse clicks and resizes of the terminal.
+pub struct TerminalEvents;
+impl Events for TerminalEvents {
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        event::poll(timeout)
+    }
+    fn read(&mut self) -> io::Result<Option<Event>> {
+        event::read().map(Some)
+    }
+}
+
+impl<I: Iterator<Item = Event>> Events for I {
+    fn poll(&mut self, _: Duration) -> io::Result<bool> {
+        Ok(true)
+    }
+    fn read(&mut self) -> io::Result<Option<Event>> {
+        Ok(self.next())
+    }
+}
+
+/// A screen kept in memory, understanding the few escape sequences needed to
+/// tell what text is where. Clones share the screen, so a test can look at what
+/// was drawn on it.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct TestBackend(std::rc::Rc<std::cell::RefCell<Screen>>);
+#[cfg(test)]
+impl TestBackend {
+    pub fn new(width: u16, height: u16) -> Self {
+        TestBackend(std::rc::Rc::new(std::cell::RefCell::new(Screen {
+            width,
+            height,
+            cells: vec![vec![' '; width as usize]; height as usize],
+            cursor: (0, 0),
+            pending: vec![],
+            last_frame: vec![],
+        })))
+    }
+    /// The text of each row as it was before the screen was last cleared,
+    /// without trailing spaces. Every frame is drawn on a cleared screen, and
+    /// it's cleared once more when quitting, so this is the last frame drawn.
+    pub fn last_frame(&self) -> Vec<String> {
+        self.0.borrow().last_frame.clone()
+    }
+}
+#[cfg(test)]
+impl Write for TestBackend {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().update();
+        Ok(())
+    }
+}
+#[cfg(test)]
+impl Backend for TestBackend {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        let screen = self.0.borrow();
+        Ok((screen.width, screen.height))
+    }
+    fn enter(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+    fn leave(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+struct Screen {
+    width: u16,
+    height: u16,
+    cells: Vec<Vec<char>>,
+    cursor: (usize, usize),
+    /// Written since the last flush, which is when the screen is updated.
+    pending: Vec<u8>,
+    last_frame: Vec<String>,
+}
+#[cfg(test)]
+impl Screen {
+    fn update(&mut self) {
+        let text = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).to_string();
+        let mut chars = text.chars();
+        while let Some(char) = chars.next() {
+            match char {
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        let mut parameters = String::new();
+                        for char in chars.by_ref() {
+                            if char.is_ascii_alphabetic() || char == '~' {
+                                self.control(&parameters, char);
+                                break;
+                            }
+                            parameters.push(char);
+                        }
+                    }
+                    // strings, like images, end with BEL or ESC \
+                    Some(']' | 'P' | '_') => {
+                        while let Some(char) = chars.next() {
+                            if char == '\x07' || (char == '\x1b' && chars.next() == Some('\\')) {
+                                break;
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                '\r' => self.cursor.0 = 0,
+                '\n' => self.cursor.1 += 1,
+                char => {
+                    let (x, y) = self.cursor;
+                    if let Some(cell) = self.cells.get_mut(y).and_then(|row| row.get_mut(x)) {
+                        *cell = char;
+                    }
+                    self.cursor.0 += 1;
+                }
+            }
+        }
+    }
+    /// Runs a control sequence, like `ESC [ 3 ; 5 H`, given what's between the
+    /// bracket and the final character.
+    fn control(&mut self, parameters: &str, command: char) {
+        let numbers: Vec<usize> = parameters
+            .split(';')
+            .map(|number| number.parse().unwrap_or(1))
+            .collect();
+        match command {
+            'H' => {
+                let row = numbers.first().copied().unwrap_or(1);
+                let column = numbers.get(1).copied().unwrap_or(1);
+                self.cursor = (column.saturating_sub(1), row.saturating_sub(1));
+            }
+            'J' if parameters == "2" => {
+                self.last_frame = self
+                    .cells
+                    .iter()
+                    .map(|row| row.iter().collect::<String>().trim_end().to_string())
+                    .collect();
+                for row in &mut self.cells {
+                    row.fill(' ');
+                }
+            }
+            'K' => {
+                let (x, y) = self.cursor;
+                if let Some(row) = self.cells.get_mut(y) {
+                    row.iter_mut().skip(x).for_each(|cell| *cell = ' ');
+                }
+            }
+            // colors, attributes and showing the cursor don't change the text
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Bookmarked directories, jumped to from a picker opened with `'`. Each can have
+//! a key jumping straight to it (so `'d` could go to Downloads), and a group it's
+//! listed under. `b` bookmarks the current directory straight away, giving it
+//! the first free digit, so the first nine can be jumped to with `'1` to `'9`.
+
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{prompt::Prompt, shared};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    /// May start with `~`.
+    pub path: String,
+    #[serde(default)]
+    pub key: Option<char>,
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+/// What's being changed about the selected bookmark, if anything.
+pub enum Editing {
+    None,
+    /// Naming a bookmark of the current directory, to be added after the
+    /// selected one.
+    Adding(Prompt),
+    Renaming(Prompt),
+    /// Waiting for the key to give the bookmark.
+    Key,
+}
+
+pub struct Picker {
+    /// Indices of the bookmarks in the order they're listed, grouped.
+    pub order: Vec<usize>,
+    /// Which of the listed bookmarks is selected.
+    pub selection: usize,
+    pub editing: Editing,
+}
+impl Picker {
+    pub fn new(bookmarks: &[Bookmark]) -> Self {
+        Picker {
+            order: order(bookmarks),
+            selection: 0,
+            editing: Editing::None,
+        }
+    }
+    /// The index of the selected bookmark.
+    pub fn selected(&self) -> Option<usize> {
+        self.order.get(self.selection).copied()
+    }
+    /// Lists `bookmarks` again after they've changed, selecting `index`.
+    pub fn refresh(&mut self, bookmarks: &[Bookmark], index: usize) {
+        self.order = order(bookmarks);
+        self.selection = self
+            .order
+            .iter()
+            .position(|listed| *listed == index)
+            .unwrap_or(0);
+    }
+}
+
+/// The first digit from 1 to 9 no bookmark has as its key.
+pub fn free_digit(bookmarks: &[Bookmark]) -> Option<char> {
+    ('1'..='9').find(|digit| {
+        !bookmarks
+            .iter()
+            .any(|bookmark| bookmark.key == Some(*digit))
+    })
+}
+
+/// Orders bookmarks by group, in the order the groups first appear, keeping the
+/// order of the bookmarks within a group. Bookmarks without a group come first.
+fn order(bookmarks: &[Bookmark]) -> Vec<usize> {
+    let mut groups: Vec<&Option<String>> = vec![];
+    for bookmark in bookmarks {
+        if !groups.contains(&&bookmark.group) {
+            groups.push(&bookmark.group);
+        }
+    }
+    groups.sort_by_key(|group| group.is_some());
+    groups
+        .into_iter()
+        .flat_map(|group| {
+            (0..bookmarks.len()).filter(move |index| bookmarks[*index].group == *group)
+        })
+        .collect()
+}
+
+/// Brings the bookmarks in `path`, the config file or a profile, up to date with
+/// the changes made from `saved` to `ours`, on top of those other instances
+/// saved since. Returns the bookmarks as they're now saved.
+pub fn sync(path: &Path, saved: &[Bookmark], ours: &[Bookmark]) -> io::Result<Vec<Bookmark>> {
+    let _lock = shared::lock(path)?;
+    let mut config: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let theirs: Vec<Bookmark> =
+        serde_json::from_value(config["bookmarks"].take()).unwrap_or_default();
+    let merged = merge(saved, ours, &theirs);
+    if merged != theirs {
+        config["bookmarks"] = serde_json::to_value(&merged)?;
+        shared::replace(path, serde_json::to_string(&config)?.as_bytes())?;
+    }
+    Ok(merged)
+}
+
+/// Combines the changes made from `saved` to `ours` with those made from
+/// `saved` to `theirs`. Ours keep their order, and bookmarks added by both
+/// don't get the same key.
+pub fn merge(saved: &[Bookmark], ours: &[Bookmark], theirs: &[Bookmark]) -> Vec<Bookmark> {
+    let mut merged: Vec<Bookmark> = ours
+        .iter()
+        .filter(|bookmark| theirs.contains(bookmark) || !saved.contains(bookmark))
+        .cloned()
+        .collect();
+    for bookmark in theirs {
+        if saved.contains(bookmark) || merged.contains(bookmark) {
+            continue;
+        }
+        let mut bookmark = bookmark.clone();
+        if merged.iter().any(|other| other.key == bookmark.key) {
+            bookmark.key = bookmark
+                .key
+                .filter(char::is_ascii_digit)
+                .and_then(|_| free_digit(&merged));
+        }
+        merged.push(bookmark);
+    }
+    merged
+}
@@ -0,0 +1,96 @@
+//! Command line arguments. Besides fee's own, the file chooser flags of ranger
+//! and lf are accepted, so scripts written for them work with fee too.
+
+use std::path::PathBuf;
+
+use crate::replay::{self, Step};
+
+#[derive(Default)]
+pub struct Options {
+    /// Directory to start in, or file to start with selected.
+    pub start: Option<PathBuf>,
+    /// File to start with selected.
+    pub select_file: Option<PathBuf>,
+    /// When set, opening a file writes its path here and quits instead. `-`
+    /// prints it.
+    pub choose_file: Option<PathBuf>,
+    /// Extensions of the files which can be chosen, when choosing a file. Any file
+    /// can be if empty.
+    pub choose_extensions: Vec<String>,
+    /// When set, the directory fee was in is written here on exit.
+    pub choose_dir: Option<PathBuf>,
+    /// A command to send to running instances, like `send 1234 cd /tmp`.
+    pub remote: Option<String>,
+    /// Name of the profile to apply over the config.
+    pub profile: Option<String>,
+    /// Keys typed at startup.
+    pub keys: Vec<Step>,
+}
+
+pub const USAGE: &str = "usage: fee [path] [options]
+       fee config schema
+
+fee starts in the directory at path, or next to the file at path with it selected.
+
+options:
+  --choosefile=PATH    write the path of the opened file to PATH, or print it if PATH is -, and
+                       quit (also --choose-file, --pick, -selection-path)
+  --filter-ext=EXTS    only let files with these comma separated extensions be chosen
+  --choosedir=PATH     write the last directory to PATH on exit, for a shell function to cd
+                       there (also --choose-dir, -last-dir-path)
+  --selectfile=PATH    start with PATH selected
+  --profile NAME       apply the config overrides in profiles/NAME.json
+  --keys KEYS          type KEYS at startup, like `jj<enter>`, `<c-p>` or `<500ms>` to wait
+  -remote COMMAND      send COMMAND (`send [pid] command`) to running instances";
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            if options.start.is_some() {
+                return Err(format!("unexpected argument: {}", arg));
+            }
+            options.start = Some(PathBuf::from(arg));
+            continue;
+        }
+        // flags take their value either after an `=` or as the next argument
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let mut value = || {
+            value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or(format!("{} needs a value", flag))
+        };
+        match flag.trim_start_matches('-') {
+            "choosefile" | "choose-file" | "choosefiles" | "selection-path" | "pick" => {
+                options.choose_file = Some(PathBuf::from(value()?))
+            }
+            "filter-ext" => {
+                options.choose_extensions = value()?
+                    .split(',')
+                    .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+                    .filter(|extension| !extension.is_empty())
+                    .collect()
+            }
+            "choosedir" | "choose-dir" | "last-dir-path" => {
+                options.choose_dir = Some(PathBuf::from(value()?))
+            }
+            "selectfile" => options.select_file = Some(PathBuf::from(value()?)),
+            "remote" => options.remote = Some(value()?),
+            "profile" => options.profile = Some(value()?),
+            "keys" => options.keys = replay::parse(&value()?)?,
+            "h" | "help" => return Err(USAGE.to_string()),
+            _ => return Err(format!("unknown option: {}\n\n{}", flag, USAGE)),
+        }
+    }
+    if !options.choose_extensions.is_empty() && options.choose_file.is_none() {
+        return Err(
+            "--filter-ext only applies when choosing a file, with --choosefile".to_string(),
+        );
+    }
+    Ok(options)
+}
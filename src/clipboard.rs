@@ -0,0 +1,176 @@
+//! Copying and cutting files to paste them elsewhere.
+
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{fileops, shared, trash};
+
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct Clipboard {
+    pub paths: Vec<PathBuf>,
+    /// Whether the files are moved when pasted, rather than copied.
+    pub cut: bool,
+}
+
+/// The clipboard is kept in a file shared by every instance, so files can be
+/// cut in one terminal and pasted in another. It's in a directory of the user's
+/// own, so other users can't see or swap what's on it.
+fn shared_path() -> io::Result<PathBuf> {
+    let directory =
+        shared::runtime_dir().ok_or(io::Error::other("no directory to keep the clipboard in"))?;
+    Ok(directory.join("fee").join("clipboard.json"))
+}
+
+impl Clipboard {
+    /// Reads the shared clipboard, if anything's on it.
+    pub fn load() -> Option<Clipboard> {
+        let contents = fs::read_to_string(shared_path().ok()?).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+    pub fn save(&self) -> io::Result<()> {
+        shared::replace(&shared_path()?, serde_json::to_string(self)?.as_bytes())
+    }
+    /// What's being done to each file while it's pasted.
+    pub fn progress_verb(&self) -> &'static str {
+        if self.cut {
+            "moving"
+        } else {
+            "copying"
+        }
+    }
+    /// Empties the shared clipboard, unless something else has been put on it
+    /// since it held `self`.
+    pub fn clear(&self) -> io::Result<()> {
+        if Clipboard::load().as_ref() == Some(self) {
+            fs::remove_file(shared_path()?)?;
+        }
+        Ok(())
+    }
+}
+
+/// What to do when a pasted file already exists at its destination.
+#[derive(Clone, Copy)]
+pub enum Resolution {
+    Overwrite,
+    /// Paste it under a new name, like `notes (2).txt`.
+    KeepBoth,
+    Skip,
+}
+
+/// A paste in progress, which is paused whenever a conflict needs resolving.
+pub struct Paste {
+    pub pending: VecDeque<PathBuf>,
+    pub directory: PathBuf,
+    /// What was on the clipboard when the paste started.
+    pub clipboard: Clipboard,
+    /// How to resolve every further conflict, once chosen for all of them.
+    pub resolve_all: Option<Resolution>,
+    pub pasted: Vec<(PathBuf, PathBuf)>,
+    pub errors: Vec<String>,
+    /// Whether overwritten files go to the trash, rather than being deleted.
+    pub use_trash: bool,
+}
+impl Paste {
+    pub fn new(clipboard: Clipboard, directory: PathBuf, use_trash: bool) -> Self {
+        Paste {
+            pending: clipboard.paths.iter().cloned().collect(),
+            directory,
+            clipboard,
+            resolve_all: None,
+            pasted: vec![],
+            errors: vec![],
+            use_trash,
+        }
+    }
+    /// Pastes files until one conflicts with an existing file without a
+    /// resolution to apply, calling `progress` with each before it's pasted.
+    /// Returns whether everything's been pasted.
+    pub fn run(&mut self, mut progress: impl FnMut(&Path) -> io::Result<()>) -> io::Result<bool> {
+        while let Some(from) = self.pending.front().cloned() {
+            let to = fileops::destination(&from, &self.directory);
+            let resolution = if to.symlink_metadata().is_err() {
+                None
+            } else if to == from && !self.clipboard.cut {
+                // copying something next to itself obviously needs a new name
+                Some(Resolution::KeepBoth)
+            } else if to == from {
+                Some(Resolution::Skip)
+            } else if self.resolve_all.is_some() {
+                self.resolve_all
+            } else {
+                return Ok(false);
+            };
+            progress(&from)?;
+            self.paste(resolution);
+        }
+        Ok(true)
+    }
+    /// The file waiting for its conflict to be resolved.
+    pub fn conflict(&self) -> Option<&PathBuf> {
+        self.pending.front()
+    }
+    /// Pastes the next file, resolving a conflict with `resolution`.
+    pub fn paste(&mut self, resolution: Option<Resolution>) {
+        let Some(from) = self.pending.pop_front() else {
+            return;
+        };
+        let mut to = fileops::destination(&from, &self.directory);
+        let result = match resolution {
+            Some(Resolution::Skip) => return,
+            Some(Resolution::KeepBoth) => {
+                to =
+                    fileops::free_name(&self.directory, to.file_name().unwrap_or_default(), |_| {
+                        false
+                    });
+                self.transfer(&from, &to)
+            }
+            Some(Resolution::Overwrite) if from.starts_with(&to) => {
+                Err(io::Error::other("it's inside what it would overwrite"))
+            }
+            Some(Resolution::Overwrite) => self.overwrite(&from, &to),
+            None => self.transfer(&from, &to),
+        };
+        match result {
+            Ok(()) => self.pasted.push((from, to)),
+            Err(error) => self
+                .errors
+                .push(format!("couldn't paste {}: {}", from.display(), error)),
+        }
+    }
+    /// Replaces `to` with `from`. It's pasted next to `to` first, so `to` is
+    /// only removed once there's something to replace it with, and goes to the
+    /// trash if it's in use.
+    fn overwrite(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut name = to.file_name().unwrap_or_default().to_os_string();
+        name.push(".fee-paste");
+        let staged = fileops::free_name(&self.directory, &name, |_| false);
+        self.transfer(from, &staged)?;
+        let removed = if self.use_trash {
+            trash::trash(to)
+        } else {
+            fileops::delete(to)
+        };
+        if let Err(error) = removed {
+            // leave things as they were
+            let _ = if self.clipboard.cut {
+                fs::rename(&staged, from)
+            } else {
+                fileops::delete(&staged)
+            };
+            return Err(error);
+        }
+        fs::rename(staged, to)
+    }
+    fn transfer(&self, from: &Path, to: &Path) -> io::Result<()> {
+        if self.clipboard.cut {
+            fileops::move_to(from, to)
+        } else {
+            fileops::copy(from, to)
+        }
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// What kind of argument a command takes, which decides how it's completed.
+pub enum Argument {
+    None,
+    Text,
+    Path,
+    /// One of the names in a list that's only known while running.
+    Choices(Choices),
+}
+
+/// The lists of names commands take one of.
+#[derive(Clone, Copy)]
+pub enum Choices {
+    SortKeys,
+    Bookmarks,
+    /// The names marks have been saved under.
+    MarkSets,
+}
+
+pub struct Command {
+    pub name: &'static str,
+    pub argument: Argument,
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "cd",
+        argument: Argument::Path,
+    },
+    Command {
+        name: "select",
+        argument: Argument::Path,
+    },
+    Command {
+        name: "find",
+        argument: Argument::Text,
+    },
+    Command {
+        name: "grep",
+        argument: Argument::Text,
+    },
+    Command {
+        name: "files",
+        argument: Argument::None,
+    },
+    Command {
+        name: "open-all",
+        argument: Argument::None,
+    },
+    Command {
+        name: "open-with",
+        argument: Argument::None,
+    },
+    Command {
+        name: "reveal",
+        argument: Argument::None,
+    },
+    Command {
+        name: "preview",
+        argument: Argument::None,
+    },
+    Command {
+        name: "dual",
+        argument: Argument::None,
+    },
+    Command {
+        name: "back",
+        argument: Argument::None,
+    },
+    Command {
+        name: "forward",
+        argument: Argument::None,
+    },
+    Command {
+        name: "wrap",
+        argument: Argument::None,
+    },
+    Command {
+        name: "reload",
+        argument: Argument::None,
+    },
+    Command {
+        name: "sort",
+        argument: Argument::Choices(Choices::SortKeys),
+    },
+    Command {
+        name: "echo",
+        argument: Argument::Text,
+    },
+    Command {
+        name: "rename",
+        argument: Argument::None,
+    },
+    Command {
+        name: "copy",
+        argument: Argument::Path,
+    },
+    Command {
+        name: "move",
+        argument: Argument::Path,
+    },
+    Command {
+        name: "touch",
+        argument: Argument::Path,
+    },
+    Command {
+        name: "mkdir",
+        argument: Argument::Path,
+    },
+    Command {
+        name: "yank",
+        argument: Argument::None,
+    },
+    Command {
+        name: "cut",
+        argument: Argument::None,
+    },
+    Command {
+        name: "paste",
+        argument: Argument::None,
+    },
+    Command {
+        name: "copy-uri",
+        argument: Argument::None,
+    },
+    Command {
+        name: "delete",
+        argument: Argument::None,
+    },
+    Command {
+        name: "delete!",
+        argument: Argument::None,
+    },
+    Command {
+        name: "unmark",
+        argument: Argument::None,
+    },
+    Command {
+        name: "save-marks",
+        argument: Argument::Text,
+    },
+    Command {
+        name: "restore-marks",
+        argument: Argument::Choices(Choices::MarkSets),
+    },
+    Command {
+        name: "split",
+        argument: Argument::Text,
+    },
+    Command {
+        name: "join",
+        argument: Argument::None,
+    },
+    Command {
+        name: "hidden",
+        argument: Argument::None,
+    },
+    Command {
+        name: "properties",
+        argument: Argument::None,
+    },
+    Command {
+        name: "accessible",
+        argument: Argument::None,
+    },
+    Command {
+        name: "dry-run",
+        argument: Argument::None,
+    },
+    Command {
+        name: "drives",
+        argument: Argument::None,
+    },
+    Command {
+        name: "bookmarks",
+        argument: Argument::None,
+    },
+    Command {
+        name: "jump",
+        argument: Argument::Choices(Choices::Bookmarks),
+    },
+    Command {
+        name: "messages",
+        argument: Argument::None,
+    },
+    Command {
+        name: "quit",
+        argument: Argument::None,
+    },
+    Command {
+        name: "quit!",
+        argument: Argument::None,
+    },
+];
+
+/// Replaces the parts of a command that are `$f` with `file`, `$fs` with every
+/// path in `files` and `$d` with `directory`.
+pub fn expand_parts(
+    command: &[String],
+    file: Option<&Path>,
+    files: &[PathBuf],
+    directory: &Path,
+) -> VecDeque<OsString> {
+    let mut parts = VecDeque::new();
+    for part in command {
+        match part.as_str() {
+            "$f" => parts.extend(file.map(|file| file.as_os_str().to_owned())),
+            "$fs" => parts.extend(files.iter().map(|file| file.as_os_str().to_owned())),
+            "$d" => parts.push_back(directory.as_os_str().to_owned()),
+            _ => parts.push_back(part.into()),
+        }
+    }
+    parts
+}
+
+/// Replaces `$f` in a shell command with `file`, or `''` if there's none, `$fs`
+/// with every path in `files` and `$d` with `directory`, each quoted for the
+/// shell. `$$` is a `$`, and other variables, like `$foo`, are left for the
+/// shell.
+pub fn expand_shell(
+    command: &str,
+    file: Option<&Path>,
+    files: &[PathBuf],
+    directory: &Path,
+) -> String {
+    let mut expanded = String::new();
+    let mut rest = command;
+    while let Some(dollar) = rest.find('$') {
+        expanded.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+        let length = rest
+            .find(|char: char| !char.is_ascii_alphanumeric() && char != '_')
+            .unwrap_or(rest.len());
+        let (name, after) = rest.split_at(length);
+        match name {
+            "fs" => {
+                let quoted: Vec<String> = files.iter().map(|file| quote(file)).collect();
+                expanded.push_str(&quoted.join(" "));
+            }
+            "f" => expanded.push_str(&quote(file.unwrap_or(Path::new("")))),
+            "d" => expanded.push_str(&quote(directory)),
+            "" if rest.starts_with('$') => {
+                expanded.push('$');
+                rest = &rest[1..];
+                continue;
+            }
+            _ => {
+                expanded.push('$');
+                continue;
+            }
+        }
+        rest = after;
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Quotes `path` so the shell takes it as a single word.
+fn quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        format!("\"{}\"", path)
+    } else {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    }
+}
+
+/// Splits a command line into the command name and its (possibly empty) argument.
+pub fn parse(input: &str) -> (&str, &str) {
+    let input = input.trim_start();
+    match input.split_once(' ') {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (input, ""),
+    }
+}
+
+/// Expands `~` and makes `path` absolute relative to `cwd`.
+pub fn resolve_path(path: &str, cwd: &Path) -> PathBuf {
+    let expanded = match path.strip_prefix('~') {
+        Some(rest) => match dirs::home_dir() {
+            Some(home) => home.join(rest.trim_start_matches('/')),
+            None => PathBuf::from(path),
+        },
+        None => PathBuf::from(path),
+    };
+    cwd.join(expanded)
+}
+
+/// A completion candidate: the full command line it completes to, and how it's
+/// shown in the completion menu.
+pub struct Candidate {
+    pub line: String,
+    pub label: String,
+}
+
+/// Returns the possible completions of the command line `input`, taking the
+/// names of the lists of choices from `choices`.
+pub fn complete(
+    input: &str,
+    cwd: &Path,
+    choices: impl Fn(Choices) -> Vec<String>,
+) -> Vec<Candidate> {
+    let input = input.trim_start();
+    let Some((name, argument)) = input.split_once(' ') else {
+        return COMMANDS
+            .iter()
+            .filter(|command| command.name.starts_with(input))
+            .map(|command| Candidate {
+                line: format!("{} ", command.name),
+                label: command.name.to_string(),
+            })
+            .collect();
+    };
+    let Some(command) = COMMANDS.iter().find(|command| command.name == name) else {
+        return vec![];
+    };
+    match command.argument {
+        Argument::Path => complete_path(argument, cwd)
+            .into_iter()
+            .map(|(path, label)| Candidate {
+                line: format!("{} {}", name, path),
+                label,
+            })
+            .collect(),
+        Argument::Choices(list) => {
+            let mut names: Vec<String> = choices(list)
+                .into_iter()
+                .filter(|choice| choice.starts_with(argument))
+                .collect();
+            names.sort();
+            names.dedup();
+            names
+                .into_iter()
+                .map(|choice| Candidate {
+                    line: format!("{} {}", name, choice),
+                    label: choice,
+                })
+                .collect()
+        }
+        Argument::None | Argument::Text => vec![],
+    }
+}
+
+/// Completes the last component of `partial`, returning the completed paths and
+/// the names of the entries they point at.
+fn complete_path(partial: &str, cwd: &Path) -> Vec<(String, String)> {
+    if partial == "~" {
+        return vec![("~/".to_string(), "~/".to_string())];
+    }
+    let (directory, prefix) = match partial.rfind('/') {
+        Some(index) => (&partial[..index + 1], &partial[index + 1..]),
+        None => ("", partial),
+    };
+    let search_directory = if directory.is_empty() {
+        cwd.to_path_buf()
+    } else {
+        resolve_path(directory, cwd)
+    };
+    let Ok(entries) = fs::read_dir(search_directory) else {
+        return vec![];
+    };
+    let mut candidates: Vec<(String, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let mut name = entry.file_name().to_str()?.to_string();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            if entry.path().is_dir() {
+                name.push('/');
+            }
+            Some((format!("{}{}", directory, name), name))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// Returns the longest prefix shared by all `lines`.
+pub fn common_prefix(lines: &[&str]) -> String {
+    let Some(first) = lines.first() else {
+        return String::new();
+    };
+    let mut prefix: String = first.to_string();
+    for line in &lines[1..] {
+        let length = prefix
+            .chars()
+            .zip(line.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(char, _)| char.len_utf8())
+            .sum();
+        prefix.truncate(length);
+    }
+    prefix
+}
+
+/// The completions shown above the command line after pressing tab.
+pub struct Menu {
+    pub candidates: Vec<Candidate>,
+    /// The candidate currently filled into the command line, if any.
+    pub selected: Option<usize>,
+}
+impl Menu {
+    /// Moves the selection to the next (or previous) candidate, wrapping around,
+    /// and returns the line it completes to.
+    pub fn cycle(&mut self, backwards: bool) -> &str {
+        let count = self.candidates.len();
+        let index = match (self.selected, backwards) {
+            (None, false) => 0,
+            (None, true) => count - 1,
+            (Some(index), false) => (index + 1) % count,
+            (Some(index), true) => (index + count - 1) % count,
+        };
+        self.selected = Some(index);
+        &self.candidates[index].line
+    }
+}
@@ -0,0 +1,41 @@
+//! The CRC-32 used by zip, PNG and `.sfv` files.
+
+pub struct Crc32 {
+    value: u32,
+}
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32 { value: 0xFFFFFFFF }
+    }
+}
+impl Crc32 {
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.value =
+                CRC_TABLE[((self.value ^ *byte as u32) & 0xFF) as usize] ^ (self.value >> 8);
+        }
+    }
+    pub fn finish(&self) -> u32 {
+        !self.value
+    }
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut value = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 == 1 {
+                0xEDB88320 ^ (value >> 1)
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[index] = value;
+        index += 1;
+    }
+    table
+};
@@ -0,0 +1,326 @@
+//! Counting the items in a directory and adding up its total size, shown in the
+//! status bar once the cursor has rested on the directory for a while. What was
+//! found is kept in a cache on disk, so the size column of the details view can
+//! show sizes of directories right away, even in later runs.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ignore::Ignore, shared};
+
+/// How long the worker has to be idle before it saves what it's added up.
+const SAVE_DELAY: Duration = Duration::from_secs(5);
+
+/// How many directories are cached at most. Those added up longest ago are
+/// dropped first.
+const MAX_CACHED: usize = 50_000;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Stat {
+    /// Items directly in the directory.
+    pub items: usize,
+    /// Size of everything in it, recursively.
+    pub size: u64,
+}
+
+/// What was found in a directory, cached on disk so adding it up again only
+/// reads the directories modified since. Growing a file doesn't modify the
+/// directory it's in, so sizes can be off until something in it is added,
+/// removed or renamed.
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    /// Modification time of the directory, in nanoseconds since the epoch.
+    modified: u128,
+    /// Whether ignored entries were left out.
+    ignoring: bool,
+    /// Size of the files directly in it.
+    files: u64,
+    /// The names of the directories directly in it.
+    directories: Vec<PathBuf>,
+    /// Its stats as last added up.
+    stat: Stat,
+    /// When it was last added up, in seconds since the epoch.
+    #[serde(default)]
+    added_up: u64,
+}
+
+/// Cached entries, read from disk by the worker once it starts, so the cache is
+/// empty until then.
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<PathBuf, Entry>,
+    /// Entries added up since the cache was last saved.
+    changed: HashSet<PathBuf>,
+}
+impl Cache {
+    fn insert(&mut self, path: PathBuf, entry: Entry) {
+        self.changed.insert(path.clone());
+        self.entries.insert(path, entry);
+    }
+    /// Takes the entries added up since the cache was last saved.
+    fn take_changed(&mut self) -> HashMap<PathBuf, Entry> {
+        std::mem::take(&mut self.changed)
+            .into_iter()
+            .filter_map(|path| {
+                let entry = self.entries.get(&path)?.clone();
+                Some((path, entry))
+            })
+            .collect()
+    }
+}
+
+/// Writes the `changed` entries over the ones on disk, which other instances
+/// may have added to since they were read, dropping those of directories that
+/// are gone.
+fn save(changed: HashMap<PathBuf, Entry>) -> io::Result<()> {
+    let Some(path) = cache_path() else {
+        return Ok(());
+    };
+    if changed.is_empty() {
+        return Ok(());
+    }
+    let _lock = shared::lock(&path)?;
+    let mut entries = read();
+    entries.extend(changed);
+    entries.retain(|directory, _| directory.is_dir());
+    prune(&mut entries);
+    // JSON can only hold paths that are valid UTF-8
+    let saved: HashMap<&PathBuf, &Entry> = entries
+        .iter()
+        .filter(|(path, entry)| {
+            path.to_str().is_some() && entry.directories.iter().all(|name| name.to_str().is_some())
+        })
+        .collect();
+    shared::replace(&path, serde_json::to_string(&saved)?.as_bytes())
+}
+
+/// Drops the entries added up longest ago, if there are more than `MAX_CACHED`.
+fn prune(entries: &mut HashMap<PathBuf, Entry>) {
+    if entries.len() <= MAX_CACHED {
+        return;
+    }
+    let mut sorted: Vec<_> = std::mem::take(entries).into_iter().collect();
+    sorted.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.added_up));
+    sorted.truncate(MAX_CACHED);
+    *entries = sorted.into_iter().collect();
+}
+
+/// The entries cached on disk.
+fn read() -> HashMap<PathBuf, Entry> {
+    cache_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn cache_path() -> Option<PathBuf> {
+    Some(
+        shared::cache_dir()?
+            .join("fee")
+            .join("directory-sizes.json"),
+    )
+}
+
+/// A directory to add up, and what to leave out of it.
+type Request = (PathBuf, Option<Arc<Ignore>>);
+
+/// Computes stats on a background thread, since adding up big trees takes a while.
+pub struct Worker {
+    requests: Sender<Request>,
+    results: Receiver<(PathBuf, Option<Stat>)>,
+    cache: Arc<Mutex<Cache>>,
+}
+impl Worker {
+    pub fn spawn() -> Self {
+        let (requests, pending) = mpsc::channel::<Request>();
+        let (finished, results) = mpsc::channel();
+        let cache = Arc::new(Mutex::new(Cache::default()));
+        let shared = cache.clone();
+        thread::spawn(move || {
+            let entries = read();
+            if let Ok(mut cache) = shared.lock() {
+                cache.entries = entries;
+            }
+            // saved without holding the lock, which drawing needs
+            let save_changes = || {
+                let changed = shared.lock().map(|mut cache| cache.take_changed());
+                let _ = save(changed.unwrap_or_default());
+                if let Ok(mut cache) = shared.lock() {
+                    prune(&mut cache.entries);
+                }
+            };
+            let mut next = None;
+            loop {
+                let mut request = match next.take() {
+                    Some(request) => request,
+                    None => match pending.recv_timeout(SAVE_DELAY) {
+                        Ok(request) => request,
+                        Err(RecvTimeoutError::Timeout) => {
+                            save_changes();
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    },
+                };
+                // only the newest request matters, older ones were scrolled past
+                while let Ok(newer) = pending.try_recv() {
+                    request = newer;
+                }
+                let (path, ignore) = request;
+                let mut walk = Walk {
+                    cache: &shared,
+                    pending: &pending,
+                    newer: None,
+                    device: None,
+                };
+                let stat = walk.stat(&path, ignore);
+                if let Some(newer) = walk.newer {
+                    // given up on, so nothing's sent for it
+                    next = Some(newer);
+                    continue;
+                }
+                if finished.send((path, stat)).is_err() {
+                    break;
+                }
+            }
+            save_changes();
+        });
+        Worker {
+            requests,
+            results,
+            cache,
+        }
+    }
+    /// Starts adding up `path`, leaving out what `ignore` matches.
+    pub fn request(&self, path: PathBuf, ignore: Option<Arc<Ignore>>) {
+        let _ = self.requests.send((path, ignore));
+    }
+    /// A directory added up, with its stats, or `None` if it couldn't be read.
+    pub fn try_receive(&self) -> Option<(PathBuf, Option<Stat>)> {
+        self.results.try_recv().ok()
+    }
+    /// The stats `path` had when last added up, possibly in an earlier run,
+    /// without checking whether they're still right.
+    pub fn cached(&self, path: &Path, ignoring: bool) -> Option<Stat> {
+        let cache = self.cache.lock().ok()?;
+        let entry = cache.entries.get(path)?;
+        (entry.ignoring == ignoring).then_some(entry.stat)
+    }
+}
+
+fn modified(metadata: &fs::Metadata) -> Option<u128> {
+    let modified = metadata.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+/// The filesystem `metadata` is of.
+#[cfg(unix)]
+fn device(metadata: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::dev(metadata)
+}
+
+#[cfg(not(unix))]
+fn device(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Adding up a directory, which is given up on once a newer request comes in.
+struct Walk<'a> {
+    cache: &'a Mutex<Cache>,
+    pending: &'a Receiver<Request>,
+    /// The request that came in while adding up, if one did.
+    newer: Option<Request>,
+    /// The filesystem of the directory being added up, the only one walked.
+    device: Option<u64>,
+}
+impl Walk<'_> {
+    /// Adds up `directory`, reading only the directories in it modified since
+    /// they were cached. Symlinks aren't followed, so nothing is counted twice,
+    /// and other filesystems mounted inside aren't counted at all.
+    fn stat(&mut self, directory: &Path, ignore: Option<Arc<Ignore>>) -> Option<Stat> {
+        if self.newer.is_none() {
+            self.newer = self.pending.try_recv().ok();
+        }
+        if self.newer.is_some() {
+            return None;
+        }
+        let metadata = directory.symlink_metadata().ok()?;
+        if *self.device.get_or_insert(device(&metadata)) != device(&metadata) {
+            return None;
+        }
+        let modified = modified(&metadata)?;
+        let ignoring = ignore.is_some();
+        let cached = self.cache.lock().ok().and_then(|cache| {
+            cache
+                .entries
+                .get(directory)
+                .filter(|entry| entry.modified == modified && entry.ignoring == ignoring)
+                .cloned()
+        });
+        let (items, files, directories) = match cached {
+            Some(entry) => (entry.stat.items, entry.files, entry.directories),
+            None => list(directory, ignore.as_deref())?,
+        };
+        let mut size = files;
+        for name in &directories {
+            // joining replaces the directory with paths cached whole by older versions
+            let path = &directory.join(name);
+            let ignore = ignore.as_ref().map(|ignore| ignore.child(path));
+            size += self.stat(path, ignore).map_or(0, |stat| stat.size);
+        }
+        // what's below wasn't all added up
+        if self.newer.is_some() {
+            return None;
+        }
+        let stat = Stat { items, size };
+        if let Ok(mut cache) = self.cache.lock() {
+            let added_up = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs());
+            let entry = Entry {
+                modified,
+                ignoring,
+                files,
+                directories,
+                stat,
+                added_up,
+            };
+            cache.insert(directory.to_path_buf(), entry);
+        }
+        Some(stat)
+    }
+}
+
+/// Reads `directory`, returning the number of items in it, the size of the files
+/// directly in it, and the names of the directories in it.
+fn list(directory: &Path, ignore: Option<&Ignore>) -> Option<(usize, u64, Vec<PathBuf>)> {
+    let mut items = 0;
+    let mut files = 0;
+    let mut directories = vec![];
+    for entry in fs::read_dir(directory).ok()?.flatten() {
+        let Ok(metadata) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        if ignore.is_some_and(|ignore| ignore.is_ignored(&entry.path(), metadata.is_dir())) {
+            continue;
+        }
+        items += 1;
+        if metadata.is_dir() {
+            directories.push(PathBuf::from(entry.file_name()));
+        } else {
+            files += metadata.len();
+        }
+    }
+    Some((items, files, directories))
+}
@@ -0,0 +1,1134 @@
+//! Drawing the screen: the listings, the preview, the bottom line and the
+//! status bar, and the views that take over the whole screen.
+
+use std::{
+    cmp,
+    io::{self, Write},
+    time::{Instant, SystemTime},
+};
+
+use crossterm::{
+    cursor, queue,
+    style::{
+        Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor,
+    },
+    terminal::{Clear, ClearType},
+};
+
+use crate::{
+    bookmarks, graphics, human_size, keymap,
+    mode::Mode,
+    preview::{self, Line},
+    properties, rename, rgb,
+    rules::{self, AgeHeat},
+    search::SearchKind,
+    statusbar,
+    theme::BorderChars,
+    truncate, Fee, Item, ItemType, Layout, Orientation, Rect, COLUMN_GAP, MAX_MENU_HEIGHT,
+    MIN_HEIGHT_FOR_PREVIEW, MIN_NAME_WIDTH, MIN_WIDTH_FOR_DUAL_PANE, MIN_WIDTH_FOR_PREVIEW,
+};
+
+impl Fee {
+    pub fn update(&mut self) -> io::Result<()> {
+        self.schedule_directory_stat();
+        if let Some(protocol) = self.graphics {
+            graphics::clear(&mut self.backend, protocol)?;
+        }
+        queue!(
+            self.backend,
+            Clear(ClearType::All),
+            cursor::MoveTo(0, 0),
+            ResetColor
+        )?;
+        self.focus = (0, 0);
+        if let Mode::QuickLook { .. } = self.mode {
+            self.draw_quick_look()?;
+        } else if let Mode::Drives { .. } = self.mode {
+            self.draw_drives()?;
+        } else if let Mode::Bookmarks(_) = self.mode {
+            self.draw_bookmarks()?;
+        } else if let Mode::Finder(_) = self.mode {
+            self.draw_finder()?;
+        } else if let Mode::BatchRename(_) = self.mode {
+            self.draw_batch_rename()?;
+        } else if let Mode::Attributes(_) = self.mode {
+            self.draw_attributes()?;
+        } else {
+            self.draw_borders()?;
+            self.draw_text()?;
+            // too narrow for both panes, it's like there's only one
+            if self.layout()?.dual {
+                self.draw_other_pane()?;
+            } else {
+                self.draw_preview()?;
+            }
+            if self.config.status_bar.own_line {
+                let (width, height) = self.backend.size()?;
+                let y = height.saturating_sub(self.reserved_rows());
+                let text = self.status_bar(width as usize);
+                self.print_line(&text, 0, y, Color::Reset, true)?;
+            }
+            if self.config.accessible {
+                self.draw_announcement()?;
+            }
+        }
+        self.draw_bottom_line()?;
+        if self.config.accessible {
+            // screen readers follow the cursor
+            queue!(
+                self.backend,
+                cursor::MoveTo(self.focus.0, self.focus.1),
+                cursor::Show
+            )?;
+        } else {
+            queue!(self.backend, cursor::MoveTo(0, 0), cursor::Hide)?;
+        }
+        self.backend.flush()?;
+        Ok(())
+    }
+    fn print_line(
+        &mut self,
+        text: &str,
+        x: u16,
+        y: u16,
+        color: Color,
+        highlighted: bool,
+    ) -> io::Result<()> {
+        queue!(self.backend, cursor::MoveTo(x, y))?;
+        queue!(self.backend, SetForegroundColor(color))?;
+        if highlighted {
+            queue!(self.backend, SetBackgroundColor(Color::White))?;
+            queue!(self.backend, SetForegroundColor(Color::Black))?;
+        }
+        queue!(self.backend, Print(text))?;
+        if highlighted {
+            queue!(self.backend, SetBackgroundColor(Color::Reset))?;
+        }
+        Ok(())
+    }
+    /// Prints the spans of `line` in their colors, cut off after `width` characters.
+    fn print_styled_line(&mut self, line: &Line, x: u16, y: u16, width: usize) -> io::Result<()> {
+        queue!(self.backend, cursor::MoveTo(x, y))?;
+        let mut remaining = width;
+        for (text, color) in &line.spans {
+            if remaining == 0 {
+                break;
+            }
+            let text = truncate(text, remaining);
+            remaining -= text.chars().count();
+            queue!(self.backend, SetForegroundColor(*color))?;
+            queue!(self.backend, Print(text))?;
+        }
+        queue!(self.backend, ResetColor)?;
+        Ok(())
+    }
+    fn draw_text(&mut self) -> io::Result<()> {
+        let list = self.layout()?.list;
+        self.draw_list(list, true)
+    }
+    /// Draws the inactive pane next to the active one, in dual pane mode.
+    fn draw_other_pane(&mut self) -> io::Result<()> {
+        let Layout {
+            side: Some(area),
+            dual: true,
+            ..
+        } = self.layout()?
+        else {
+            return Ok(());
+        };
+        self.swap_panes();
+        let result = self.draw_list(area, false);
+        self.swap_panes();
+        result
+    }
+    /// Draws the listing in `list`. Only the active pane shows the selection,
+    /// and is affected by what's being typed.
+    fn draw_list(&mut self, list: Rect, active: bool) -> io::Result<()> {
+        let dir_color = Color::Rgb {
+            r: self.config.dir_color[0],
+            g: self.config.dir_color[1],
+            b: self.config.dir_color[2],
+        };
+        let file_color = Color::Rgb {
+            r: self.config.file_color[0],
+            g: self.config.file_color[1],
+            b: self.config.file_color[2],
+        };
+        let link_color = rgb(self.config.link_color);
+        let special_color = rgb(self.config.special_color);
+        let width = list.width as usize;
+        if self.layout()?.dual {
+            // each pane is headed by its directory
+            let title = match &self.virtual_view {
+                Some(view) => view.title.clone(),
+                None => self.cwd.display().to_string(),
+            };
+            let title = format!("{:width$}", truncate(&title, width));
+            self.print_line(&title, list.x, list.y - 1, Color::Reset, active)?;
+        }
+        // which entries are listed in each row, and which row is selected
+        let height = list.height as usize;
+        let (visible, selection, scroll): (Vec<(usize, usize)>, usize, usize) = match &self.mode {
+            Mode::Filter(filter) if active => {
+                let scroll = filter.selection.saturating_sub(height.saturating_sub(1));
+                let visible = filter
+                    .matches
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .skip(scroll)
+                    .take(height)
+                    .collect();
+                (visible, filter.selection, scroll)
+            }
+            // only the visible window is looked at, however long the listing
+            _ => {
+                let end = cmp::min(self.scroll + height, self.current_contents.len());
+                let visible = (self.scroll..end).map(|index| (index, index)).collect();
+                (visible, self.selection, self.scroll)
+            }
+        };
+        if active {
+            self.request_metadata(visible.iter().map(|(_, index)| *index));
+        }
+        if self.current_contents.is_empty() {
+            if active {
+                self.focus = (list.x, list.y);
+            }
+            let notice = truncate("<empty directory>", width);
+            return self.print_line(&notice, list.x, list.y, Color::DarkGrey, false);
+        }
+        // the columns next to the names are as wide as their widest visible text,
+        // and the last ones are left out when the names would get too cramped
+        let columns: Vec<Vec<String>> = visible
+            .iter()
+            .map(|(_, index)| self.columns(&self.current_contents[*index]))
+            .collect();
+        let mut widths: Vec<usize> = (0..columns.first().map_or(0, Vec::len))
+            .map(|column| {
+                columns
+                    .iter()
+                    .map(|texts| texts[column].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let block_width = |widths: &[usize]| -> usize {
+            widths
+                .iter()
+                .filter(|width| **width > 0)
+                .map(|width| width + COLUMN_GAP)
+                .sum()
+        };
+        while !widths.is_empty() && block_width(&widths) + MIN_NAME_WIDTH > width {
+            widths.pop();
+        }
+        let block_width = block_width(&widths);
+        for ((row, index), texts) in visible.into_iter().zip(columns) {
+            let item = &self.current_contents[index];
+            let mut label = item.name.clone();
+            let mut color = dir_color;
+
+            if let ItemType::Special(special) = item.item_type {
+                if !self.config.accessible {
+                    label.push(special.symbol());
+                }
+                color = special_color;
+            } else if item.is_link {
+                color = link_color;
+            } else if item.is_file() && self.choose_file.is_some() && !self.choosable(&item.path) {
+                color = Color::DarkGrey;
+            } else if item.is_file() {
+                color = file_color;
+            }
+            let heat = self.age_heat(item);
+            if let (Some(heat), AgeHeat::Name) = (heat, self.config.age_heat) {
+                color = heat;
+            }
+            let style = self.color_rule(item).map(|rule| rule.style.clone());
+            if let Some(style) = &style {
+                if let Some(rule_color) = style.color {
+                    color = rgb(rule_color);
+                }
+                if let Some(icon) = &style.icon {
+                    label = format!("{}{}", icon, label);
+                }
+                for (enabled, attribute) in [
+                    (style.bold, Attribute::Bold),
+                    (style.italic, Attribute::Italic),
+                    (style.underline, Attribute::Underlined),
+                ] {
+                    if enabled {
+                        queue!(self.backend, SetAttribute(attribute))?;
+                    }
+                }
+            }
+            if self.marks.contains(&item.path) {
+                label = format!("+ {}", label);
+            }
+            let name = truncate(&label, width - block_width);
+            let y = list.y + (row - scroll) as u16;
+            let highlighted = active && selection == row;
+            if highlighted {
+                self.focus = (list.x, y);
+            }
+            self.print_line(&name, list.x, y, color, highlighted)?;
+            if style.is_some() {
+                queue!(self.backend, SetAttribute(Attribute::Reset))?;
+            }
+            let mut x = list.x + (width - block_width) as u16;
+            for (column, (text, column_width)) in texts.iter().zip(&widths).enumerate() {
+                if *column_width == 0 {
+                    continue;
+                }
+                // the modification time is the second column of the details
+                let color = match heat {
+                    Some(heat) if column == 1 && self.config.age_heat == AgeHeat::Time => heat,
+                    _ => Color::DarkGrey,
+                };
+                let text = format!("{:>1$}", text, column_width + COLUMN_GAP);
+                self.print_line(&text, x, y, color, highlighted)?;
+                x += (column_width + COLUMN_GAP) as u16;
+            }
+        }
+        queue!(self.backend, ResetColor)?;
+        if let (Mode::Rename(_, prompt), true) = (&self.mode, active) {
+            if self.selection >= self.scroll && self.selection < self.scroll + height {
+                let (text, cursor) = (prompt.text(), prompt.cursor());
+                let y = list.y + (self.selection - self.scroll) as u16;
+                self.draw_input(&text, Some(cursor), list.x, y, width)?;
+            }
+        }
+        Ok(())
+    }
+    /// The first of the `color_rules` matching `item`, if any.
+    fn color_rule(&self, item: &Item) -> Option<&rules::Rule> {
+        if self.color_rules.is_empty() {
+            return None;
+        }
+        let kind = match item.item_type {
+            _ if item.is_link => rules::Kind::Link,
+            ItemType::Directory => rules::Kind::Directory,
+            ItemType::Special(_) => rules::Kind::Special,
+            _ => rules::Kind::File,
+        };
+        let metadata = self.metadata.get(&item.path);
+        let size = item.size.or(metadata.map(|metadata| metadata.size));
+        let modified = self.modified(item);
+        let now = SystemTime::now();
+        self.color_rules
+            .iter()
+            .find(|rule| rule.matches(&item.name, kind, size, modified, now))
+    }
+    /// The color `item` is tinted by how recently it was modified, if it's known
+    /// and `age_heat` is on.
+    fn age_heat(&self, item: &Item) -> Option<Color> {
+        if self.config.age_heat == AgeHeat::Off {
+            return None;
+        }
+        let [recent, old] = self.config.age_heat_colors;
+        let modified = self.modified(item)?;
+        Some(rgb(rules::age_color(
+            modified,
+            SystemTime::now(),
+            recent,
+            old,
+        )))
+    }
+    /// When `item` was modified, if it's been read yet.
+    fn modified(&self, item: &Item) -> Option<SystemTime> {
+        item.modified.or(self
+            .metadata
+            .get(&item.path)
+            .and_then(|metadata| metadata.modified))
+    }
+    /// The texts of the columns shown next to the name of `item`: its size,
+    /// modification time and permissions in the details view, and the last
+    /// commit touching it with the git column. Unknown ones are empty.
+    fn columns(&self, item: &Item) -> Vec<String> {
+        let mut columns = vec![];
+        if self.view.details {
+            let metadata = self.metadata.get(&item.path);
+            // the size of a directory itself says nothing, so it's only shown
+            // once it's been added up, now or in an earlier run
+            let size = match item.item_type {
+                ItemType::Directory => self
+                    .directory_stats
+                    .get(&item.path)
+                    .copied()
+                    .flatten()
+                    .or_else(|| {
+                        self.directory_stat_worker
+                            .cached(&item.path, self.ignoring())
+                    })
+                    .map(|stat| human_size(stat.size)),
+                _ => metadata.map(|metadata| human_size(metadata.size)),
+            };
+            columns.push(size.unwrap_or_default());
+            columns.push(
+                metadata
+                    .and_then(|metadata| metadata.modified)
+                    .map(properties::format_short_time)
+                    .unwrap_or_default(),
+            );
+            columns.push(
+                metadata
+                    .map(|metadata| metadata.permissions.clone())
+                    .unwrap_or_default(),
+            );
+        }
+        if self.config.git_column {
+            let commit = self
+                .git_commits
+                .get(&self.cwd)
+                .filter(|_| self.virtual_view.is_none())
+                .and_then(|commits| commits.get(&item.name))
+                .map(|commit| format!("{} {}", commit.date, commit.hash));
+            columns.push(commit.unwrap_or_default());
+        }
+        columns
+    }
+    /// Splits the screen above the bottom line between the file listing and the
+    /// other pane or the preview pane, if they're shown.
+    pub fn layout(&self) -> io::Result<Layout> {
+        let (width, height) = self.backend.size()?;
+        let height = height.saturating_sub(self.reserved_rows());
+        let full = if self.border_chars()?.is_some() {
+            Rect {
+                x: 1,
+                y: 1,
+                width: width.saturating_sub(2),
+                height: height.saturating_sub(2),
+            }
+        } else {
+            Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }
+        };
+        if self.other_pane.is_some() && full.width as usize >= MIN_WIDTH_FOR_DUAL_PANE {
+            // the first row of each pane is its directory
+            let left = Rect {
+                y: full.y + 1,
+                width: (full.width - 1) / 2,
+                height: full.height.saturating_sub(1),
+                ..full
+            };
+            let right = Rect {
+                x: left.x + left.width + 1,
+                width: full.width - left.width - 1,
+                ..left
+            };
+            let (list, side) = if self.right_pane_active {
+                (right, left)
+            } else {
+                (left, right)
+            };
+            return Ok(Layout {
+                list,
+                side: Some(side),
+                dual: true,
+            });
+        }
+        let ratio = self.config.preview_ratio.clamp(0.1, 0.9);
+        let single = |list, preview| Layout {
+            list,
+            side: preview,
+            dual: false,
+        };
+        if !self.show_preview {
+            return Ok(single(full, None));
+        }
+        match self.config.preview_orientation {
+            Orientation::Right if full.width as usize >= MIN_WIDTH_FOR_PREVIEW => {
+                let preview_width = (full.width as f32 * ratio) as u16;
+                let list = Rect {
+                    width: full.width - preview_width - 1,
+                    ..full
+                };
+                let preview = Rect {
+                    x: full.x + full.width - preview_width,
+                    width: preview_width,
+                    ..full
+                };
+                Ok(single(list, Some(preview)))
+            }
+            Orientation::Bottom if full.height as usize >= MIN_HEIGHT_FOR_PREVIEW => {
+                let preview_height = (full.height as f32 * ratio) as u16;
+                let list = Rect {
+                    height: full.height - preview_height - 1,
+                    ..full
+                };
+                let preview = Rect {
+                    y: full.y + full.height - preview_height,
+                    height: preview_height,
+                    ..full
+                };
+                Ok(single(list, Some(preview)))
+            }
+            _ => Ok(single(full, None)),
+        }
+    }
+    /// Returns the characters to draw borders with, unless they're turned off or
+    /// the terminal is too small to fit them.
+    fn border_chars(&self) -> io::Result<Option<BorderChars>> {
+        let (width, height) = self.backend.size()?;
+        if width < 3 || height < 4 || self.config.accessible {
+            return Ok(None);
+        }
+        Ok(self.config.theme.border_style.chars())
+    }
+    /// How many rows at the bottom of the screen aren't for the listing: the
+    /// bottom line, the status line if it has its own, and in accessible mode the
+    /// announcement line.
+    fn reserved_rows(&self) -> u16 {
+        1 + self.config.status_bar.own_line as u16 + self.config.accessible as u16
+    }
+    fn draw_borders(&mut self) -> io::Result<()> {
+        let Some(chars) = self.border_chars()? else {
+            return Ok(());
+        };
+        let Layout {
+            list,
+            side: preview,
+            ..
+        } = self.layout()?;
+        let (width, height) = self.backend.size()?;
+        let bottom = height.saturating_sub(self.reserved_rows() + 1);
+        let color = rgb(self.config.theme.border_color);
+
+        let mut top = vec![chars.horizontal; width as usize];
+        top[0] = chars.top_left;
+        top[width as usize - 1] = chars.top_right;
+        let mut bottom_row = vec![chars.horizontal; width as usize];
+        bottom_row[0] = chars.bottom_left;
+        bottom_row[width as usize - 1] = chars.bottom_right;
+        let mut separator_row = None;
+
+        if let Some(preview) = preview {
+            if preview.y == list.y {
+                // side by side, separated by a column
+                let x = cmp::min(list.x + list.width, preview.x + preview.width);
+                top[x as usize] = chars.tee_down;
+                bottom_row[x as usize] = chars.tee_up;
+                for y in 1..bottom {
+                    self.print_line(&chars.vertical.to_string(), x, y, color, false)?;
+                }
+            } else {
+                // stacked, separated by a row
+                let mut row = vec![chars.horizontal; width as usize];
+                row[0] = chars.tee_right;
+                row[width as usize - 1] = chars.tee_left;
+                separator_row = Some((list.y + list.height, row));
+            }
+        }
+        self.print_line(&top.iter().collect::<String>(), 0, 0, color, false)?;
+        self.print_line(
+            &bottom_row.iter().collect::<String>(),
+            0,
+            bottom,
+            color,
+            false,
+        )?;
+        for y in 1..bottom {
+            let vertical = chars.vertical.to_string();
+            self.print_line(&vertical, 0, y, color, false)?;
+            self.print_line(&vertical, width - 1, y, color, false)?;
+        }
+        if let Some((y, row)) = separator_row {
+            self.print_line(&row.iter().collect::<String>(), 0, y, color, false)?;
+        }
+        Ok(())
+    }
+    /// Describes the selected item, and the directory when it has changed, on the
+    /// line above the bottom line.
+    fn draw_announcement(&mut self) -> io::Result<()> {
+        let mut announcement = String::new();
+        if self.entered_directory {
+            let count = self.current_contents.len();
+            announcement = format!(
+                "in {}, {} {}. ",
+                self.cwd.display(),
+                count,
+                if count == 1 { "item" } else { "items" }
+            );
+        }
+        match self.selected_item() {
+            Some(item) => {
+                let kind = match item.item_type {
+                    ItemType::File if item.is_link => "link to file",
+                    ItemType::Directory if item.is_link => "link to directory",
+                    ItemType::File => "file",
+                    ItemType::Directory => "directory",
+                    ItemType::Package => "package",
+                    ItemType::Special(special) => special.name(),
+                };
+                announcement += &format!(
+                    "{} {}, {} of {}",
+                    kind,
+                    item.name,
+                    self.selection + 1,
+                    self.current_contents.len()
+                );
+            }
+            None => announcement += "empty",
+        }
+        let width = self.backend.size()?.0 as usize;
+        let y = self.terminal_height()?.saturating_sub(1);
+        self.print_line(&truncate(&announcement, width), 0, y, Color::Reset, false)
+    }
+    /// The number of rows above the bottom line.
+    pub fn terminal_height(&self) -> io::Result<u16> {
+        Ok(self.backend.size()?.1.saturating_sub(1))
+    }
+    pub fn list_height(&self) -> io::Result<u16> {
+        Ok(self.layout()?.list.height)
+    }
+    fn draw_preview(&mut self) -> io::Result<()> {
+        let Some(area) = self.layout()?.side else {
+            return Ok(());
+        };
+        let Some(item) = self.selected_item() else {
+            return Ok(());
+        };
+        let show_hidden = self.view.show_hidden;
+        let Ok(key) = preview::CacheKey::new(&item.path, area.height as usize, show_hidden) else {
+            return Ok(());
+        };
+        let Some(preview) = self.preview_cache.get(&key) else {
+            let pending = self.pending_preview.as_ref().map(|(key, _)| key);
+            if self.requested_preview.as_ref() != Some(&key) && pending != Some(&key) {
+                self.pending_preview = Some((key, Instant::now()));
+            }
+            return self.print_line("…", area.x, area.y, Color::DarkGrey, false);
+        };
+        let width = area.width as usize;
+        let lines = preview.layout(width, self.wrap_previews);
+        for (y, line) in lines.iter().take(area.height as usize).enumerate() {
+            self.print_styled_line(line, area.x, area.y + y as u16, width)?;
+        }
+        if let (Some(image), Some(protocol)) = (&preview.image, self.graphics) {
+            let y = lines.len() as u16 + 1;
+            if y < area.height && protocol.shows_png() {
+                queue!(self.backend, cursor::MoveTo(area.x, area.y + y))?;
+                graphics::draw_png(
+                    &mut self.backend,
+                    protocol,
+                    image,
+                    area.width,
+                    area.height - y,
+                )?;
+            }
+        }
+        if let Some(pixmap) = &preview.pixmap {
+            let y = if lines.is_empty() {
+                0
+            } else {
+                lines.len() as u16 + 1
+            };
+            if y < area.height {
+                graphics::draw_pixmap(
+                    &mut self.backend,
+                    self.graphics,
+                    pixmap,
+                    area.x,
+                    area.y + y,
+                    area.width,
+                    area.height - y,
+                )?;
+            }
+        }
+        Ok(())
+    }
+    fn draw_quick_look(&mut self) -> io::Result<()> {
+        let Mode::QuickLook {
+            preview, scroll, ..
+        } = &self.mode
+        else {
+            return Ok(());
+        };
+        let width = self.backend.size()?.0 as usize;
+        let visible: Vec<Line> = preview
+            .layout(width, self.wrap_previews)
+            .into_iter()
+            .skip(*scroll)
+            .take(self.terminal_height()? as usize)
+            .collect();
+        for (y, line) in visible.iter().enumerate() {
+            self.print_styled_line(line, 0, y as u16, width)?;
+        }
+        Ok(())
+    }
+    /// Draws the old and new name of every file being renamed, above the find
+    /// and replace fields.
+    fn draw_batch_rename(&mut self) -> io::Result<()> {
+        let Mode::BatchRename(batch) = &self.mode else {
+            return Ok(());
+        };
+        let width = self.backend.size()?.0 as usize;
+        let bottom = self.terminal_height()?;
+        let header = match &self.status_message {
+            Some(message) => (message.to_owned(), Color::Red),
+            None => (
+                format!(
+                    "renaming {} files, matching {} (ctrl+r to switch, tab to change field)",
+                    batch.targets.len(),
+                    batch.matching.name()
+                ),
+                Color::Reset,
+            ),
+        };
+        let mut lines = vec![header];
+        let names: Vec<String> = batch
+            .targets
+            .iter()
+            .map(|path| {
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        match batch.new_names() {
+            Ok(new_names) => {
+                for (old, new) in names.iter().zip(new_names) {
+                    if *old == new {
+                        lines.push((old.to_owned(), Color::DarkGrey));
+                    } else {
+                        lines.push((format!("{} -> {}", old, new), Color::Reset));
+                    }
+                }
+            }
+            Err(error) => lines.push((error, Color::Red)),
+        }
+        let (find, replace) = (batch.find.text(), batch.replace.text());
+        if batch.matching == rename::Matching::Template {
+            let cursor = batch.find.cursor();
+            for (y, (text, color)) in lines.iter().take(bottom as usize).enumerate() {
+                self.print_line(&truncate(text, width), 0, y as u16, *color, false)?;
+            }
+            return self.draw_prompt_at("template: ", &find, Some(cursor), bottom);
+        }
+        let (find_cursor, replace_cursor) = if batch.editing_replace {
+            (None, Some(batch.replace.cursor()))
+        } else {
+            (Some(batch.find.cursor()), None)
+        };
+        for (y, (text, color)) in lines.iter().take(bottom as usize - 1).enumerate() {
+            self.print_line(&truncate(text, width), 0, y as u16, *color, false)?;
+        }
+        self.draw_prompt_at("find: ", &find, find_cursor, bottom - 1)?;
+        self.draw_prompt_at("replace: ", &replace, replace_cursor, bottom)
+    }
+    fn draw_drives(&mut self) -> io::Result<()> {
+        let Mode::Drives { drives, selection } = &self.mode else {
+            return Ok(());
+        };
+        let width = self.backend.size()?.0 as usize;
+        let mut lines: Vec<(String, Color, bool)> = drives
+            .iter()
+            .enumerate()
+            .map(|(index, drive)| {
+                let color = if drive.removable {
+                    rgb(self.config.dir_color)
+                } else {
+                    Color::Reset
+                };
+                (
+                    truncate(&drive.describe(), width),
+                    color,
+                    index == *selection,
+                )
+            })
+            .collect();
+        if lines.is_empty() {
+            lines.push(("no drives found".to_string(), Color::Reset, false));
+        }
+        // keep the selection visible
+        let height = self.terminal_height()? as usize;
+        let offset = (*selection + 1).saturating_sub(height);
+        self.focus = (0, (*selection - offset) as u16);
+        for (y, (text, color, highlighted)) in lines.iter().skip(offset).take(height).enumerate() {
+            self.print_line(text, 0, y as u16, *color, *highlighted)?;
+        }
+        Ok(())
+    }
+    fn draw_bookmarks(&mut self) -> io::Result<()> {
+        let Mode::Bookmarks(picker) = &self.mode else {
+            return Ok(());
+        };
+        let width = self.backend.size()?.0 as usize;
+        let bookmarks = &self.config.bookmarks;
+        let name_width = bookmarks
+            .iter()
+            .map(|bookmark| bookmark.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut lines: Vec<(String, Color, bool)> = vec![];
+        let mut selected_line = 0;
+        let mut group = None;
+        for (position, index) in picker.order.iter().enumerate() {
+            let bookmark = &bookmarks[*index];
+            if bookmark.group.is_some() && bookmark.group.as_ref() != group {
+                let name = bookmark.group.clone().unwrap_or_default();
+                lines.push((truncate(&name, width), rgb(self.config.dir_color), false));
+            }
+            group = bookmark.group.as_ref();
+            if position == picker.selection {
+                selected_line = lines.len();
+            }
+            let text = format!(
+                "  {} {:<3$}  {}",
+                bookmark.key.unwrap_or(' '),
+                bookmark.name,
+                bookmark.path,
+                name_width
+            );
+            let highlighted = position == picker.selection;
+            lines.push((truncate(&text, width), Color::Reset, highlighted));
+        }
+        if lines.is_empty() {
+            let text = "no bookmarks, b or ctrl+a bookmarks the current directory".to_string();
+            lines.push((text, Color::Reset, false));
+        }
+        // keep the selection visible
+        let height = self.terminal_height()? as usize;
+        let offset = (selected_line + 1).saturating_sub(height);
+        self.focus = (0, (selected_line - offset) as u16);
+        for (y, (text, color, highlighted)) in lines.iter().skip(offset).take(height).enumerate() {
+            self.print_line(text, 0, y as u16, *color, *highlighted)?;
+        }
+        Ok(())
+    }
+    fn draw_finder(&mut self) -> io::Result<()> {
+        let Mode::Finder(finder) = &self.mode else {
+            return Ok(());
+        };
+        let width = self.backend.size()?.0 as usize;
+        let height = self.terminal_height()? as usize;
+        // keep the selection visible
+        let offset = (finder.selection + 1).saturating_sub(height);
+        let lines: Vec<(String, bool)> = finder
+            .matches
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(height)
+            .map(|(position, index)| {
+                let text = truncate(&finder.paths[*index], width);
+                (text, position == finder.selection)
+            })
+            .collect();
+        self.focus = (0, (finder.selection - offset) as u16);
+        for (y, (text, highlighted)) in lines.iter().enumerate() {
+            self.print_line(text, 0, y as u16, Color::Reset, *highlighted)?;
+        }
+        Ok(())
+    }
+    fn draw_attributes(&mut self) -> io::Result<()> {
+        let Mode::Attributes(batch) = &self.mode else {
+            return Ok(());
+        };
+        let width = self.backend.size()?.0 as usize;
+        let mut lines = batch.summary();
+        lines.push(String::new());
+        lines.push(batch.preview());
+        for (y, line) in lines
+            .iter()
+            .take(self.terminal_height()? as usize)
+            .enumerate()
+        {
+            self.print_line(&truncate(line, width), 0, y as u16, Color::Reset, false)?;
+        }
+        Ok(())
+    }
+    /// Draws `prompt` on the bottom line after `label`, with the cursor highlighted.
+    fn draw_prompt(&mut self, label: &str, text: &str, cursor: usize) -> io::Result<()> {
+        self.draw_prompt_at(label, text, Some(cursor), self.terminal_height()?)
+    }
+    /// Draws a prompt on row `y`. Without a cursor, it's drawn as unfocused.
+    fn draw_prompt_at(
+        &mut self,
+        label: &str,
+        text: &str,
+        cursor: Option<usize>,
+        y: u16,
+    ) -> io::Result<()> {
+        let width = self.backend.size()?.0 as usize;
+        let label_width = label.chars().count();
+        self.print_line(label, 0, y, Color::Reset, false)?;
+        self.draw_input(
+            text,
+            cursor,
+            label_width as u16,
+            y,
+            width.saturating_sub(label_width),
+        )
+    }
+    /// Draws the text of an input `width` characters wide at `x`, `y`, clearing
+    /// whatever was there.
+    fn draw_input(
+        &mut self,
+        text: &str,
+        cursor: Option<usize>,
+        x: u16,
+        y: u16,
+        width: usize,
+    ) -> io::Result<()> {
+        let available = width.saturating_sub(1);
+        // scroll the text so the cursor stays visible
+        let offset = cursor.unwrap_or(0).saturating_sub(available);
+        let visible: String = text.chars().skip(offset).take(available).collect();
+        self.print_line(
+            &format!("{:<1$}", visible, width),
+            x,
+            y,
+            Color::Reset,
+            false,
+        )?;
+        let Some(cursor) = cursor else {
+            return Ok(());
+        };
+        let under_cursor = text.chars().nth(cursor).unwrap_or(' ').to_string();
+        let x = x + (cursor - offset) as u16;
+        self.focus = (x, y);
+        self.print_line(&under_cursor, x, y, Color::Reset, true)
+    }
+    fn draw_bottom_line(&mut self) -> io::Result<()> {
+        let width = self.backend.size()?.0 as usize;
+        let text = match &self.mode {
+            Mode::Search(kind, prompt) => {
+                let label = match kind {
+                    SearchKind::Name => "find: ",
+                    SearchKind::Content => "grep: ",
+                };
+                return self.draw_prompt(label, &prompt.text(), prompt.cursor());
+            }
+            Mode::Filter(filter) => {
+                let (text, cursor) = (filter.prompt.text(), filter.prompt.cursor());
+                return self.draw_prompt("/", &text, cursor);
+            }
+            Mode::Create {
+                directory,
+                prompt,
+                contents,
+            } => {
+                let label = match (directory, contents) {
+                    (true, _) => "new directory: ",
+                    (false, Some(_)) => "new file from clipboard: ",
+                    (false, None) => "new file: ",
+                };
+                return self.draw_prompt(label, &prompt.text(), prompt.cursor());
+            }
+            Mode::Command(prompt, menu) => {
+                let (text, cursor) = (prompt.text(), prompt.cursor());
+                if let Some(menu) = menu {
+                    let labels: Vec<String> = menu
+                        .candidates
+                        .iter()
+                        .map(|candidate| candidate.label.to_owned())
+                        .collect();
+                    let selected = menu.selected;
+                    self.draw_completion_menu(&labels, selected)?;
+                }
+                return self.draw_prompt(":", &text, cursor);
+            }
+            Mode::QuickLook {
+                title,
+                preview,
+                scroll,
+            } => {
+                let line_count = preview.layout(width, self.wrap_previews).len();
+                format!(
+                    "{} ({}/{})",
+                    title,
+                    cmp::min(scroll + 1, line_count),
+                    line_count
+                )
+            }
+            // drawn along with the rest of it
+            Mode::BatchRename(_) => return Ok(()),
+            Mode::Confirm(confirmation) => confirmation.question(),
+            Mode::Rename(..) => match &self.status_message {
+                Some(message) => message.to_owned(),
+                None => "rename: enter to confirm, esc to cancel".to_string(),
+            },
+            Mode::Paste(paste) => {
+                let name = paste
+                    .conflict()
+                    .and_then(|path| path.file_name())
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                format!(
+                    "'{}' already exists: o: overwrite, k: keep both, s: skip (capitalized for all), esc: stop",
+                    name
+                )
+            }
+            Mode::Attributes(batch) => {
+                let (text, cursor) = (batch.prompt.text(), batch.prompt.cursor());
+                return self.draw_prompt("chmod: ", &text, cursor);
+            }
+            Mode::Context { actions, selection } => {
+                let labels: Vec<String> = actions
+                    .iter()
+                    .map(|action| keymap::describe(*action, self.config.key_style))
+                    .collect();
+                let selection = *selection;
+                self.draw_completion_menu(&labels, Some(selection))?;
+                "enter: do, esc: close".to_string()
+            }
+            Mode::OpenWith(menu) => {
+                let labels: Vec<String> = menu
+                    .openers
+                    .iter()
+                    .map(|opener| opener.label.to_owned())
+                    .collect();
+                let (selection, text, cursor) =
+                    (menu.selection, menu.prompt.text(), menu.prompt.cursor());
+                self.draw_completion_menu(&labels, Some(selection))?;
+                return self.draw_prompt("open with: ", &text, cursor);
+            }
+            Mode::Finder(finder) => {
+                let more = if finder.limited {
+                    " (limit)"
+                } else if finder.done {
+                    ""
+                } else {
+                    "+"
+                };
+                let label = format!("{}/{}{} > ", finder.matches.len(), finder.paths.len(), more);
+                let (text, cursor) = (finder.prompt.text(), finder.prompt.cursor());
+                return self.draw_prompt(&label, &text, cursor);
+            }
+            Mode::Bookmarks(picker) => match &picker.editing {
+                bookmarks::Editing::Adding(prompt) | bookmarks::Editing::Renaming(prompt) => {
+                    let (text, cursor) = (prompt.text(), prompt.cursor());
+                    return self.draw_prompt("name: ", &text, cursor);
+                }
+                bookmarks::Editing::Key => {
+                    "press the key for this bookmark, or backspace to remove it".to_string()
+                }
+                bookmarks::Editing::None => match &self.status_message {
+                    Some(message) => message.to_owned(),
+                    None => "key/enter: go, ctrl+a: add, ctrl+r: rename, ctrl+k: set key, ctrl+d: delete, esc: close".to_string(),
+                },
+            },
+            Mode::Drives { .. } => match &self.status_message {
+                Some(message) => message.to_owned(),
+                None => "enter: open, u: unmount, e: eject, r: refresh, esc: close".to_string(),
+            },
+            Mode::Normal => match &self.status_message {
+                Some(message) => message.to_owned(),
+                None if self.config.status_bar.own_line => String::new(),
+                None => self.status_bar(width),
+            },
+        };
+        self.print_line(&text, 0, self.terminal_height()?, Color::Reset, false)
+    }
+    /// Draws the completion candidates in a box just above the bottom line.
+    fn draw_completion_menu(
+        &mut self,
+        labels: &[String],
+        selected: Option<usize>,
+    ) -> io::Result<()> {
+        // above the bottom line, and the status line if it has its own
+        let bottom = self
+            .terminal_height()?
+            .saturating_sub(self.config.status_bar.own_line as u16);
+        let rows = cmp::min(labels.len(), cmp::min(MAX_MENU_HEIGHT, bottom as usize));
+        // scroll the menu so the selected candidate stays visible
+        let offset = selected.map_or(0, |selected| (selected + 1).saturating_sub(rows));
+        let width = labels
+            .iter()
+            .map(|label| label.chars().count())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        let top = bottom - rows as u16;
+        for (row, (index, label)) in labels
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(rows)
+            .enumerate()
+        {
+            let text = format!(" {:<1$}", label, width - 1);
+            let highlighted = selected == Some(index);
+            self.print_line(&text, 0, top + row as u16, Color::Reset, highlighted)?;
+        }
+        Ok(())
+    }
+    /// What's being done, as shown by `{mode}` in the status bar.
+    fn mode_name(&self) -> &'static str {
+        match &self.mode {
+            Mode::Normal => "",
+            Mode::Search(..) => "SEARCH",
+            Mode::Filter(_) => "FILTER",
+            Mode::Command(..) => "COMMAND",
+            Mode::QuickLook { .. } => "VIEW",
+            Mode::Drives { .. } => "DRIVES",
+            Mode::BatchRename(_) | Mode::Rename(..) => "RENAME",
+            Mode::OpenWith(_) => "OPEN WITH",
+            Mode::Context { .. } => "MENU",
+            Mode::Attributes(_) => "PERMISSIONS",
+            Mode::Paste(_) => "PASTE",
+            Mode::Confirm(_) => "CONFIRM",
+            Mode::Create { .. } => "NEW",
+            Mode::Bookmarks(_) => "BOOKMARKS",
+            Mode::Finder(_) => "FIND FILE",
+        }
+    }
+    fn status_bar(&self, width: usize) -> String {
+        let lookup = |placeholder: &str| -> Option<String> {
+            Some(match placeholder {
+                "path" => self.display_path(&self.cwd).to_string_lossy().to_string(),
+                "mode" => self.mode_name().to_string(),
+                "name" => self
+                    .selected_item()
+                    .map(|item| item.name.to_owned())
+                    .unwrap_or_default(),
+                "index" => cmp::min(self.selection + 1, self.current_contents.len()).to_string(),
+                "total" => self.current_contents.len().to_string(),
+                "marked" => match self.marks.len() {
+                    0 => String::new(),
+                    count => format!("{} marked", count),
+                },
+                "size" | "modified" | "permissions" => {
+                    let metadata = self
+                        .selected_item()
+                        .and_then(|item| self.metadata.get(&item.path));
+                    match (placeholder, metadata) {
+                        (_, None) => String::new(),
+                        ("size", Some(metadata)) => human_size(metadata.size),
+                        ("modified", Some(metadata)) => metadata
+                            .modified
+                            .map(properties::format_time)
+                            .unwrap_or_default(),
+                        (_, Some(metadata)) => metadata.permissions.clone(),
+                    }
+                }
+                "contents" => self
+                    .selected_item()
+                    .and_then(|item| *self.directory_stats.get(&item.path)?)
+                    .map(|stat| format!("{} items, {}", stat.items, human_size(stat.size)))
+                    .unwrap_or_default(),
+                "dry-run" if self.config.dry_run => "[dry run]".to_string(),
+                "dry-run" => String::new(),
+                "filter" => self
+                    .virtual_view
+                    .as_ref()
+                    .map(|view| format!("[{}]", view.title))
+                    .unwrap_or_default(),
+                "free" => statusbar::free_space(&self.cwd)
+                    .map(human_size)
+                    .unwrap_or_default(),
+                _ => return None,
+            })
+        };
+        let left = statusbar::expand(&self.config.status_bar.left, lookup);
+        let right = statusbar::expand(&self.config.status_bar.right, lookup);
+        statusbar::compose(&left, &right, width)
+    }
+}
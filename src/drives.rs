@@ -0,0 +1,139 @@
+//! Listing, mounting and unmounting drives through `lsblk` and udisks2's `udisksctl`.
+
+use std::{io, path::PathBuf, process::Command};
+
+use serde_json::Value;
+
+#[derive(Clone)]
+pub struct Drive {
+    /// Device node, like `/dev/sdb1`.
+    pub device: String,
+    pub label: Option<String>,
+    pub size: String,
+    pub mount_point: Option<PathBuf>,
+    pub removable: bool,
+}
+impl Drive {
+    pub fn describe(&self) -> String {
+        let mut description = format!("{}  {}", self.device, self.size);
+        if let Some(label) = &self.label {
+            description = format!("{}  {}", description, label);
+        }
+        match &self.mount_point {
+            Some(mount_point) => format!("{}  at {}", description, mount_point.display()),
+            None => format!("{}  (not mounted)", description),
+        }
+    }
+}
+
+/// Returns the removable drives, and every mounted one.
+pub fn list() -> io::Result<Vec<Drive>> {
+    if !cfg!(target_os = "linux") {
+        return Err(io::Error::other("drives can only be managed on Linux"));
+    }
+    let output = run(&[
+        "lsblk",
+        "--json",
+        "--output",
+        "PATH,LABEL,SIZE,FSTYPE,MOUNTPOINT,RM,HOTPLUG",
+    ])?;
+    let value: Value = serde_json::from_str(&output).map_err(io::Error::other)?;
+    let mut drives = vec![];
+    if let Some(devices) = value["blockdevices"].as_array() {
+        collect(devices, false, &mut drives);
+    }
+    Ok(drives)
+}
+
+/// Adds `devices` and their partitions to `drives`, if they have a filesystem
+/// and are removable or mounted.
+fn collect(devices: &[Value], parent_removable: bool, drives: &mut Vec<Drive>) {
+    for device in devices {
+        let removable = parent_removable || flag(&device["rm"]) || flag(&device["hotplug"]);
+        let mount_point = device["mountpoint"].as_str().map(PathBuf::from);
+        let has_filesystem = device["fstype"].is_string();
+        if has_filesystem && (removable || mount_point.is_some()) {
+            drives.push(Drive {
+                device: device["path"].as_str().unwrap_or_default().to_string(),
+                label: device["label"].as_str().map(str::to_string),
+                size: device["size"].as_str().unwrap_or_default().to_string(),
+                mount_point,
+                removable,
+            });
+        }
+        if let Some(children) = device["children"].as_array() {
+            collect(children, removable, drives);
+        }
+    }
+}
+
+/// Older versions of lsblk print flags as "0" and "1" rather than booleans.
+fn flag(value: &Value) -> bool {
+    value.as_bool().unwrap_or(value.as_str() == Some("1"))
+}
+
+pub fn mount(drive: &Drive) -> io::Result<()> {
+    run(&[
+        "udisksctl",
+        "mount",
+        "--no-user-interaction",
+        "-b",
+        &drive.device,
+    ])
+    .map(|_| ())
+}
+
+pub fn unmount(drive: &Drive) -> io::Result<()> {
+    run(&[
+        "udisksctl",
+        "unmount",
+        "--no-user-interaction",
+        "-b",
+        &drive.device,
+    ])
+    .map(|_| ())
+}
+
+/// Flushes pending writes, unmounts `drive` if needed, and powers it off so it
+/// can be unplugged.
+pub fn eject(drive: &Drive) -> io::Result<()> {
+    sync();
+    if drive.mount_point.is_some() {
+        unmount(drive)?;
+    }
+    run(&[
+        "udisksctl",
+        "power-off",
+        "--no-user-interaction",
+        "-b",
+        &drive.device,
+    ])
+    .map(|_| ())
+}
+
+/// Writes everything buffered in memory out to disk.
+fn sync() {
+    #[cfg(unix)]
+    // SAFETY: sync takes no arguments and can't fail
+    unsafe {
+        libc::sync()
+    };
+}
+
+/// Runs `command`, returning its output, or its error message if it fails.
+fn run(command: &[&str]) -> io::Result<String> {
+    let output = Command::new(command[0])
+        .args(&command[1..])
+        .output()
+        .map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("couldn't run {}: {}", command[0], error),
+            )
+        })?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::other(message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
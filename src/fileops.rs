@@ -0,0 +1,264 @@
+//! Copying, moving and deleting files and directories. Symlinks are copied and
+//! deleted as links, never followed.
+
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// Copies `from` to `to`, recursing into directories.
+///
+/// Files and directories are copied under a partial name, and only renamed to
+/// `to` once complete. If a copy is interrupted, say by fee being killed, copying
+/// the same thing again picks up where it left off rather than starting over,
+/// unless it's been changed since.
+pub fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    check_destination(from, to)?;
+    copy_recursive(from, to)
+}
+
+/// Appended to the names of files and directories still being copied.
+const PARTIAL_SUFFIX: &str = ".fee-part";
+
+/// Appended to the name of a file next to each partial copy, holding the size
+/// and modification time of what it's a copy of.
+const SOURCE_SUFFIX: &str = ".fee-source";
+
+/// How much of the end of a partial copy is compared against the source before
+/// it's continued, in case the source changed since.
+const RESUME_CHECK_BYTES: u64 = 64 * 1024;
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    // inside a resumed directory, whatever has its final name was copied fully
+    if to.symlink_metadata().is_ok() {
+        return Ok(());
+    }
+    let metadata = fs::symlink_metadata(from)?;
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        return copy_link(from, to);
+    }
+    let partial = with_suffix(to, PARTIAL_SUFFIX);
+    let source = with_suffix(to, SOURCE_SUFFIX);
+    let identity = identity(&metadata);
+    if partial.symlink_metadata().is_ok()
+        && fs::read_to_string(&source).ok().as_ref() != Some(&identity)
+    {
+        // left over from copying something else, or this before it changed
+        delete(&partial)?;
+    }
+    fs::write(&source, identity)?;
+    if file_type.is_dir() {
+        if !partial.is_dir() {
+            fs::create_dir(&partial)?;
+        }
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &partial.join(entry.file_name()))?;
+        }
+    } else {
+        copy_file(from, &partial)?;
+    }
+    fs::remove_file(source)?;
+    fs::rename(partial, to)
+}
+
+/// The size and modification time in `metadata`, telling whether a partial copy
+/// is of the same thing.
+fn identity(metadata: &fs::Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos());
+    format!("{} {}", metadata.len(), modified)
+}
+
+/// Copies the file `from` to `partial`, continuing an earlier copy if `partial`
+/// is the start of it.
+fn copy_file(from: &Path, partial: &Path) -> io::Result<()> {
+    let copied = fs::metadata(partial).map_or(0, |metadata| metadata.len());
+    let mut source = fs::File::open(from)?;
+    let mut destination = if copied > 0 && is_start_of(partial, from, copied)? {
+        source.seek(SeekFrom::Start(copied))?;
+        fs::OpenOptions::new().append(true).open(partial)?
+    } else {
+        fs::File::create(partial)?
+    };
+    io::copy(&mut source, &mut destination)?;
+    // only once it's complete, so copies of read-only files can be continued
+    fs::set_permissions(partial, fs::metadata(from)?.permissions())
+}
+
+/// Whether the `length` bytes of `partial` are the start of `file`, going by
+/// the last few of them.
+fn is_start_of(partial: &Path, file: &Path, length: u64) -> io::Result<bool> {
+    if fs::metadata(file)?.len() < length {
+        return Ok(false);
+    }
+    let start = length.saturating_sub(RESUME_CHECK_BYTES);
+    let read_tail = |path: &Path| -> io::Result<Vec<u8>> {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = vec![];
+        file.take(length - start).read_to_end(&mut tail)?;
+        Ok(tail)
+    };
+    Ok(read_tail(partial)? == read_tail(file)?)
+}
+
+/// Returns `path` with `suffix` added to the end of its name.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Returns where in `directory` to put something named `name`, numbering the
+/// name like `notes (2).txt` if something's already there, or `taken` says the
+/// name is.
+pub fn free_name(directory: &Path, name: &OsStr, taken: impl Fn(&OsStr) -> bool) -> PathBuf {
+    let free = |name: &OsStr| directory.join(name).symlink_metadata().is_err() && !taken(name);
+    if free(name) {
+        return directory.join(name);
+    }
+    let stem = Path::new(name).file_stem().unwrap_or(name);
+    let extension = Path::new(name).extension();
+    (2..)
+        .map(|number| {
+            let mut candidate = stem.to_os_string();
+            candidate.push(format!(" ({})", number));
+            if let Some(extension) = extension {
+                candidate.push(".");
+                candidate.push(extension);
+            }
+            candidate
+        })
+        .find(|candidate| free(candidate))
+        .map_or_else(
+            || directory.join(name),
+            |candidate| directory.join(candidate),
+        )
+}
+
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    let target = fs::read_link(from)?;
+    if fs::metadata(from).is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
+    }
+}
+
+/// Moves `from` to `to`, copying and deleting it if they're on different drives.
+pub fn move_to(from: &Path, to: &Path) -> io::Result<()> {
+    check_destination(from, to)?;
+    match fs::rename(from, to) {
+        Err(error) if error.kind() == io::ErrorKind::CrossesDevices => {
+            copy_recursive(from, to)?;
+            delete(from)
+        }
+        result => result,
+    }
+}
+
+/// Deletes `path`, along with everything in it if it's a directory.
+pub fn delete(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Checks that entries can be added to and removed from `directory`, so an
+/// operation can be refused before it starts rather than failing partway.
+pub fn check_writable(directory: &Path) -> io::Result<()> {
+    let error = match writable(directory) {
+        Ok(()) => return Ok(()),
+        Err(error) => error,
+    };
+    let message = match error.kind() {
+        io::ErrorKind::ReadOnlyFilesystem => {
+            format!("'{}' is on a read-only filesystem", directory.display())
+        }
+        io::ErrorKind::PermissionDenied => {
+            format!("permission denied: can't change '{}'", directory.display())
+        }
+        _ => return Err(error),
+    };
+    Err(io::Error::new(error.kind(), message))
+}
+
+#[cfg(unix)]
+fn writable(directory: &Path) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(directory.as_os_str().as_bytes())?;
+    // SAFETY: `path` is a valid C string
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn writable(directory: &Path) -> io::Result<()> {
+    if fs::metadata(directory)?.permissions().readonly() {
+        return Err(io::ErrorKind::PermissionDenied.into());
+    }
+    Ok(())
+}
+
+/// Checks that `path` can be deleted, along with what's directly in it if it's
+/// a directory.
+pub fn check_removable(path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        check_writable(parent)?;
+    }
+    if fs::symlink_metadata(path)?.is_dir() {
+        check_writable(path)?;
+    }
+    Ok(())
+}
+
+/// Returns where `path` ends up when copied or moved into `directory`.
+pub fn destination(path: &Path, directory: &Path) -> PathBuf {
+    directory.join(path.file_name().unwrap_or_default())
+}
+
+/// Returns `path` with links in the directories leading to it resolved, though
+/// not if it's a link itself, since links are copied, moved and selected as they
+/// are.
+pub fn resolve_directories(path: &Path) -> PathBuf {
+    let parent = path.parent().and_then(|parent| parent.canonicalize().ok());
+    match (parent, path.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn check_destination(from: &Path, to: &Path) -> io::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("'{}' already exists", to.display()),
+        ));
+    }
+    if resolve_directories(to).starts_with(resolve_directories(from)) {
+        return Err(io::Error::other(format!(
+            "can't put '{}' inside itself",
+            from.display()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! Narrowing down the listing to the entries fuzzy matching what's typed.
+
+use crate::prompt::Prompt;
+
+/// A filter being typed, shown in place of the listing.
+pub struct Filter {
+    pub prompt: Prompt,
+    /// Indices of the matching entries, best match first.
+    pub matches: Vec<usize>,
+    /// Which of the matches is selected.
+    pub selection: usize,
+    /// The selection and scroll of the listing from before filtering, to go
+    /// back to when cancelled.
+    pub previous: (usize, usize),
+}
+impl Filter {
+    pub fn new(previous: (usize, usize)) -> Self {
+        Filter {
+            prompt: Prompt::default(),
+            matches: vec![],
+            selection: 0,
+            previous,
+        }
+    }
+    /// Matches `names` against the query again.
+    pub fn update(&mut self, names: &[&str]) {
+        let query = self.prompt.text();
+        let mut scored: Vec<(i64, usize)> = names
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| Some((score(&query, name)?, index)))
+            .collect();
+        // stable, so equally good matches stay in listing order
+        scored.sort_by_key(|(score, _)| -score);
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selection = 0;
+    }
+    /// The index of the selected entry, if anything matches.
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.selection).copied()
+    }
+}
+
+/// Scores how well `name` matches `query`, if it contains all its characters in
+/// order. Runs of consecutive characters, characters starting words and, in
+/// paths, characters in the file name score higher, and gaps between the matched
+/// characters lower. Case is ignored, unless the query has uppercase letters.
+pub fn score(query: &str, name: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let fold = |char: char| {
+        if ignore_case {
+            char.to_lowercase().next().unwrap_or(char)
+        } else {
+            char
+        }
+    };
+    let query: Vec<char> = query.chars().map(fold).collect();
+    let name: Vec<char> = name.chars().collect();
+    let folded: Vec<char> = name.iter().copied().map(fold).collect();
+    // the leftmost match isn't always the best, so try starting at every place
+    // the first character appears
+    let best = (0..name.len())
+        .filter(|start| folded[*start] == query[0])
+        .filter_map(|start| score_from(&query, &name, &folded, start))
+        .max()?;
+    // prefer shorter names, where more of it was matched
+    Some(best * 100 - name.len() as i64)
+}
+
+/// Scores matching `query` against `name` from `start` on, taking each next
+/// character as soon as it appears.
+fn score_from(query: &[char], name: &[char], folded: &[char], start: usize) -> Option<i64> {
+    let file_name = name
+        .iter()
+        .rposition(|char| std::path::is_separator(*char))
+        .map_or(0, |separator| separator + 1);
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut position = start;
+    for wanted in query {
+        let index = (position..name.len()).find(|index| folded[*index] == *wanted)?;
+        score += 16;
+        match previous {
+            Some(previous) if previous + 1 == index => score += 15,
+            Some(previous) => score -= (index - previous - 1) as i64,
+            None => {}
+        }
+        let word_start = match index.checked_sub(1).map(|before| name[before]) {
+            None => true,
+            Some(before) => {
+                !before.is_alphanumeric() || (name[index].is_uppercase() && before.is_lowercase())
+            }
+        };
+        if word_start {
+            score += 10;
+        }
+        if index >= file_name {
+            score += 4;
+        }
+        previous = Some(index);
+        position = index + 1;
+    }
+    Some(score)
+}
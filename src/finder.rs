@@ -0,0 +1,192 @@
+//! Finding files anywhere below the current directory by fuzzy matching their
+//! paths, like fzf. The tree is walked on a background thread, and files show up
+//! as they're found.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+};
+
+use crate::{filter, ignore::Ignore, prompt::Prompt};
+
+/// How many paths the walker finds before handing them over.
+const BATCH_SIZE: usize = 512;
+
+/// How many paths are kept at most, so walking a huge tree can't take up all
+/// the memory. The walk stops there.
+const MAX_PATHS: usize = 200_000;
+
+pub struct Finder {
+    pub root: PathBuf,
+    pub prompt: Prompt,
+    /// Every file found so far, relative to the root.
+    pub paths: Vec<String>,
+    /// Indices of the paths matching the query, best match first.
+    pub matches: Vec<usize>,
+    scores: Vec<i64>,
+    /// Which of the matches is selected.
+    pub selection: usize,
+    /// Whether the whole tree has been walked, or as much of it as is kept.
+    pub done: bool,
+    /// Whether the walk stopped at `MAX_PATHS`, before the whole tree was walked.
+    pub limited: bool,
+    receiver: Receiver<Vec<String>>,
+    stop: Arc<AtomicBool>,
+}
+impl Finder {
+    /// Starts walking `root`, skipping hidden entries unless `show_hidden`, and
+    /// entries matched by `ignore`.
+    pub fn new(root: PathBuf, show_hidden: bool, ignore: Option<Arc<Ignore>>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let walker = (root.clone(), stop.clone());
+        thread::spawn(move || {
+            let (root, stop) = walker;
+            // breadth first, so files near the top show up first
+            let mut pending = VecDeque::from([(PathBuf::new(), ignore)]);
+            let mut batch = vec![];
+            while let Some((relative, ignore)) = pending.pop_front() {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let Ok(entries) = fs::read_dir(root.join(&relative)) else {
+                    continue;
+                };
+                let mut entries: Vec<_> = entries.flatten().collect();
+                entries.sort_by_key(|entry| entry.file_name());
+                for entry in entries {
+                    let name = entry.file_name();
+                    if !show_hidden && name.to_string_lossy().starts_with('.') {
+                        continue;
+                    }
+                    // symlinks aren't followed, so links back up the tree can't loop
+                    let Ok(file_type) = entry.file_type() else {
+                        continue;
+                    };
+                    let path = relative.join(name);
+                    let full = root.join(&path);
+                    if ignore
+                        .as_ref()
+                        .is_some_and(|ignore| ignore.is_ignored(&full, file_type.is_dir()))
+                    {
+                        continue;
+                    }
+                    if file_type.is_dir() {
+                        let ignore = ignore.as_ref().map(|ignore| ignore.child(&full));
+                        pending.push_back((path, ignore));
+                    } else {
+                        batch.push(path.to_string_lossy().to_string());
+                    }
+                }
+                if batch.len() >= BATCH_SIZE && sender.send(std::mem::take(&mut batch)).is_err() {
+                    return;
+                }
+            }
+            let _ = sender.send(batch);
+        });
+        Finder {
+            root,
+            prompt: Prompt::default(),
+            paths: vec![],
+            matches: vec![],
+            scores: vec![],
+            selection: 0,
+            done: false,
+            limited: false,
+            receiver,
+            stop,
+        }
+    }
+    /// Takes in the files found since last time. Returns whether there were any.
+    pub fn receive(&mut self) -> bool {
+        let mut received = false;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(mut batch) => {
+                    let start = self.paths.len();
+                    let room = MAX_PATHS - start;
+                    if batch.len() >= room {
+                        batch.truncate(room);
+                        self.stop.store(true, Ordering::Relaxed);
+                        self.done = true;
+                        self.limited = true;
+                    }
+                    self.paths.extend(batch);
+                    self.score(start);
+                    received = true;
+                    if self.done {
+                        break;
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        received
+    }
+    /// Matches every path against the query again, after it's changed.
+    pub fn update(&mut self) {
+        self.matches.clear();
+        self.scores.clear();
+        self.score(0);
+        self.selection = 0;
+    }
+    /// Scores the paths from `start` on, and merges them in with the other
+    /// matches, keeping the same match selected.
+    fn score(&mut self, start: usize) {
+        let query = self.prompt.text();
+        let selected = self.matches.get(self.selection).copied();
+        let mut new: Vec<(i64, usize)> = self
+            .paths
+            .iter()
+            .enumerate()
+            .skip(start)
+            .filter_map(|(index, path)| Some((filter::score(&query, path)?, index)))
+            .collect();
+        // stable, so equally good matches stay in the order they were found
+        new.sort_by_key(|(score, _)| -score);
+        let old = std::mem::take(&mut self.scores)
+            .into_iter()
+            .zip(std::mem::take(&mut self.matches));
+        let mut new = new.into_iter().peekable();
+        for (score, index) in old {
+            while let Some(better) = new.next_if(|(new_score, _)| *new_score > score) {
+                self.scores.push(better.0);
+                self.matches.push(better.1);
+            }
+            self.scores.push(score);
+            self.matches.push(index);
+        }
+        for (score, index) in new {
+            self.scores.push(score);
+            self.matches.push(index);
+        }
+        if let Some(selected) = selected {
+            self.selection = self
+                .matches
+                .iter()
+                .position(|index| *index == selected)
+                .unwrap_or(0);
+        }
+    }
+    /// The full path of the selected match.
+    pub fn selected(&self) -> Option<PathBuf> {
+        let index = self.matches.get(self.selection)?;
+        Some(self.root.join(&self.paths[*index]))
+    }
+}
+impl Drop for Finder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,170 @@
+//! The last commit touching each entry of a directory in a git repository, shown
+//! in a column with the `git_column` option, to spot files nobody has touched in
+//! ages.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader},
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+pub struct Commit {
+    /// Abbreviated hash.
+    pub hash: String,
+    /// Date it was committed, as YYYY-MM-DD.
+    pub date: String,
+}
+
+/// Last commits of each entry, by name.
+pub type Commits = HashMap<String, Commit>;
+
+/// A directory, and the names of the entries in it to find commits for.
+type Request = (PathBuf, HashSet<String>);
+
+/// Walks the history on a background thread, since it can go back a long way
+/// for entries that haven't changed in a while.
+pub struct Worker {
+    requests: Sender<Request>,
+    results: Receiver<(PathBuf, Commits)>,
+}
+impl Worker {
+    pub fn spawn() -> Self {
+        let (requests, pending) = mpsc::channel::<Request>();
+        let (finished, results) = mpsc::channel();
+        thread::spawn(move || {
+            let mut next = pending.recv().ok();
+            while let Some(mut request) = next.take() {
+                // only the newest request matters, older directories were left
+                while let Ok(newer) = pending.try_recv() {
+                    request = newer;
+                }
+                let (directory, names) = request;
+                match last_commits(&directory, names, &pending) {
+                    Ok(commits) => {
+                        if finished.send((directory, commits)).is_err() {
+                            break;
+                        }
+                        next = pending.recv().ok();
+                    }
+                    Err(newer) => next = Some(newer),
+                }
+            }
+        });
+        Worker { requests, results }
+    }
+    /// Starts looking up the last commits of the entries in `directory` named `names`.
+    pub fn request(&self, directory: PathBuf, names: HashSet<String>) {
+        let _ = self.requests.send((directory, names));
+    }
+    pub fn try_receive(&self) -> Option<(PathBuf, Commits)> {
+        self.results.try_recv().ok()
+    }
+}
+
+/// The names of the entries of `directory` that git tracks, or have tracked
+/// files in them. Nothing, outside of a repository or without git.
+fn tracked_entries(directory: &Path) -> HashSet<String> {
+    let output = Command::new("git")
+        .args(["ls-files", "-z"])
+        .current_dir(directory)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    let Ok(output) = output else {
+        return HashSet::new();
+    };
+    // listed relative to the directory, so the first component is the entry
+    output
+        .stdout
+        .split(|byte| *byte == 0)
+        .filter_map(|path| path.split(|byte| *byte == b'/').next())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| String::from_utf8_lossy(entry).to_string())
+        .collect()
+}
+
+/// Goes through the history of `directory` newest first, until every tracked
+/// entry in `names` has been seen or the history runs out. Outside of a
+/// repository, or without git, nothing is found. Gives up for a newer request
+/// on `pending`, returning it.
+fn last_commits(
+    directory: &Path,
+    mut names: HashSet<String>,
+    pending: &Receiver<Request>,
+) -> Result<Commits, Request> {
+    let mut commits = Commits::new();
+    // untracked entries would never be seen, and the whole history walked
+    let tracked = tracked_entries(directory);
+    names.retain(|name| tracked.contains(name));
+    if names.is_empty() {
+        return Ok(commits);
+    }
+    let child = Command::new("git")
+        .args([
+            "-c",
+            "core.quotepath=off",
+            "log",
+            "--name-only",
+            "--relative",
+        ])
+        .arg("--format=%x00%h %cs")
+        .args(["--", "."])
+        .current_dir(directory)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return Ok(commits);
+    };
+    let Some(stdout) = child.stdout.take() else {
+        return Ok(commits);
+    };
+    let mut current: Option<(String, String)> = None;
+    let mut newer = None;
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if let Ok(request) = pending.try_recv() {
+            newer = Some(request);
+            break;
+        }
+        if let Some(header) = line.strip_prefix('\0') {
+            current = header
+                .split_once(' ')
+                .map(|(hash, date)| (hash.to_string(), date.to_string()));
+            continue;
+        }
+        let Some((hash, date)) = &current else {
+            continue;
+        };
+        // changed paths are relative to the directory, so the first component
+        // is the entry they're in
+        let Some(Component::Normal(entry)) = Path::new(&line).components().next() else {
+            continue;
+        };
+        let entry = entry.to_string_lossy().to_string();
+        if names.remove(&entry) {
+            commits.insert(
+                entry,
+                Commit {
+                    hash: hash.clone(),
+                    date: date.clone(),
+                },
+            );
+            if names.is_empty() {
+                break;
+            }
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    match newer {
+        Some(request) => Err(request),
+        None => Ok(commits),
+    }
+}
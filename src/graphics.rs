@@ -0,0 +1,362 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crossterm::{
+    cursor, queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+
+use crate::crc32::Crc32;
+
+/// A terminal image protocol.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Kitty,
+    Iterm,
+    /// Only draws [`Pixmap`]s, as the pixels are encoded by us.
+    Sixel,
+}
+impl Protocol {
+    /// Whether PNG files can be drawn as they are.
+    pub fn shows_png(self) -> bool {
+        self != Protocol::Sixel
+    }
+}
+
+/// Guesses which image protocol the terminal supports from the environment.
+pub fn detect() -> Option<Protocol> {
+    let term = env::var("TERM").unwrap_or_default();
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+    if term.contains("kitty")
+        || env::var_os("KITTY_WINDOW_ID").is_some()
+        || term_program == "ghostty"
+    {
+        Some(Protocol::Kitty)
+    } else if term_program == "iTerm.app"
+        || term_program == "WezTerm"
+        || env::var("LC_TERMINAL").is_ok_and(|terminal| terminal == "iTerm2")
+    {
+        Some(Protocol::Iterm)
+    } else if term.contains("sixel")
+        || ["foot", "mlterm", "contour"]
+            .iter()
+            .any(|terminal| term.starts_with(terminal))
+        || env::var_os("WT_SESSION").is_some()
+    {
+        Some(Protocol::Sixel)
+    } else {
+        None
+    }
+}
+
+/// Draws the PNG at `path` at the cursor position, scaled to fit `columns`x`rows` cells.
+pub fn draw_png(
+    out: &mut impl Write,
+    protocol: Protocol,
+    path: &Path,
+    columns: u16,
+    rows: u16,
+) -> io::Result<()> {
+    draw_png_data(out, protocol, &fs::read(path)?, columns, rows)
+}
+
+fn draw_png_data(
+    out: &mut impl Write,
+    protocol: Protocol,
+    png: &[u8],
+    columns: u16,
+    rows: u16,
+) -> io::Result<()> {
+    let data = base64(png);
+    match protocol {
+        Protocol::Kitty => {
+            // kitty limits each escape sequence to 4096 bytes of payload
+            let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+            for (index, chunk) in chunks.iter().enumerate() {
+                let more = (index + 1 < chunks.len()) as u8;
+                if index == 0 {
+                    write!(
+                        out,
+                        "\x1b_Ga=T,f=100,q=2,c={},r={},m={};",
+                        columns, rows, more
+                    )?;
+                } else {
+                    write!(out, "\x1b_Gm={};", more)?;
+                }
+                out.write_all(chunk)?;
+                write!(out, "\x1b\\")?;
+            }
+        }
+        Protocol::Iterm => {
+            write!(
+                out,
+                "\x1b]1337;File=inline=1;width={};height={};preserveAspectRatio=1:{}\x07",
+                columns, rows, data
+            )?;
+        }
+        Protocol::Sixel => {}
+    }
+    Ok(())
+}
+
+/// An image decoded to RGB pixels, so it can be drawn with any protocol, or
+/// without one.
+pub struct Pixmap {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+impl Pixmap {
+    /// Reads a binary (P6) PPM file, the simplest format image converters
+    /// can write.
+    pub fn read_ppm(bytes: &[u8]) -> Option<Self> {
+        let mut fields = vec![];
+        let mut index = 0;
+        while fields.len() < 4 {
+            match bytes.get(index)? {
+                b'#' => {
+                    while *bytes.get(index)? != b'\n' {
+                        index += 1;
+                    }
+                }
+                byte if byte.is_ascii_whitespace() => index += 1,
+                _ => {
+                    let start = index;
+                    while !bytes.get(index)?.is_ascii_whitespace() {
+                        index += 1;
+                    }
+                    fields.push(std::str::from_utf8(&bytes[start..index]).ok()?);
+                }
+            }
+        }
+        // a single whitespace character separates the header from the pixels
+        let data = &bytes[index + 1..];
+        let width: usize = fields[1].parse().ok()?;
+        let height: usize = fields[2].parse().ok()?;
+        if fields[0] != "P6"
+            || fields[3] != "255"
+            || width * height == 0
+            || data.len() < width * height * 3
+        {
+            return None;
+        }
+        let pixels = data
+            .chunks_exact(3)
+            .take(width * height)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect();
+        Some(Pixmap {
+            width,
+            height,
+            pixels,
+        })
+    }
+    /// Scales the image with nearest neighbour sampling.
+    fn resized(&self, width: usize, height: usize) -> Pixmap {
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = y * self.height / height;
+            for x in 0..width {
+                pixels.push(self.pixels[row * self.width + x * self.width / width]);
+            }
+        }
+        Pixmap {
+            width,
+            height,
+            pixels,
+        }
+    }
+    /// The size in pixels of the image scaled to fit within `width`x`height`
+    /// pixels, keeping its aspect ratio. Images are never scaled up.
+    fn fit(&self, width: usize, height: usize) -> (usize, usize) {
+        let scale = (width as f64 / self.width as f64)
+            .min(height as f64 / self.height as f64)
+            .min(1.0);
+        (
+            ((self.width as f64 * scale) as usize).max(1),
+            ((self.height as f64 * scale) as usize).max(1),
+        )
+    }
+    /// Encodes the image as an uncompressed PNG.
+    fn to_png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width) {
+            // each row starts with the filter used, none
+            raw.push(0);
+            raw.extend(row.iter().flatten());
+        }
+        // a zlib stream of stored blocks, which hold up to 65535 bytes each
+        let mut zlib = vec![0x78, 0x01];
+        let blocks: Vec<&[u8]> = raw.chunks(65535).collect();
+        for (index, block) in blocks.iter().enumerate() {
+            zlib.push((index + 1 == blocks.len()) as u8);
+            zlib.extend((block.len() as u16).to_le_bytes());
+            zlib.extend((!(block.len() as u16)).to_le_bytes());
+            zlib.extend(*block);
+        }
+        zlib.extend(adler32(&raw).to_be_bytes());
+
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bit RGB, without interlacing
+        header.extend([8, 2, 0, 0, 0]);
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &zlib);
+        png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+/// Draws `pixmap` at `x`, `y`, scaled to fit `columns`x`rows` cells. Without
+/// a protocol, it's drawn with half blocks, two pixels to a cell.
+pub fn draw_pixmap(
+    out: &mut impl Write,
+    protocol: Option<Protocol>,
+    pixmap: &Pixmap,
+    x: u16,
+    y: u16,
+    columns: u16,
+    rows: u16,
+) -> io::Result<()> {
+    let (cell_width, cell_height) = cell_size();
+    let (width, height) = match protocol {
+        Some(_) => pixmap.fit(columns as usize * cell_width, rows as usize * cell_height),
+        None => pixmap.fit(columns as usize, rows as usize * 2),
+    };
+    queue!(out, cursor::MoveTo(x, y))?;
+    match protocol {
+        Some(Protocol::Sixel) => write_sixel(out, &pixmap.resized(width, height)),
+        Some(protocol) => draw_png_data(
+            out,
+            protocol,
+            &pixmap.to_png(),
+            width.div_ceil(cell_width) as u16,
+            height.div_ceil(cell_height) as u16,
+        ),
+        None => {
+            let pixmap = pixmap.resized(width, height);
+            let color = |[r, g, b]: [u8; 3]| Color::Rgb { r, g, b };
+            for (row, pairs) in pixmap.pixels.chunks(width * 2).enumerate() {
+                queue!(out, cursor::MoveTo(x, y + row as u16))?;
+                let (top, bottom) = pairs.split_at(width.min(pairs.len()));
+                for (column, top) in top.iter().enumerate() {
+                    let background = bottom
+                        .get(column)
+                        .map_or(Color::Reset, |pixel| color(*pixel));
+                    queue!(
+                        out,
+                        SetForegroundColor(color(*top)),
+                        SetBackgroundColor(background),
+                        Print('▀')
+                    )?;
+                }
+            }
+            queue!(out, ResetColor)
+        }
+    }
+}
+
+/// The size of a cell in pixels, as reported by the terminal, or a guess.
+fn cell_size() -> (usize, usize) {
+    match terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns) as usize,
+            (size.height / size.rows) as usize,
+        ),
+        _ => (8, 16),
+    }
+}
+
+/// Writes `pixmap` as sixels, with its colors rounded to a 6x6x6 color cube.
+fn write_sixel(out: &mut impl Write, pixmap: &Pixmap) -> io::Result<()> {
+    write!(out, "\x1bPq\"1;1;{};{}", pixmap.width, pixmap.height)?;
+    for index in 0..216 {
+        let (r, g, b) = (index / 36, index / 6 % 6, index % 6);
+        write!(out, "#{};2;{};{};{}", index, r * 20, g * 20, b * 20)?;
+    }
+    let level = |value: u8| (value as usize * 5 + 127) / 255;
+    for band in (0..pixmap.height).step_by(6) {
+        // which of the band's six rows are set in each column, by color
+        let mut colors: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+        for row in band..(band + 6).min(pixmap.height) {
+            for column in 0..pixmap.width {
+                let [r, g, b] = pixmap.pixels[row * pixmap.width + column];
+                let color = level(r) * 36 + level(g) * 6 + level(b);
+                colors.entry(color).or_insert_with(|| vec![0; pixmap.width])[column] |=
+                    1 << (row - band);
+            }
+        }
+        for (index, (color, sixels)) in colors.iter().enumerate() {
+            if index > 0 {
+                // back to the start of the band, to draw over it with the next color
+                write!(out, "$")?;
+            }
+            write!(out, "#{}", color)?;
+            for run in sixels.chunk_by(|a, b| a == b) {
+                let char = (run[0] + 63) as char;
+                if run.len() > 3 {
+                    write!(out, "!{}{}", run.len(), char)?;
+                } else {
+                    write!(out, "{}", char.to_string().repeat(run.len()))?;
+                }
+            }
+        }
+        write!(out, "-")?;
+    }
+    write!(out, "\x1b\\")
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    let mut crc = Crc32::default();
+    crc.update(kind);
+    crc.update(data);
+    png.extend(crc.finish().to_be_bytes());
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+/// Removes all images drawn with `protocol`. Images drawn with the iTerm protocol are
+/// part of the cell contents, so clearing the screen is enough for those.
+pub fn clear(out: &mut impl Write, protocol: Protocol) -> io::Result<()> {
+    if protocol == Protocol::Kitty {
+        write!(out, "\x1b_Ga=d,q=2\x1b\\")?;
+    }
+    Ok(())
+}
+
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(triple >> (18 - index * 6)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
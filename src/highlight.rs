@@ -0,0 +1,235 @@
+//! Syntax highlighting of source files in previews. Rather than parsing each
+//! language, lines are split into keywords, strings, numbers and comments, which
+//! is enough to make code easier to read at a glance.
+
+use std::path::Path;
+
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::preview::Line;
+
+/// Colors of highlighted code, picked to suit the terminal's background.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyntaxTheme {
+    #[default]
+    Dark,
+    Light,
+}
+
+struct Palette {
+    keyword: Color,
+    string: Color,
+    number: Color,
+    comment: Color,
+}
+
+impl SyntaxTheme {
+    fn palette(self) -> Palette {
+        let rgb = |r, g, b| Color::Rgb { r, g, b };
+        match self {
+            SyntaxTheme::Dark => Palette {
+                keyword: rgb(198, 120, 221),
+                string: rgb(152, 195, 121),
+                number: rgb(209, 154, 102),
+                comment: rgb(127, 132, 142),
+            },
+            SyntaxTheme::Light => Palette {
+                keyword: rgb(166, 38, 164),
+                string: rgb(80, 161, 79),
+                number: rgb(152, 104, 1),
+                comment: rgb(160, 161, 167),
+            },
+        }
+    }
+}
+
+struct Language {
+    extensions: &'static [&'static str],
+    /// Separated by spaces.
+    keywords: &'static str,
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        keywords:
+            "as async await break const continue crate dyn else enum extern false fn for if impl \
+             in let loop match mod move mut pub ref return self Self static struct super trait \
+             true type unsafe use where while",
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        // single quotes also start lifetimes, so only double quotes are strings
+        quotes: &['"'],
+    },
+    Language {
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hh"],
+        keywords:
+            "auto bool break case char class const continue default delete do double else enum \
+             extern false float for if inline int long namespace new nullptr private protected \
+             public return short signed sizeof static struct switch template this true typedef \
+             union unsigned using virtual void volatile while",
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Language {
+        extensions: &["js", "jsx", "mjs", "cjs", "ts", "tsx"],
+        keywords:
+            "async await break case catch class const continue default delete do else export \
+             extends false finally for from function if import in instanceof interface let new \
+             null of return static super switch this throw true try type typeof undefined var \
+             void while yield",
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Language {
+        extensions: &["java", "kt", "cs"],
+        keywords:
+            "abstract boolean break case catch class const continue default do double else enum \
+             extends false final finally float for fun if implements import int interface long \
+             namespace new null override package private protected public return static super \
+             switch this throw throws true try using val var void when while",
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Language {
+        extensions: &["go"],
+        keywords:
+            "break case chan const continue default defer else false fallthrough for func go goto \
+             if import interface map nil package range return select struct switch true type var",
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Language {
+        extensions: &["py", "pyw"],
+        keywords: "and as assert async await break class continue def del elif else except False \
+             finally for from global if import in is lambda None nonlocal not or pass raise \
+             return True try while with yield",
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Language {
+        extensions: &["sh", "bash", "zsh"],
+        keywords:
+            "case do done elif else esac export fi for function if in local return then until \
+             while",
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Language {
+        extensions: &["lua"],
+        keywords:
+            "and break do else elseif end false for function if in local nil not or repeat return \
+             then true until while",
+        line_comments: &["--"],
+        block_comment: Some(("--[[", "]]")),
+        quotes: &['"', '\''],
+    },
+];
+
+/// Highlights `lines` of the file at `path`, if its language is known.
+pub fn highlight(path: &Path, lines: &[String], theme: SyntaxTheme) -> Option<Vec<Line>> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let language = LANGUAGES
+        .iter()
+        .find(|language| language.extensions.contains(&extension.as_str()))?;
+    let palette = theme.palette();
+    let mut in_comment = false;
+    Some(
+        lines
+            .iter()
+            .map(|line| highlight_line(line, language, &palette, &mut in_comment))
+            .collect(),
+    )
+}
+
+/// Highlights a line, continuing a block comment left open by the lines before
+/// it if `in_comment`.
+fn highlight_line(
+    text: &str,
+    language: &Language,
+    palette: &Palette,
+    in_comment: &mut bool,
+) -> Line {
+    let chars: Vec<char> = text.chars().collect();
+    let starts_with = |index: usize, pattern: &str| {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(offset, char)| chars.get(index + offset) == Some(&char))
+    };
+    let is_word = |char: char| char.is_alphanumeric() || char == '_';
+    let span = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+    let mut line = Line::default();
+    let mut index = 0;
+    while index < chars.len() {
+        let start = index;
+        if *in_comment {
+            let (_, end) = language.block_comment.unwrap_or_default();
+            index = (index..chars.len())
+                .find(|index| starts_with(*index, end))
+                .map_or(chars.len(), |found| {
+                    *in_comment = false;
+                    found + end.chars().count()
+                });
+            line.push(&span(start, index), palette.comment);
+        } else if let Some((open, _)) = language
+            .block_comment
+            .filter(|(open, _)| starts_with(index, open))
+        {
+            *in_comment = true;
+            index += open.chars().count();
+            line.push(&span(start, index), palette.comment);
+        } else if language
+            .line_comments
+            .iter()
+            .any(|comment| starts_with(index, comment))
+        {
+            line.push(&span(start, chars.len()), palette.comment);
+            break;
+        } else if language.quotes.contains(&chars[index]) {
+            let quote = chars[index];
+            index += 1;
+            while index < chars.len() && chars[index] != quote {
+                // skip escaped characters, like \"
+                index += if chars[index] == '\\' { 2 } else { 1 };
+            }
+            index = (index + 1).min(chars.len());
+            line.push(&span(start, index), palette.string);
+        } else if chars[index].is_ascii_digit() {
+            while index < chars.len() && (is_word(chars[index]) || chars[index] == '.') {
+                index += 1;
+            }
+            line.push(&span(start, index), palette.number);
+        } else if is_word(chars[index]) {
+            while index < chars.len() && is_word(chars[index]) {
+                index += 1;
+            }
+            let word = span(start, index);
+            let color = if language
+                .keywords
+                .split_whitespace()
+                .any(|keyword| keyword == word)
+            {
+                palette.keyword
+            } else {
+                Color::Reset
+            };
+            line.push(&word, color);
+        } else {
+            index += 1;
+            line.push(&span(start, index), Color::Reset);
+        }
+    }
+    line
+}
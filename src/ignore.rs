@@ -0,0 +1,199 @@
+//! Matching paths against the rules in `.gitignore` and `.ignore` files, so
+//! build output and dependencies can be left out of the listing and the
+//! recursive walks, with the `respect_ignore_files` option.
+//!
+//! The usual syntax is supported: `*`, `?`, `[...]` and `**` globs, `!` to
+//! include something again, a trailing `/` to only match directories, and a
+//! slash anywhere else anchoring the pattern to the directory of the file.
+//! Rules in deeper files, and later in the same file, take precedence.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Files read in every directory, later ones taking precedence.
+const FILES: &[&str] = &[".gitignore", ".ignore"];
+
+struct Pattern {
+    glob: Vec<char>,
+    negated: bool,
+    directory_only: bool,
+    /// Whether it's matched against the path from the ignore file's directory,
+    /// rather than just the name.
+    anchored: bool,
+}
+impl Pattern {
+    fn parse(line: &str) -> Option<Self> {
+        let mut line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let negated = line.starts_with('!');
+        // a backslash keeps a leading `!` or `#` literal
+        if negated || line.starts_with("\\!") || line.starts_with("\\#") {
+            line = &line[1..];
+        }
+        let directory_only = line.ends_with('/');
+        let line = line.trim_end_matches('/');
+        let anchored = line.contains('/');
+        let line = line.trim_start_matches('/');
+        if line.is_empty() {
+            return None;
+        }
+        Some(Pattern {
+            glob: line.chars().collect(),
+            negated,
+            directory_only,
+            anchored,
+        })
+    }
+}
+
+/// The rules of a directory, along with the ones of the directories above it.
+pub struct Ignore {
+    parent: Option<Arc<Ignore>>,
+    directory: PathBuf,
+    patterns: Vec<Pattern>,
+}
+impl Ignore {
+    /// Loads the rules applying in `directory`, from its ignore files and the
+    /// ones of its parents up to the root of the git repository it's in.
+    pub fn new(directory: &Path) -> Arc<Self> {
+        let root = directory
+            .ancestors()
+            .find(|ancestor| ancestor.join(".git").exists())
+            .unwrap_or(directory);
+        let mut directories: Vec<&Path> = directory
+            .ancestors()
+            .take_while(|ancestor| ancestor.starts_with(root))
+            .collect();
+        directories.reverse();
+        let mut ignore: Option<Arc<Ignore>> = None;
+        for directory in directories {
+            ignore = Some(Ignore::load(ignore, directory));
+        }
+        ignore.unwrap_or_else(|| Ignore::load(None, directory))
+    }
+    /// Adds the rules of `directory`, which is inside this one's.
+    pub fn child(self: &Arc<Self>, directory: &Path) -> Arc<Self> {
+        Ignore::load(Some(self.clone()), directory)
+    }
+    fn load(parent: Option<Arc<Ignore>>, directory: &Path) -> Arc<Self> {
+        let patterns = FILES
+            .iter()
+            .filter_map(|file| fs::read_to_string(directory.join(file)).ok())
+            .flat_map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(Pattern::parse)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Arc::new(Ignore {
+            parent,
+            directory: directory.to_path_buf(),
+            patterns,
+        })
+    }
+    /// Whether `path` is left out by the rules. The `.git` directory always is.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(name) = path.file_name() else {
+            return false;
+        };
+        if name == ".git" {
+            return true;
+        }
+        let name: Vec<char> = name.to_string_lossy().chars().collect();
+        let mut level = Some(self);
+        while let Some(ignore) = level {
+            if let Ok(relative) = path.strip_prefix(&ignore.directory) {
+                let relative: Vec<char> = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/")
+                    .chars()
+                    .collect();
+                let matched = ignore.patterns.iter().rev().find(|pattern| {
+                    (is_dir || !pattern.directory_only)
+                        && glob_matches(
+                            &pattern.glob,
+                            if pattern.anchored { &relative } else { &name },
+                        )
+                });
+                if let Some(pattern) = matched {
+                    return !pattern.negated;
+                }
+            }
+            level = ignore.parent.as_deref();
+        }
+        false
+    }
+}
+
+/// Matches `text` against a glob, where `*` and `?` don't match slashes but
+/// `**` does.
+pub fn glob_matches(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches no directories at all
+            if let ['/', after @ ..] = rest {
+                if glob_matches(after, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..]))
+        }
+        ['*', rest @ ..] => {
+            let segment = text
+                .iter()
+                .position(|char| *char == '/')
+                .unwrap_or(text.len());
+            (0..=segment).any(|skip| glob_matches(rest, &text[skip..]))
+        }
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(char) if *char != '/') && glob_matches(rest, &text[1..])
+        }
+        ['[', class @ ..] => match class_matches(class, text.first().copied()) {
+            Some((matched, rest)) => matched && glob_matches(rest, &text[1..]),
+            // without a closing bracket, it's just a bracket
+            None => text.first() == Some(&'[') && glob_matches(class, &text[1..]),
+        },
+        ['\\', escaped, rest @ ..] | [escaped, rest @ ..] => {
+            text.first() == Some(escaped) && glob_matches(rest, &text[1..])
+        }
+    }
+}
+
+/// Matches `char` against the character class at the start of `class` (just
+/// after the `[`). Returns whether it matched, and the glob after the class,
+/// unless the class is never closed.
+fn class_matches(class: &[char], char: Option<char>) -> Option<(bool, &[char])> {
+    let (negated, mut index) = match class.first() {
+        Some('!' | '^') => (true, 1),
+        _ => (false, 0),
+    };
+    let start = index;
+    let mut matched = false;
+    loop {
+        let first = *class.get(index)?;
+        // a bracket first in the class is part of it
+        if first == ']' && index > start {
+            break;
+        }
+        if class.get(index + 1) == Some(&'-') && class.get(index + 2).is_some_and(|end| *end != ']')
+        {
+            let last = class[index + 2];
+            matched |= char.is_some_and(|char| (first..=last).contains(&char));
+            index += 3;
+        } else {
+            matched |= char == Some(first);
+            index += 1;
+        }
+    }
+    let matched = char.is_some_and(|char| char != '/') && matched != negated;
+    Some((matched, &class[index + 1..]))
+}
@@ -0,0 +1,275 @@
+//! What keys do in the listing. Keys are looked up as actions, so the key styles
+//! only differ in the tables here.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::replay;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyStyle {
+    #[default]
+    Arrows,
+    /// hjkl movement, `gg`/`G` and ctrl+d/ctrl+u, on top of the usual keys.
+    Vim,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Top,
+    Bottom,
+    HalfPageUp,
+    HalfPageDown,
+    Open,
+    Back,
+    HistoryBack,
+    HistoryForward,
+    FindName,
+    FindContent,
+    CommandLine,
+    Shell,
+    Subshell,
+    GoTo,
+    OpenAll,
+    Reveal,
+    QuickLook,
+    ToggleWrap,
+    TogglePreview,
+    ToggleHidden,
+    ToggleDetails,
+    ToggleDualPane,
+    SwitchPane,
+    CopyToOtherPane,
+    MoveToOtherPane,
+    CycleSort,
+    ReverseSort,
+    Properties,
+    Mark,
+    BatchRename,
+    Rename,
+    Filter,
+    Bookmarks,
+    BookmarkDirectory,
+    Finder,
+    NewFile,
+    NewFileFromClipboard,
+    NewDirectory,
+    OpenWith,
+    OpenWithSystem,
+    Yank,
+    Cut,
+    Paste,
+    CopyUri,
+    Trash,
+    Delete,
+    ContextMenu,
+}
+
+/// A key, whether it's pressed with control, and what it does. Shift is left out,
+/// since it already makes letters uppercase.
+type Binding = (KeyCode, bool, Action);
+
+const COMMON: &[Binding] = &[
+    (KeyCode::Up, false, Action::Up),
+    (KeyCode::Down, false, Action::Down),
+    (KeyCode::Home, false, Action::Top),
+    (KeyCode::End, false, Action::Bottom),
+    (KeyCode::Enter, false, Action::Open),
+    (KeyCode::Right, false, Action::Open),
+    (KeyCode::Esc, false, Action::Back),
+    (KeyCode::Left, false, Action::Back),
+    (KeyCode::Char('f'), false, Action::FindName),
+    (KeyCode::Char('F'), false, Action::FindContent),
+    (KeyCode::Char(':'), false, Action::CommandLine),
+    (KeyCode::Char('!'), false, Action::Shell),
+    (KeyCode::Char('$'), false, Action::Subshell),
+    (KeyCode::Char('g'), true, Action::GoTo),
+    (KeyCode::Char('o'), false, Action::OpenAll),
+    (KeyCode::Char('r'), false, Action::Reveal),
+    (KeyCode::Char('v'), false, Action::QuickLook),
+    (KeyCode::Char('w'), false, Action::ToggleWrap),
+    (KeyCode::Char('p'), false, Action::TogglePreview),
+    (KeyCode::Char('.'), false, Action::ToggleHidden),
+    (KeyCode::Char('L'), false, Action::ToggleDetails),
+    (KeyCode::Char('T'), false, Action::ToggleDualPane),
+    (KeyCode::Tab, false, Action::SwitchPane),
+    (KeyCode::F(5), false, Action::CopyToOtherPane),
+    (KeyCode::F(6), false, Action::MoveToOtherPane),
+    (KeyCode::Char('s'), false, Action::CycleSort),
+    (KeyCode::Char('S'), false, Action::ReverseSort),
+    (KeyCode::Char('i'), false, Action::Properties),
+    (KeyCode::Char(' '), false, Action::Mark),
+    (KeyCode::Char('R'), false, Action::BatchRename),
+    (KeyCode::Char('a'), false, Action::Rename),
+    (KeyCode::F(2), false, Action::Rename),
+    (KeyCode::Char('/'), false, Action::Filter),
+    (KeyCode::Char('\''), false, Action::Bookmarks),
+    (KeyCode::Char('b'), false, Action::BookmarkDirectory),
+    (KeyCode::Char('p'), true, Action::Finder),
+    (KeyCode::Char('n'), false, Action::NewFile),
+    (KeyCode::Char('n'), true, Action::NewFileFromClipboard),
+    (KeyCode::Char('N'), false, Action::NewDirectory),
+    (KeyCode::Char('O'), false, Action::OpenWith),
+    (KeyCode::Char('X'), false, Action::OpenWithSystem),
+    (KeyCode::Char('y'), false, Action::Yank),
+    (KeyCode::Char('x'), false, Action::Cut),
+    (KeyCode::Char('P'), false, Action::Paste),
+    (KeyCode::Char('U'), false, Action::CopyUri),
+    (KeyCode::Char('d'), false, Action::Trash),
+    (KeyCode::Delete, false, Action::Trash),
+    (KeyCode::Char('D'), false, Action::Delete),
+    (KeyCode::Char('c'), false, Action::ContextMenu),
+    (KeyCode::Menu, false, Action::ContextMenu),
+];
+
+/// Looked up first when alt is held.
+const ALT: &[(KeyCode, Action)] = &[
+    (KeyCode::Left, Action::HistoryBack),
+    (KeyCode::Right, Action::HistoryForward),
+];
+
+/// Looked up before the common keys, so they can be overridden.
+const VIM: &[Binding] = &[
+    (KeyCode::Char('h'), false, Action::Back),
+    (KeyCode::Char('j'), false, Action::Down),
+    (KeyCode::Char('k'), false, Action::Up),
+    (KeyCode::Char('l'), false, Action::Open),
+    (KeyCode::Char('G'), false, Action::Bottom),
+    (KeyCode::Char('d'), true, Action::HalfPageDown),
+    (KeyCode::Char('u'), true, Action::HalfPageUp),
+    (KeyCode::Char('o'), true, Action::HistoryBack),
+];
+
+/// Actions done by typing a sequence of keys, like the chords in the config.
+const VIM_SEQUENCES: &[(&str, Action)] = &[("gg", Action::Top)];
+
+/// What can be done with the selected item, in the order the context menu lists
+/// them, described, and whether it's only for files.
+const ITEM_ACTIONS: &[(Action, &str, bool)] = &[
+    (Action::Open, "open", false),
+    (Action::OpenWith, "open with", true),
+    (Action::OpenWithSystem, "open with default app", false),
+    (Action::QuickLook, "view", true),
+    (Action::Rename, "rename", false),
+    (Action::Mark, "mark", false),
+    (Action::Yank, "copy", false),
+    (Action::Cut, "cut", false),
+    (Action::CopyUri, "copy path as URI", false),
+    (Action::Properties, "properties", false),
+    (Action::Trash, "move to trash", false),
+    (Action::Delete, "delete", false),
+];
+
+/// Returns the actions applying to the selected item, for the context menu.
+pub fn item_actions(is_dir: bool) -> Vec<Action> {
+    ITEM_ACTIONS
+        .iter()
+        .filter(|(_, _, files_only)| !(is_dir && *files_only))
+        .map(|(action, _, _)| *action)
+        .collect()
+}
+
+/// Describes `action` along with the key doing it, like `rename (a)`.
+pub fn describe(action: Action, style: KeyStyle) -> String {
+    let label = ITEM_ACTIONS
+        .iter()
+        .find(|(item_action, _, _)| *item_action == action)
+        .map_or("", |(_, label, _)| label);
+    let bindings = match style {
+        KeyStyle::Arrows => &[][..],
+        KeyStyle::Vim => VIM,
+    };
+    let key = bindings
+        .iter()
+        .chain(COMMON)
+        .find(|(_, _, bound)| *bound == action)
+        .map(|(code, control, _)| key_name(*code, *control));
+    match key {
+        Some(key) => format!("{} ({})", label, key),
+        None => label.to_string(),
+    }
+}
+
+fn key_name(code: KeyCode, control: bool) -> String {
+    let name = match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(char) => char.to_string(),
+        KeyCode::F(number) => format!("F{}", number),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        code => format!("{:?}", code).to_lowercase(),
+    };
+    if control {
+        format!("ctrl+{}", name)
+    } else {
+        name
+    }
+}
+
+/// Returns what `key` does in the listing.
+pub fn action(key: KeyEvent, style: KeyStyle) -> Option<Action> {
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        if let Some((_, action)) = ALT.iter().find(|(code, _)| *code == key.code) {
+            return Some(*action);
+        }
+    }
+    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+    let bindings = match style {
+        KeyStyle::Arrows => &[][..],
+        KeyStyle::Vim => VIM,
+    };
+    bindings
+        .iter()
+        .chain(COMMON)
+        .find(|(code, with_control, _)| *code == key.code && *with_control == control)
+        .map(|(_, _, action)| *action)
+}
+
+/// A command run by a key of the user's choosing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomCommand {
+    /// Written like with `--keys`, like `g` or `<c-g>`.
+    pub key: String,
+    pub command: Vec<String>,
+    /// Keep its output up until a key is pressed.
+    #[serde(default)]
+    pub wait: bool,
+}
+
+/// Returns the custom command `key` runs, if any. These go before the usual
+/// bindings.
+pub fn custom(key: KeyEvent, commands: &[CustomCommand]) -> Option<&CustomCommand> {
+    commands.iter().find(|command| {
+        // shift is already in which character was typed
+        let modifiers = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        replay::parse_key(&command.key).is_some_and(|bound| {
+            bound.code == key.code && bound.modifiers & modifiers == key.modifiers & modifiers
+        })
+    })
+}
+
+/// Returns the key sequences of the style, and what they do.
+pub fn sequences(style: KeyStyle) -> &'static [(&'static str, Action)] {
+    match style {
+        KeyStyle::Arrows => &[],
+        KeyStyle::Vim => VIM_SEQUENCES,
+    }
+}
+
+/// Translates the movement keys of the style to arrow keys, for the views that
+/// only handle those.
+pub fn translate(code: KeyCode, style: KeyStyle) -> KeyCode {
+    if style != KeyStyle::Vim {
+        return code;
+    }
+    match code {
+        KeyCode::Char('h') => KeyCode::Left,
+        KeyCode::Char('j') => KeyCode::Down,
+        KeyCode::Char('k') => KeyCode::Up,
+        KeyCode::Char('l') => KeyCode::Right,
+        code => code,
+    }
+}
@@ -0,0 +1,888 @@
+//! Handling keypresses, in each mode, and the actions they're bound to.
+
+use std::{cmp, io, path::PathBuf};
+
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind,
+};
+
+use crate::{
+    attributes, bookmarks, clipboard,
+    command::{self, Choices},
+    drives, fileops,
+    keymap::{self, Action},
+    mode::{Confirmation, Elevated, Mode},
+    navigation,
+    prompt::{History, Prompt, PromptEvent},
+    rename,
+    search::SearchKind,
+    sort::SortBy,
+    sysclip, Fee,
+};
+
+impl Fee {
+    fn handle_rename_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        let Mode::Rename(_, prompt) = &mut self.mode else {
+            return Ok(());
+        };
+        let name = match prompt.handle_key(key, &History::default(), &mut self.kill_ring) {
+            PromptEvent::None => return Ok(()),
+            PromptEvent::Cancel => {
+                self.mode = Mode::Normal;
+                return Ok(());
+            }
+            PromptEvent::Submit(name) => name,
+        };
+        let Mode::Rename(from, _) = &self.mode else {
+            return Ok(());
+        };
+        let from = from.clone();
+        let to = from.with_file_name(&name);
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            self.show_message(format!("invalid name: '{}'", name));
+            return Ok(());
+        }
+        self.mode = Mode::Normal;
+        if to == from {
+            return Ok(());
+        }
+        if to.symlink_metadata().is_ok() {
+            self.show_message(format!("'{}' already exists", name));
+            return Ok(());
+        }
+        let old_name = from.file_name().unwrap_or_default().to_string_lossy();
+        let action = format!("rename '{}' to '{}'", old_name, name);
+        let elevated = Elevated::new(action, &["mv"], [from.as_path(), to.as_path()]);
+        let checks = from.parent().map(fileops::check_writable);
+        if !self.permitted(checks, Some(elevated)) {
+            return Ok(());
+        }
+        if self.config.dry_run {
+            let action = format!("rename {} -> {}", from.display(), to.display());
+            self.rehearse(vec![action]);
+            return Ok(());
+        }
+        std::fs::rename(&from, &to)?;
+        self.audit(&[format!("rename {} -> {}", from.display(), to.display())]);
+        self.follow_move(&from, &to);
+        if let Some(view) = &mut self.virtual_view {
+            for hit in view.hits.iter_mut().filter(|hit| hit.path == from) {
+                hit.path = to.clone();
+            }
+        }
+        self.reload()?;
+        self.select_path(&to)
+    }
+    fn handle_create_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        let Mode::Create {
+            directory,
+            prompt,
+            contents,
+        } = &mut self.mode
+        else {
+            return Ok(());
+        };
+        let directory = *directory;
+        match prompt.handle_key(key, &History::default(), &mut self.kill_ring) {
+            PromptEvent::None => {}
+            PromptEvent::Cancel => self.mode = Mode::Normal,
+            PromptEvent::Submit(name) => {
+                let contents = contents.take();
+                self.mode = Mode::Normal;
+                self.create(&name, directory, contents.as_deref())?;
+            }
+        }
+        Ok(())
+    }
+    fn handle_paste_keypress(&mut self, code: KeyCode) -> io::Result<()> {
+        let Mode::Paste(paste) = &mut self.mode else {
+            return Ok(());
+        };
+        let resolution = match code {
+            KeyCode::Char('o' | 'O') => clipboard::Resolution::Overwrite,
+            KeyCode::Char('k' | 'K') => clipboard::Resolution::KeepBoth,
+            KeyCode::Char('s' | 'S') => clipboard::Resolution::Skip,
+            KeyCode::Esc => {
+                paste.pending.clear();
+                let Mode::Paste(paste) = std::mem::replace(&mut self.mode, Mode::Normal) else {
+                    return Ok(());
+                };
+                return self.continue_paste(paste);
+            }
+            _ => return Ok(()),
+        };
+        if matches!(code, KeyCode::Char(char) if char.is_uppercase()) {
+            paste.resolve_all = Some(resolution);
+        }
+        let Mode::Paste(mut paste) = std::mem::replace(&mut self.mode, Mode::Normal) else {
+            return Ok(());
+        };
+        let skipped = matches!(resolution, clipboard::Resolution::Skip);
+        if let Some(from) = paste.conflict().filter(|_| !skipped) {
+            let message = format!("{} {}", paste.clipboard.progress_verb(), from.display());
+            self.show_progress(message)?;
+        }
+        paste.paste(Some(resolution));
+        self.continue_paste(paste)
+    }
+    fn handle_confirm_keypress(&mut self, code: KeyCode) -> io::Result<()> {
+        let confirmed = match code {
+            KeyCode::Char('y' | 'Y') => true,
+            KeyCode::Char('n' | 'N') => false,
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        let Mode::Confirm(confirmation) = std::mem::replace(&mut self.mode, Mode::Normal) else {
+            return Ok(());
+        };
+        match confirmation {
+            Confirmation::Delete { paths, permanently } if confirmed => {
+                self.delete(paths, permanently)
+            }
+            Confirmation::Delete { .. } => Ok(()),
+            Confirmation::Repack(_, _, destination) => self.leave_archive(confirmed, destination),
+            Confirmation::Elevate { elevated, .. } if confirmed => {
+                self.run_elevated(elevated.command)
+            }
+            Confirmation::Elevate { .. } => Ok(()),
+        }
+    }
+    fn handle_batch_rename_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        let Mode::BatchRename(batch) = &mut self.mode else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Tab | KeyCode::BackTab => batch.editing_replace = !batch.editing_replace,
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                batch.matching = batch.matching.next()
+            }
+            _ => {
+                let history = History::default();
+                match batch
+                    .focused()
+                    .handle_key(key, &history, &mut self.kill_ring)
+                {
+                    PromptEvent::None => {}
+                    PromptEvent::Cancel => self.mode = Mode::Normal,
+                    PromptEvent::Submit(_) => {
+                        let renames = match batch.plan() {
+                            Ok(renames) => renames,
+                            Err(error) => {
+                                self.show_message(error);
+                                return Ok(());
+                            }
+                        };
+                        self.mode = Mode::Normal;
+                        let checks = renames
+                            .iter()
+                            .filter_map(|(from, _)| from.parent())
+                            .map(fileops::check_writable);
+                        if !self.permitted(checks, None) {
+                            return Ok(());
+                        }
+                        let actions: Vec<String> = renames
+                            .iter()
+                            .map(|(from, to)| {
+                                format!("rename {} -> {}", from.display(), to.display())
+                            })
+                            .collect();
+                        if self.config.dry_run {
+                            self.rehearse(actions);
+                            return Ok(());
+                        }
+                        rename::apply(&renames)?;
+                        self.audit(&actions);
+                        // keep the renamed files marked
+                        for (from, to) in &renames {
+                            self.follow_move(from, to);
+                        }
+                        self.show_message(format!("renamed {} files", renames.len()));
+                        self.set_contents(self.get_contents()?);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    fn handle_bookmarks_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        let Mode::Bookmarks(picker) = &mut self.mode else {
+            return Ok(());
+        };
+        let bookmarks = &mut self.config.bookmarks;
+        let selected = picker.selected();
+        match &mut picker.editing {
+            bookmarks::Editing::Key => {
+                picker.editing = bookmarks::Editing::None;
+                let Some(index) = selected else {
+                    return Ok(());
+                };
+                match key.code {
+                    KeyCode::Char(char) => {
+                        // a key only leads to one bookmark
+                        for bookmark in bookmarks.iter_mut() {
+                            if bookmark.key == Some(char) {
+                                bookmark.key = None;
+                            }
+                        }
+                        bookmarks[index].key = Some(char);
+                    }
+                    KeyCode::Backspace | KeyCode::Delete => bookmarks[index].key = None,
+                    _ => return Ok(()),
+                }
+                return self.sync_bookmarks();
+            }
+            bookmarks::Editing::Adding(prompt) | bookmarks::Editing::Renaming(prompt) => {
+                let name = match prompt.handle_key(key, &History::default(), &mut self.kill_ring) {
+                    PromptEvent::None => return Ok(()),
+                    PromptEvent::Cancel => {
+                        picker.editing = bookmarks::Editing::None;
+                        return Ok(());
+                    }
+                    PromptEvent::Submit(name) => name.trim().to_string(),
+                };
+                let adding = matches!(picker.editing, bookmarks::Editing::Adding(_));
+                picker.editing = bookmarks::Editing::None;
+                if name.is_empty() {
+                    return Ok(());
+                }
+                if adding {
+                    // added to the group of the selected bookmark, just after it
+                    let index = selected.map_or(bookmarks.len(), |index| index + 1);
+                    let bookmark = bookmarks::Bookmark {
+                        name,
+                        path: self.cwd.to_string_lossy().to_string(),
+                        key: None,
+                        group: selected.and_then(|index| bookmarks[index].group.clone()),
+                    };
+                    bookmarks.insert(index, bookmark);
+                    picker.refresh(bookmarks, index);
+                } else if let Some(index) = selected {
+                    bookmarks[index].name = name;
+                }
+                return self.sync_bookmarks();
+            }
+            bookmarks::Editing::None => {}
+        }
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let count = picker.order.len();
+        match key.code {
+            KeyCode::Up if count > 0 => picker.selection = (picker.selection + count - 1) % count,
+            KeyCode::Down if count > 0 => picker.selection = (picker.selection + 1) % count,
+            KeyCode::Char('a') if control => {
+                let mut prompt = Prompt::default();
+                let name = self.cwd.file_name().unwrap_or(self.cwd.as_os_str());
+                prompt.set_text(&name.to_string_lossy());
+                picker.editing = bookmarks::Editing::Adding(prompt);
+            }
+            KeyCode::Char('r') | KeyCode::F(2) if control || key.code == KeyCode::F(2) => {
+                if let Some(index) = selected {
+                    let mut prompt = Prompt::default();
+                    prompt.set_text(&bookmarks[index].name);
+                    picker.editing = bookmarks::Editing::Renaming(prompt);
+                }
+            }
+            KeyCode::Char('k') if control && selected.is_some() => {
+                picker.editing = bookmarks::Editing::Key
+            }
+            KeyCode::Char('d') | KeyCode::Delete
+                if (control || key.code == KeyCode::Delete) && selected.is_some() =>
+            {
+                let index = selected.unwrap_or_default();
+                bookmarks.remove(index);
+                let next = cmp::min(index, bookmarks.len().saturating_sub(1));
+                picker.refresh(bookmarks, next);
+                return self.sync_bookmarks();
+            }
+            KeyCode::Enter | KeyCode::Right => {
+                if let Some(index) = selected {
+                    let path = bookmarks[index].path.clone();
+                    self.mode = Mode::Normal;
+                    self.go_to(&path)?;
+                }
+            }
+            KeyCode::Esc | KeyCode::Left => self.mode = Mode::Normal,
+            KeyCode::Char(char) if !control => {
+                if let Some(bookmark) = bookmarks.iter().find(|bookmark| bookmark.key == Some(char))
+                {
+                    let path = bookmark.path.clone();
+                    self.mode = Mode::Normal;
+                    self.go_to(&path)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    fn handle_finder_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        let Mode::Finder(finder) = &mut self.mode else {
+            return Ok(());
+        };
+        let count = finder.matches.len();
+        match key.code {
+            KeyCode::Up if count > 0 => finder.selection = (finder.selection + count - 1) % count,
+            KeyCode::Down if count > 0 => finder.selection = (finder.selection + 1) % count,
+            _ => {
+                let query = finder.prompt.text();
+                match finder
+                    .prompt
+                    .handle_key(key, &History::default(), &mut self.kill_ring)
+                {
+                    PromptEvent::None => {
+                        if finder.prompt.text() != query {
+                            finder.update();
+                        }
+                    }
+                    PromptEvent::Cancel => self.mode = Mode::Normal,
+                    PromptEvent::Submit(_) => {
+                        let selected = finder.selected();
+                        self.mode = Mode::Normal;
+                        match selected {
+                            Some(path) => self.jump_to(&path)?,
+                            None => self.show_message("no matches".to_string()),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    fn handle_drives_keypress(&mut self, code: KeyCode) -> io::Result<()> {
+        let Mode::Drives { drives, selection } = &mut self.mode else {
+            return Ok(());
+        };
+        match code {
+            KeyCode::Up => *selection = selection.saturating_sub(1),
+            KeyCode::Down => *selection = cmp::min(*selection + 1, drives.len().saturating_sub(1)),
+            KeyCode::Enter | KeyCode::Right => {
+                let Some(drive) = drives.get(*selection) else {
+                    return Ok(());
+                };
+                if drive.mount_point.is_none() {
+                    drives::mount(drive)?;
+                }
+                // find out where it ended up
+                let device = drive.device.clone();
+                let mount_point = drives::list()?
+                    .into_iter()
+                    .find(|drive| drive.device == device)
+                    .and_then(|drive| drive.mount_point);
+                if let Some(mount_point) = mount_point {
+                    self.mode = Mode::Normal;
+                    self.change_directory(&mount_point.to_string_lossy())?;
+                }
+            }
+            KeyCode::Char('u') => {
+                if let Some(drive) = drives.get(*selection) {
+                    drives::unmount(drive)?;
+                    let message = format!("unmounted {}", drive.device);
+                    self.show_message(message);
+                    self.refresh_drives()?;
+                }
+            }
+            KeyCode::Char('e') => {
+                if let Some(drive) = drives.get(*selection).cloned() {
+                    self.eject_drive(&drive)?;
+                }
+            }
+            KeyCode::Char('r') => self.refresh_drives()?,
+            KeyCode::Esc | KeyCode::Left | KeyCode::Char('q') => self.mode = Mode::Normal,
+            _ => {}
+        }
+        Ok(())
+    }
+    fn handle_attributes_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        let Mode::Attributes(batch) = &mut self.mode else {
+            return Ok(());
+        };
+        match batch
+            .prompt
+            .handle_key(key, &History::default(), &mut self.kill_ring)
+        {
+            PromptEvent::None => {}
+            PromptEvent::Cancel => self.mode = Mode::Normal,
+            PromptEvent::Submit(text) => {
+                let Some(change) = attributes::Change::parse(&text) else {
+                    self.show_message(format!("'{}' isn't a valid change", text));
+                    return Ok(());
+                };
+                let changes: Vec<(PathBuf, u32)> = batch
+                    .affected(&change)
+                    .map(|(path, mode)| (path.clone(), mode))
+                    .collect();
+                self.mode = Mode::Normal;
+                self.change_modes(changes)?;
+            }
+        }
+        Ok(())
+    }
+    fn handle_quick_look_keypress(&mut self, code: KeyCode) -> io::Result<()> {
+        let height = self.terminal_height()? as usize;
+        let width = self.backend.size()?.0 as usize;
+        let Mode::QuickLook {
+            preview, scroll, ..
+        } = &mut self.mode
+        else {
+            return Ok(());
+        };
+        let line_count = preview.layout(width, self.wrap_previews).len();
+        let max_scroll = line_count.saturating_sub(height);
+        match code {
+            KeyCode::Up => *scroll = scroll.saturating_sub(1),
+            KeyCode::Down => *scroll = cmp::min(*scroll + 1, max_scroll),
+            KeyCode::PageUp => *scroll = scroll.saturating_sub(height),
+            KeyCode::PageDown => *scroll = cmp::min(*scroll + height, max_scroll),
+            KeyCode::Home => *scroll = 0,
+            KeyCode::End => *scroll = max_scroll,
+            KeyCode::Char('w') => {
+                self.wrap_previews = !self.wrap_previews;
+                *scroll = 0;
+            }
+            KeyCode::Esc | KeyCode::Left | KeyCode::Char('q') | KeyCode::Char('v') => {
+                self.mode = Mode::Normal
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    /// Selects the item right-clicked at `x`, `y`, and opens its context menu.
+    fn handle_right_click(&mut self, x: u16, y: u16) -> io::Result<()> {
+        if !matches!(self.mode, Mode::Normal) {
+            return Ok(());
+        }
+        let list = self.layout()?.list;
+        if x < list.x || x >= list.x + list.width || y < list.y || y >= list.y + list.height {
+            return Ok(());
+        }
+        let index = self.scroll + (y - list.y) as usize;
+        if index >= self.current_contents.len() {
+            return Ok(());
+        }
+        self.select_index(index)?;
+        self.show_context_menu();
+        Ok(())
+    }
+    fn handle_context_keypress(&mut self, code: KeyCode) -> io::Result<()> {
+        let Mode::Context { actions, selection } = &mut self.mode else {
+            return Ok(());
+        };
+        match code {
+            KeyCode::Up => *selection = selection.saturating_sub(1),
+            KeyCode::Down => *selection = cmp::min(*selection + 1, actions.len() - 1),
+            KeyCode::Enter | KeyCode::Right => {
+                let action = actions[*selection];
+                self.mode = Mode::Normal;
+                self.perform(action)?;
+            }
+            KeyCode::Esc | KeyCode::Left => self.mode = Mode::Normal,
+            _ => {}
+        }
+        Ok(())
+    }
+    fn handle_open_with_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        let Mode::OpenWith(menu) = &mut self.mode else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Up => menu.selection = menu.selection.saturating_sub(1),
+            KeyCode::Down => {
+                menu.selection = cmp::min(menu.selection + 1, menu.openers.len().saturating_sub(1))
+            }
+            _ => match menu
+                .prompt
+                .handle_key(key, &History::default(), &mut self.kill_ring)
+            {
+                PromptEvent::None => {}
+                PromptEvent::Cancel => self.mode = Mode::Normal,
+                PromptEvent::Submit(_) => {
+                    let (path, chosen) = (menu.path.clone(), menu.chosen());
+                    self.mode = Mode::Normal;
+                    let Some(command) = chosen else {
+                        return Ok(());
+                    };
+                    let parts = command::expand_parts(&command, Some(&path), &[], &self.cwd);
+                    self.last_openers.remember(&path, command);
+                    self.run_editor(parts)?;
+                }
+            },
+        }
+        Ok(())
+    }
+    /// Completes the command line, or cycles through the completions if there are several.
+    fn complete_command(&mut self, backwards: bool) {
+        let Mode::Command(prompt, menu) = &mut self.mode else {
+            return;
+        };
+        if let Some(menu) = menu {
+            prompt.set_text(menu.cycle(backwards));
+            return;
+        }
+        let (bookmarks, mark_sets) = (&self.config.bookmarks, &self.saved_marks);
+        let candidates = command::complete(&prompt.text(), &self.cwd, |choices| match choices {
+            Choices::SortKeys => SortBy::ALL.iter().map(|by| by.name().to_string()).collect(),
+            Choices::Bookmarks => bookmarks
+                .iter()
+                .map(|bookmark| bookmark.name.clone())
+                .collect(),
+            Choices::MarkSets => mark_sets.keys().cloned().collect(),
+        });
+        match candidates.len() {
+            0 => {}
+            1 => prompt.set_text(&candidates[0].line),
+            _ => {
+                let lines: Vec<&str> = candidates.iter().map(|c| c.line.as_str()).collect();
+                let prefix = command::common_prefix(&lines);
+                if prefix.chars().count() > prompt.text().chars().count() {
+                    prompt.set_text(&prefix);
+                }
+                *menu = Some(command::Menu {
+                    candidates,
+                    selected: None,
+                });
+            }
+        }
+    }
+    fn handle_command_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        if matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+            self.complete_command(key.code == KeyCode::BackTab);
+            return Ok(());
+        }
+        let Mode::Command(prompt, menu) = &mut self.mode else {
+            return Ok(());
+        };
+        *menu = None;
+        match prompt.handle_key(key, &self.command_history, &mut self.kill_ring) {
+            PromptEvent::None => {}
+            PromptEvent::Cancel => self.mode = Mode::Normal,
+            PromptEvent::Submit(input) => {
+                self.mode = Mode::Normal;
+                self.command_history.push(&input);
+                self.run_command(&input)?;
+            }
+        }
+        Ok(())
+    }
+    fn handle_search_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        let Mode::Search(_, prompt) = &mut self.mode else {
+            return Ok(());
+        };
+        match prompt.handle_key(key, &self.search_history, &mut self.kill_ring) {
+            PromptEvent::None => {}
+            PromptEvent::Cancel => self.mode = Mode::Normal,
+            PromptEvent::Submit(query) => {
+                if let Mode::Search(kind, _) = std::mem::replace(&mut self.mode, Mode::Normal) {
+                    if !query.is_empty() {
+                        self.search_history.push(&query);
+                        self.save_history("search", &query);
+                        self.search(kind, query)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    fn handle_filter_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        let Mode::Filter(filter) = &mut self.mode else {
+            return Ok(());
+        };
+        let count = filter.matches.len();
+        // up and down go through past filters until something is typed
+        let recalling = filter.prompt.browsing_history()
+            || (filter.prompt.text().is_empty() && !self.filter_history.is_empty());
+        match key.code {
+            KeyCode::Up if count > 0 && !recalling => {
+                filter.selection = (filter.selection + count - 1) % count
+            }
+            KeyCode::Down if count > 0 && !recalling => {
+                filter.selection = (filter.selection + 1) % count
+            }
+            _ => {
+                let query = filter.prompt.text();
+                match filter
+                    .prompt
+                    .handle_key(key, &self.filter_history, &mut self.kill_ring)
+                {
+                    PromptEvent::None => {
+                        if filter.prompt.text() != query {
+                            // once edited, up and down go through the matches again
+                            if !matches!(key.code, KeyCode::Up | KeyCode::Down) {
+                                filter.prompt.stop_browsing_history();
+                            }
+                            let names: Vec<&str> = self
+                                .current_contents
+                                .iter()
+                                .map(|item| item.name.as_str())
+                                .collect();
+                            filter.update(&names);
+                        }
+                    }
+                    PromptEvent::Cancel => {
+                        (self.selection, self.scroll) = filter.previous;
+                        self.mode = Mode::Normal;
+                        return Ok(());
+                    }
+                    PromptEvent::Submit(query) => {
+                        // the listing already follows the selected match
+                        if filter.selected().is_none() {
+                            (self.selection, self.scroll) = filter.previous;
+                            self.show_message("no matches".to_string());
+                        }
+                        self.mode = Mode::Normal;
+                        self.filter_history.push(&query);
+                        self.save_history("filter", &query);
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        // keep the listing on the selected match, so the preview and status
+        // bar show it
+        if let Some(index) = filter.selected() {
+            self.select_index(index)?;
+        }
+        Ok(())
+    }
+    /// Whether `key` is the first key of a chord.
+    fn starts_chord(&self, key: KeyEvent) -> bool {
+        // chords are typed without control, so ctrl+g isn't the g of one
+        if key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            return false;
+        }
+        match key.code {
+            KeyCode::Char(char) => self.is_chord_prefix(&char.to_string()),
+            _ => false,
+        }
+    }
+    /// Whether some chord, from the config or the key style, starts with `typed`.
+    fn is_chord_prefix(&self, typed: &str) -> bool {
+        let sequences = keymap::sequences(self.config.key_style);
+        self.config
+            .chords
+            .keys()
+            .any(|chord| chord.starts_with(typed))
+            || sequences.iter().any(|(keys, _)| keys.starts_with(typed))
+    }
+    /// Adds the key to the chord being typed, running its command once it's
+    /// complete. Any other key cancels it.
+    fn handle_chord_keypress(&mut self, code: KeyCode) -> io::Result<()> {
+        let mut typed = self.chord.take().unwrap_or_default();
+        let KeyCode::Char(char) = code else {
+            return Ok(());
+        };
+        typed.push(char);
+        if let Some(command) = self.config.chords.get(&typed).cloned() {
+            return self.run_command(&command);
+        }
+        let sequences = keymap::sequences(self.config.key_style);
+        if let Some((_, action)) = sequences.iter().find(|(keys, _)| *keys == typed) {
+            return self.perform(*action);
+        }
+        if self.is_chord_prefix(&typed) {
+            self.status_message = Some(typed.clone());
+            self.chord = Some(typed);
+        } else {
+            self.show_message(format!("no chord {}", typed));
+        }
+        Ok(())
+    }
+    fn handle_normal_keypress(&mut self, key: KeyEvent) -> io::Result<()> {
+        match keymap::action(key, self.config.key_style) {
+            Some(action) => self.perform(action),
+            None => Ok(()),
+        }
+    }
+    fn perform(&mut self, action: Action) -> io::Result<()> {
+        match action {
+            Action::Up => self.move_up()?,
+            Action::Down => self.move_down()?,
+            Action::Top => self.move_to(0)?,
+            Action::Bottom => self.move_to(self.current_contents.len().saturating_sub(1))?,
+            Action::HalfPageUp => {
+                let half = self.list_height()? as usize / 2;
+                self.move_to(self.selection.saturating_sub(half))?
+            }
+            Action::HalfPageDown => {
+                let half = self.list_height()? as usize / 2;
+                let last = self.current_contents.len().saturating_sub(1);
+                self.move_to(cmp::min(self.selection + half, last))?
+            }
+            Action::Open => self.select()?,
+            Action::Back => self.go_back()?,
+            Action::FindName => self.mode = Mode::Search(SearchKind::Name, Prompt::default()),
+            Action::FindContent => self.mode = Mode::Search(SearchKind::Content, Prompt::default()),
+            Action::CommandLine => self.mode = Mode::Command(Prompt::default(), None),
+            Action::Shell => {
+                let mut prompt = Prompt::default();
+                prompt.set_text("!");
+                self.mode = Mode::Command(prompt, None);
+            }
+            Action::Subshell => self.open_subshell()?,
+            Action::GoTo => {
+                let mut prompt = Prompt::default();
+                prompt.set_text("cd ");
+                self.mode = Mode::Command(prompt, None);
+            }
+            Action::OpenAll => self.open_all_hits()?,
+            Action::Reveal => self.reveal()?,
+            Action::QuickLook => self.quick_look()?,
+            Action::ToggleWrap => self.wrap_previews = !self.wrap_previews,
+            Action::TogglePreview => self.show_preview = !self.show_preview,
+            Action::ToggleHidden => self.toggle_hidden()?,
+            Action::ToggleDetails => self.view.details = !self.view.details,
+            Action::ToggleDualPane => self.toggle_dual_pane()?,
+            Action::SwitchPane => self.switch_pane()?,
+            Action::CopyToOtherPane => self.transfer_to_other_pane(false)?,
+            Action::MoveToOtherPane => self.transfer_to_other_pane(true)?,
+            Action::CycleSort => self.cycle_sort(false)?,
+            Action::ReverseSort => self.cycle_sort(true)?,
+            Action::Properties => self.show_properties()?,
+            Action::Mark => self.toggle_mark()?,
+            Action::BatchRename => self.open_batch_rename(),
+            Action::Rename => self.start_rename(),
+            Action::Filter => self.start_filter(),
+            Action::Bookmarks => self.show_bookmarks()?,
+            Action::HistoryBack => self.move_through_history(false)?,
+            Action::HistoryForward => self.move_through_history(true)?,
+            Action::BookmarkDirectory => self.bookmark_directory()?,
+            Action::Finder => self.show_finder(),
+            Action::NewFile => {
+                self.mode = Mode::Create {
+                    directory: false,
+                    prompt: Prompt::default(),
+                    contents: None,
+                }
+            }
+            Action::NewFileFromClipboard => match sysclip::paste() {
+                Ok(contents) if contents.is_empty() => {
+                    self.show_message("the clipboard is empty".to_string())
+                }
+                Ok(contents) => {
+                    self.mode = Mode::Create {
+                        directory: false,
+                        prompt: Prompt::default(),
+                        contents: Some(contents),
+                    }
+                }
+                Err(error) => self.show_message(format!("couldn't paste: {}", error)),
+            },
+            Action::NewDirectory => {
+                self.mode = Mode::Create {
+                    directory: true,
+                    prompt: Prompt::default(),
+                    contents: None,
+                }
+            }
+            Action::OpenWith => self.show_open_with(),
+            Action::OpenWithSystem => {
+                if let Some(path) = self.selected_item().map(|item| item.path.clone()) {
+                    self.open_with_system(&path);
+                }
+            }
+            Action::ContextMenu => self.show_context_menu(),
+            Action::Yank => self.yank(false),
+            Action::Cut => self.yank(true),
+            Action::Paste => self.paste()?,
+            Action::CopyUri => self.copy_uris()?,
+            Action::Trash => self.confirm_delete(false),
+            Action::Delete => self.confirm_delete(true),
+        }
+        Ok(())
+    }
+    pub fn handle_keypress(&mut self, event: Event) -> io::Result<()> {
+        if let Event::Resize(..) = event {
+            return self.update();
+        }
+        if let Event::FocusLost = event {
+            self.focused = false;
+            return Ok(());
+        }
+        if let Event::FocusGained = event {
+            self.focused = true;
+            // catch up on whatever changed while paused
+            return self.update();
+        }
+        if let Event::Mouse(mouse) = event {
+            if mouse.kind == MouseEventKind::Down(MouseButton::Right) {
+                self.status_message = None;
+                self.handle_right_click(mouse.column, mouse.row)?;
+                return self.update();
+            }
+        }
+        if let Event::Key(key) = event {
+            if key.kind == KeyEventKind::Press {
+                self.status_message = None;
+                let previous_cwd = self.cwd.clone();
+                let previous_selected = self.selected_item().map(|item| item.path.clone());
+                let previous_pane = self.right_pane_active;
+                let result = if key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(KeyModifiers::CONTROL)
+                {
+                    self.quit();
+                    Ok(())
+                } else if matches!(self.mode, Mode::Search(..)) {
+                    self.handle_search_keypress(key)
+                } else if matches!(self.mode, Mode::Command(..)) {
+                    self.handle_command_keypress(key)
+                } else if matches!(self.mode, Mode::QuickLook { .. }) {
+                    self.handle_quick_look_keypress(keymap::translate(
+                        key.code,
+                        self.config.key_style,
+                    ))
+                } else if matches!(self.mode, Mode::BatchRename(_)) {
+                    self.handle_batch_rename_keypress(key)
+                } else if matches!(self.mode, Mode::Drives { .. }) {
+                    self.handle_drives_keypress(keymap::translate(key.code, self.config.key_style))
+                } else if matches!(self.mode, Mode::Finder(_)) {
+                    self.handle_finder_keypress(key)
+                } else if matches!(self.mode, Mode::Bookmarks(_)) {
+                    self.handle_bookmarks_keypress(key)
+                } else if matches!(self.mode, Mode::Filter(_)) {
+                    self.handle_filter_keypress(key)
+                } else if matches!(self.mode, Mode::Create { .. }) {
+                    self.handle_create_keypress(key)
+                } else if matches!(self.mode, Mode::Rename(..)) {
+                    self.handle_rename_keypress(key)
+                } else if matches!(self.mode, Mode::Confirm(_)) {
+                    self.handle_confirm_keypress(key.code)
+                } else if matches!(self.mode, Mode::Paste(_)) {
+                    self.handle_paste_keypress(key.code)
+                } else if matches!(self.mode, Mode::OpenWith(_)) {
+                    self.handle_open_with_keypress(key)
+                } else if matches!(self.mode, Mode::Attributes(_)) {
+                    self.handle_attributes_keypress(key)
+                } else if matches!(self.mode, Mode::Context { .. }) {
+                    self.handle_context_keypress(keymap::translate(key.code, self.config.key_style))
+                } else if let Some(command) = keymap::custom(key, &self.config.custom_commands)
+                    .filter(|_| self.chord.is_none())
+                    .cloned()
+                {
+                    self.run_custom_command(&command)
+                } else if self.chord.is_some() || self.starts_chord(key) {
+                    self.handle_chord_keypress(key.code)
+                } else {
+                    self.handle_normal_keypress(key)
+                };
+                // a failed action shouldn't take down the whole program
+                if let Err(error) = result {
+                    self.show_message(format!("error: {}", error));
+                }
+                self.entered_directory = self.cwd != previous_cwd;
+                // switching panes isn't going anywhere
+                if self.entered_directory && self.right_pane_active == previous_pane {
+                    let from = navigation::Visit {
+                        directory: previous_cwd,
+                        selected: previous_selected,
+                    };
+                    self.navigation.visit(from, &self.cwd);
+                }
+                self.update()?;
+            }
+        }
+        Ok(())
+    }
+}
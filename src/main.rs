@@ -1,252 +1,2168 @@
 use std::{
     cmp,
-    collections::VecDeque,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     env::current_dir,
-    io::{self, stdout, Error, Read, Stdout, Write},
-    path::{Path, PathBuf},
-    process::Command,
+    ffi::OsString,
+    io::{self, Error, IsTerminal, Read, Write},
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 
+use backend::{Backend, Events};
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    event::{
+        DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event,
+        KeyEventKind,
+    },
     queue,
-    style::{Color, ResetColor, SetBackgroundColor, SetForegroundColor},
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    style::{Color, Print, ResetColor},
+    terminal::{Clear, ClearType},
 };
-use dirs::config_dir;
+use keymap::KeyStyle;
+use mode::{describe_deletion, describe_items, Confirmation, Elevated, Mode};
+use preview::Preview;
+use prompt::{History, KillRing, Prompt};
+use properties::Special;
+use rules::AgeHeat;
+use search::{Hit, SearchKind};
 use serde::{Deserialize, Serialize};
+use sort::SortBy;
+use statusbar::StatusBar;
+use theme::Theme;
+
+mod archive;
+mod attributes;
+mod audit;
+mod backend;
+mod bookmarks;
+mod cli;
+mod clipboard;
+mod command;
+mod crc32;
+mod dirstat;
+mod draw;
+mod drives;
+mod fileops;
+mod filter;
+mod finder;
+mod gitlog;
+mod graphics;
+mod highlight;
+mod ignore;
+mod keymap;
+mod keys;
+mod metadata;
+mod migration;
+mod mode;
+mod navigation;
+mod openwith;
+mod preview;
+mod previewserver;
+mod profile;
+mod prompt;
+mod properties;
+mod remote;
+mod rename;
+mod replay;
+mod rules;
+mod schema;
+mod search;
+mod setup;
+mod shared;
+mod sort;
+mod split;
+mod statusbar;
+mod structured;
+mod sysclip;
+#[cfg(test)]
+mod tests;
+mod theme;
+mod trash;
 
 enum ItemType {
     File,
     Directory,
+    /// A directory meant to be opened as a whole, like a macOS `.app` bundle.
+    Package,
+    /// A socket, FIFO or device, which can't be opened.
+    Special(Special),
 }
 
 struct Item {
     name: String,
+    path: PathBuf,
+    /// What the item is, or points at if it's a link.
     item_type: ItemType,
+    /// Whether the item is a symlink (or on Windows, a junction).
+    is_link: bool,
+    /// Only known when the listing is sorted by them, see [`SortBy::needs_metadata`].
+    size: Option<u64>,
+    modified: Option<SystemTime>,
 }
 impl Item {
     fn _is_dir(&self) -> bool {
         matches!(self.item_type, ItemType::Directory)
     }
-    fn is_file(&self) -> bool {
-        matches!(self.item_type, ItemType::File)
+    fn is_file(&self) -> bool {
+        matches!(self.item_type, ItemType::File)
+    }
+}
+
+/// A listing that isn't backed by a real directory, such as search results.
+/// Its items point at real paths, so opening them works like in a normal listing.
+struct VirtualView {
+    title: String,
+    root: PathBuf,
+    hits: Vec<Hit>,
+    previous_selection: usize,
+    previous_scroll: usize,
+}
+
+/// How a listing is shown, as opposed to what it's showing. Kept together so a
+/// pane can have its own, and switching between panes brings their views along.
+#[derive(Clone)]
+struct ViewState {
+    show_hidden: bool,
+    /// Whether sizes, modification times and permissions are shown next to names.
+    details: bool,
+    sort_by: SortBy,
+    sort_descending: bool,
+}
+impl ViewState {
+    fn new(config: &Config) -> Self {
+        ViewState {
+            show_hidden: config.show_hidden,
+            details: config.show_details,
+            sort_by: config.sort_by,
+            sort_descending: config.sort_descending,
+        }
+    }
+}
+
+/// The listing of the inactive pane in dual pane mode. The active pane's lives
+/// in [`Fee`] itself, and the two are swapped when switching panes.
+struct Pane {
+    cwd: PathBuf,
+    selection: usize,
+    scroll: usize,
+    current_contents: Vec<Item>,
+    metadata: HashMap<PathBuf, metadata::Metadata>,
+    requested_metadata: HashSet<PathBuf>,
+    virtual_view: Option<VirtualView>,
+    view: ViewState,
+    navigation: navigation::Navigation,
+}
+
+#[derive(Clone, Copy)]
+struct Rect {
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+}
+
+/// Where the listing goes, and what's beside it.
+struct Layout {
+    list: Rect,
+    /// The other pane in dual pane mode, or else the preview.
+    side: Option<Rect>,
+    /// Whether both panes are shown, which needs a wide enough terminal.
+    dual: bool,
+}
+
+struct Fee {
+    listening: bool,
+    cwd: PathBuf,
+    config: Config,
+    backend: Box<dyn Backend>,
+    events: Box<dyn Events>,
+    /// Index of the selected item, and of the first visible one.
+    selection: usize,
+    scroll: usize,
+    current_contents: Vec<Item>,
+    /// Metadata of the items that have been visible, filled in as it's loaded by
+    /// `metadata_loader`.
+    metadata: HashMap<PathBuf, metadata::Metadata>,
+    /// The visible items whose metadata was last asked for.
+    requested_metadata: HashSet<PathBuf>,
+    metadata_loader: metadata::Loader,
+    mode: Mode,
+    virtual_view: Option<VirtualView>,
+    status_message: Option<String>,
+    /// Every message shown in the status bar, oldest first.
+    message_log: VecDeque<String>,
+    graphics: Option<graphics::Protocol>,
+    wrap_previews: bool,
+    show_preview: bool,
+    view: ViewState,
+    preview_cache: preview::Cache,
+    preview_worker: preview::Worker,
+    /// A preview waiting for the cursor to settle before being generated.
+    pending_preview: Option<(preview::CacheKey, Instant)>,
+    /// A preview being generated by the worker.
+    requested_preview: Option<preview::CacheKey>,
+    /// Item counts and sizes of directories the cursor has rested on, or `None`
+    /// for those that couldn't be added up.
+    directory_stats: HashMap<PathBuf, Option<dirstat::Stat>>,
+    directory_stat_worker: dirstat::Worker,
+    /// A directory waiting for the cursor to settle before being added up.
+    pending_stat: Option<(PathBuf, Instant)>,
+    /// A directory being added up by the worker.
+    requested_stat: Option<PathBuf>,
+    /// Last commits of the entries of directories listed with the git column on.
+    git_commits: HashMap<PathBuf, gitlog::Commits>,
+    git_worker: gitlog::Worker,
+    /// A directory whose last commits are being looked up by the worker.
+    requested_commits: Option<PathBuf>,
+    /// Where the terminal cursor is left after drawing, in accessible mode.
+    focus: (u16, u16),
+    /// Set when the directory changes, to announce it in accessible mode until the
+    /// next keypress.
+    entered_directory: bool,
+    /// The directories visited, to go back and forward through.
+    navigation: navigation::Navigation,
+    search_history: History,
+    filter_history: History,
+    command_history: History,
+    kill_ring: KillRing,
+    /// Receives commands sent with `fee -remote`.
+    remote: Option<remote::Server>,
+    /// When set, opening a file writes its path here and quits instead. `-`
+    /// prints it.
+    choose_file: Option<PathBuf>,
+    /// The file opened while choosing, written once the terminal is given back.
+    chosen: Option<PathBuf>,
+    /// Extensions of the files which can be chosen. Any file can be if empty.
+    choose_extensions: Vec<String>,
+    /// When set, the directory fee was last in is written here on exit.
+    choose_dir: Option<PathBuf>,
+    /// The directory last reported to the directory change hooks.
+    exported_cwd: PathBuf,
+    /// Items marked with space, which batch operations work on.
+    marks: HashSet<PathBuf>,
+    /// Sets of marks saved by name with `:save-marks`.
+    saved_marks: HashMap<String, HashSet<PathBuf>>,
+    /// The bookmarks as last saved or read. Comparing them with the current ones
+    /// tells changes made here apart from those saved by other instances.
+    saved_bookmarks: Vec<bookmarks::Bookmark>,
+    /// The file the bookmarks are saved in: the profile's if it has its own, or
+    /// else the config file. They aren't saved without one.
+    bookmarks_file: Option<PathBuf>,
+    last_openers: openwith::LastOpeners,
+    /// Archives being browsed, innermost last.
+    archives: Vec<archive::Mounted>,
+    /// Temporary files, like decompressed copies, opened in editors fee didn't
+    /// wait for, removed on exit.
+    temp_files: Vec<archive::TempFile>,
+    /// The keys typed so far of a chord, like the `g` of `g/`.
+    chord: Option<String>,
+    /// The inactive pane, in dual pane mode.
+    other_pane: Option<Pane>,
+    /// Whether the active pane is the one on the right, in dual pane mode.
+    right_pane_active: bool,
+    /// The `color_rules` of the config, parsed.
+    color_rules: Vec<rules::Rule>,
+    /// Whether the terminal has focus. Background work is paused while it
+    /// doesn't, with the `pause_when_unfocused` option.
+    focused: bool,
+}
+impl Fee {
+    fn new(
+        cwd: PathBuf,
+        config: Config,
+        backend: Box<dyn Backend>,
+        events: Box<dyn Events>,
+    ) -> Self {
+        let wrap_previews = config.preview_wrap;
+        let show_preview = config.show_preview;
+        let view = ViewState::new(&config);
+        let saved_bookmarks = config.bookmarks.clone();
+        let (search_history, filter_history) = if config.persist_history {
+            (History::load("search"), History::load("filter"))
+        } else {
+            (History::default(), History::default())
+        };
+        let preview_worker = preview::Worker::spawn(config.clone());
+        let (color_rules, rule_error) = match rules::Rule::parse_all(&config.color_rules) {
+            Ok(rules) => (rules, None),
+            Err(error) => (vec![], Some(error)),
+        };
+        let mut fee = Fee {
+            listening: false,
+            cwd,
+            config,
+            backend,
+            events,
+            selection: 0,
+            scroll: 0,
+            current_contents: vec![],
+            metadata: HashMap::new(),
+            metadata_loader: metadata::Loader::spawn(),
+            requested_metadata: HashSet::new(),
+            mode: Mode::Normal,
+            virtual_view: None,
+            status_message: None,
+            message_log: VecDeque::new(),
+            graphics: graphics::detect(),
+            wrap_previews,
+            show_preview,
+            view,
+            preview_cache: preview::Cache::default(),
+            preview_worker,
+            pending_preview: None,
+            requested_preview: None,
+            directory_stats: HashMap::new(),
+            directory_stat_worker: dirstat::Worker::spawn(),
+            pending_stat: None,
+            requested_stat: None,
+            git_commits: HashMap::new(),
+            git_worker: gitlog::Worker::spawn(),
+            requested_commits: None,
+            focus: (0, 0),
+            entered_directory: true,
+            navigation: navigation::Navigation::default(),
+            search_history,
+            filter_history,
+            command_history: History::default(),
+            kill_ring: KillRing::default(),
+            remote: remote::Server::start().ok(),
+            choose_file: None,
+            chosen: None,
+            choose_extensions: vec![],
+            choose_dir: None,
+            exported_cwd: PathBuf::new(),
+            marks: HashSet::new(),
+            saved_marks: HashMap::new(),
+            saved_bookmarks,
+            bookmarks_file: None,
+            last_openers: openwith::LastOpeners::default(),
+            archives: vec![],
+            temp_files: vec![],
+            chord: None,
+            other_pane: None,
+            right_pane_active: false,
+            color_rules,
+            focused: true,
+        };
+        if let Some(error) = rule_error {
+            fee.show_message(error);
+        }
+        fee
+    }
+    fn cleanup_terminal(&mut self) -> io::Result<()> {
+        if let Some(protocol) = self.graphics {
+            graphics::clear(&mut self.backend, protocol)?;
+        }
+        queue!(
+            self.backend,
+            Clear(ClearType::All),
+            cursor::Show,
+            cursor::MoveTo(0, 0),
+            ResetColor
+        )?;
+        if self.config.mouse {
+            queue!(self.backend, DisableMouseCapture)?;
+        }
+        if self.config.pause_when_unfocused {
+            queue!(self.backend, DisableFocusChange)?;
+        }
+        self.backend.flush()?;
+        self.backend.leave()?;
+        Ok(())
+    }
+    fn prepare_terminal(&mut self) -> io::Result<()> {
+        self.backend.flush()?;
+        queue!(
+            self.backend,
+            Clear(ClearType::All),
+            cursor::Hide,
+            cursor::MoveTo(0, 0)
+        )?;
+        if self.config.mouse {
+            queue!(self.backend, EnableMouseCapture)?;
+        }
+        if self.config.pause_when_unfocused {
+            queue!(self.backend, EnableFocusChange)?;
+        }
+        self.backend.flush()?;
+        self.backend.enter()?;
+        self.set_contents(self.get_contents()?);
+        Ok(())
+    }
+    /// Replaces the listed items. Their metadata is loaded once they're visible.
+    fn set_contents(&mut self, contents: Vec<Item>) {
+        self.metadata.clear();
+        self.requested_metadata.clear();
+        self.metadata_loader.request(vec![]);
+        self.current_contents = contents;
+        self.request_commits();
+    }
+    /// Starts loading the metadata of the items at `indices`, the visible ones,
+    /// unless it's already known or on its way. Only ever loading what's
+    /// visible keeps directories with millions of entries quick to scroll.
+    fn request_metadata(&mut self, indices: impl Iterator<Item = usize>) {
+        let missing: Vec<PathBuf> = indices
+            .map(|index| &self.current_contents[index].path)
+            .filter(|path| !self.metadata.contains_key(*path))
+            .cloned()
+            .collect();
+        if missing
+            .iter()
+            .any(|path| !self.requested_metadata.contains(path))
+        {
+            self.requested_metadata = missing.iter().cloned().collect();
+            self.metadata_loader.request(missing);
+        }
+    }
+    /// Starts looking up the last commits of the listed entries, unless they're
+    /// already known.
+    fn request_commits(&mut self) {
+        if !self.config.git_column
+            || self.virtual_view.is_some()
+            || self.git_commits.contains_key(&self.cwd)
+            || self.requested_commits.as_ref() == Some(&self.cwd)
+        {
+            return;
+        }
+        let names = self
+            .current_contents
+            .iter()
+            .map(|item| item.name.clone())
+            .collect();
+        self.git_worker.request(self.cwd.clone(), names);
+        self.requested_commits = Some(self.cwd.clone());
+    }
+    /// Stores looked up commits, redrawing if they're of the listed directory.
+    fn update_commits(&mut self) -> io::Result<()> {
+        let mut received = false;
+        while let Some((directory, commits)) = self.git_worker.try_receive() {
+            if self.requested_commits.as_ref() == Some(&directory) {
+                self.requested_commits = None;
+            }
+            received |= directory == self.cwd;
+            self.git_commits.insert(directory, commits);
+        }
+        if received {
+            self.update()?;
+        }
+        Ok(())
+    }
+    /// Stores loaded metadata, redrawing if any arrived.
+    fn update_metadata(&mut self) -> io::Result<()> {
+        let mut received = false;
+        while let Some(batch) = self.metadata_loader.try_receive() {
+            self.metadata.extend(batch);
+            received = true;
+        }
+        if received {
+            self.update()?;
+        }
+        Ok(())
+    }
+    fn get_contents(&self) -> io::Result<Vec<Item>> {
+        match &self.virtual_view {
+            Some(view) => Ok(view
+                .hits
+                .iter()
+                .map(|hit| {
+                    let relative = hit.path.strip_prefix(&view.root).unwrap_or(&hit.path);
+                    let mut name = relative.to_string_lossy().to_string();
+                    if let (Some(line), Some(text)) = (hit.line, &hit.text) {
+                        name = format!("{}:{}: {}", name, line, text);
+                    }
+                    let special = std::fs::metadata(&hit.path)
+                        .ok()
+                        .and_then(|metadata| Special::of(metadata.file_type()));
+                    let item_type = if self.is_package(&hit.path) {
+                        ItemType::Package
+                    } else if let Some(special) = special {
+                        ItemType::Special(special)
+                    } else if hit.path.is_dir() {
+                        ItemType::Directory
+                    } else {
+                        ItemType::File
+                    };
+                    Item {
+                        name,
+                        path: hit.path.clone(),
+                        item_type,
+                        is_link: hit.path.is_symlink(),
+                        size: None,
+                        modified: None,
+                    }
+                })
+                .collect()),
+            None => self.get_cwd_contents(),
+        }
+    }
+    fn get_cwd_contents(&self) -> io::Result<Vec<Item>> {
+        let mut dirs = vec![];
+        let mut files = vec![];
+        // names listed in a `.hidden` file are hidden along with dotfiles, and on
+        // windows, files with the hidden attribute
+        let ignore = self.ignore(&self.cwd);
+        let hidden_names: Vec<String> = if self.view.show_hidden {
+            vec![]
+        } else {
+            std::fs::read_to_string(self.cwd.join(".hidden"))
+                .unwrap_or_default()
+                .lines()
+                .map(|line| line.trim().to_string())
+                .collect()
+        };
+
+        for item in std::fs::read_dir(&self.cwd)?.flatten() {
+            let mut item_type = item.file_type()?;
+            // only shown, as the path keeps the real name
+            let item_name = item.file_name().to_string_lossy().to_string();
+            let hidden = item_name.starts_with('.')
+                || hidden_names.contains(&item_name)
+                || has_hidden_attribute(&item);
+            if (hidden && !self.view.show_hidden)
+                || ignore
+                    .as_ref()
+                    .is_some_and(|ignore| ignore.is_ignored(&item.path(), item_type.is_dir()))
+            {
+                continue;
+            }
+
+            // junctions count as symlinks too. links are listed as what they point
+            // at, and broken ones as files
+            let is_link = item_type.is_symlink();
+            if is_link {
+                if let Ok(metadata) = std::fs::metadata(item.path()) {
+                    item_type = metadata.file_type();
+                }
+            }
+
+            let (size, modified) = if self.view.sort_by.needs_metadata() {
+                let metadata = std::fs::metadata(item.path()).or_else(|_| item.metadata());
+                metadata.map_or((None, None), |metadata| {
+                    (Some(metadata.len()), metadata.modified().ok())
+                })
+            } else {
+                (None, None)
+            };
+
+            if item_type.is_dir() && self.is_package(&item.path()) {
+                files.push(Item {
+                    name: item_name,
+                    path: item.path(),
+                    item_type: ItemType::Package,
+                    is_link,
+                    size,
+                    modified,
+                })
+            } else if item_type.is_dir() {
+                dirs.push(Item {
+                    name: item_name,
+                    path: item.path(),
+                    item_type: ItemType::Directory,
+                    is_link,
+                    size,
+                    modified,
+                })
+            } else if let Some(special) = Special::of(item_type) {
+                files.push(Item {
+                    name: item_name,
+                    path: item.path(),
+                    item_type: ItemType::Special(special),
+                    is_link,
+                    size,
+                    modified,
+                })
+            } else if item_type.is_file() || is_link {
+                files.push(Item {
+                    name: item_name,
+                    path: item.path(),
+                    item_type: ItemType::File,
+                    is_link,
+                    size,
+                    modified,
+                })
+            }
+        }
+        sort::sort(&mut dirs, self.view.sort_by, self.view.sort_descending);
+        sort::sort(&mut files, self.view.sort_by, self.view.sort_descending);
+        let mut items = dirs;
+        items.append(&mut files);
+        Ok(items)
+    }
+    /// The ignore rules applying in `directory`, if ignored entries are left out
+    /// right now. They're hidden files, so they show up along with dotfiles.
+    fn ignore(&self, directory: &Path) -> Option<Arc<ignore::Ignore>> {
+        self.ignoring().then(|| ignore::Ignore::new(directory))
+    }
+    /// Whether entries matched by ignore files are left out.
+    fn ignoring(&self) -> bool {
+        self.config.respect_ignore_files
+    }
+    fn is_package(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|extension| {
+            self.config
+                .package_extensions
+                .iter()
+                .any(|package| extension.eq_ignore_ascii_case(package))
+        }) && path.is_dir()
+    }
+
+    /// Whether `path` has one of the extensions files are chosen by.
+    fn choosable(&self, path: &Path) -> bool {
+        if self.choose_extensions.is_empty() {
+            return true;
+        }
+        let extension = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        self.choose_extensions.contains(&extension)
+    }
+    fn selected_item(&self) -> Option<&Item> {
+        self.current_contents.get(self.selection)
+    }
+    /// Sends the pending preview to the worker once the cursor has settled, and
+    /// redraws when generated previews arrive.
+    fn update_previews(&mut self) -> io::Result<()> {
+        if let Some((_, since)) = &self.pending_preview {
+            if since.elapsed() >= PREVIEW_DELAY {
+                if let Some((key, _)) = self.pending_preview.take() {
+                    self.preview_worker.request(
+                        key.clone(),
+                        self.graphics.is_some_and(graphics::Protocol::shows_png),
+                    );
+                    self.requested_preview = Some(key);
+                }
+            }
+        }
+        let mut received = false;
+        while let Some((key, preview)) = self.preview_worker.try_receive() {
+            if self.requested_preview.as_ref() == Some(&key) {
+                self.requested_preview = None;
+            }
+            self.preview_cache.insert(key, preview);
+            received = true;
+        }
+        if received {
+            self.update()?;
+        }
+        Ok(())
+    }
+    /// Starts waiting for the cursor to settle on the selected directory, if its
+    /// stats are still unknown.
+    fn schedule_directory_stat(&mut self) {
+        if self.config.directory_stat_delay_ms.is_none() {
+            return;
+        }
+        let path = match self.selected_item() {
+            Some(item) if matches!(item.item_type, ItemType::Directory) => item.path.clone(),
+            _ => {
+                self.pending_stat = None;
+                return;
+            }
+        };
+        let pending = self.pending_stat.as_ref().map(|(path, _)| path);
+        if self.directory_stats.contains_key(&path)
+            || self.requested_stat.as_ref() == Some(&path)
+            || pending == Some(&path)
+        {
+            return;
+        }
+        self.pending_stat = Some((path, Instant::now()));
+    }
+    /// Sends the pending directory to the worker once the cursor has settled, and
+    /// redraws when its stats arrive.
+    fn update_directory_stats(&mut self) -> io::Result<()> {
+        let delay = Duration::from_millis(self.config.directory_stat_delay_ms.unwrap_or(0));
+        if let Some((_, since)) = &self.pending_stat {
+            if since.elapsed() >= delay {
+                if let Some((path, _)) = self.pending_stat.take() {
+                    let ignore = self.ignore(&path);
+                    self.directory_stat_worker.request(path.clone(), ignore);
+                    self.requested_stat = Some(path);
+                }
+            }
+        }
+        let mut received = false;
+        while let Some((path, stat)) = self.directory_stat_worker.try_receive() {
+            if self.requested_stat.as_ref() == Some(&path) {
+                self.requested_stat = None;
+            }
+            self.directory_stats.insert(path, stat);
+            received = true;
+        }
+        if received {
+            self.update()?;
+        }
+        Ok(())
+    }
+    fn quick_look(&mut self) -> io::Result<()> {
+        let Some(item) = self.selected_item() else {
+            return Ok(());
+        };
+        if !item.is_file() {
+            return Ok(());
+        }
+        let title = item.name.to_owned();
+        let preview = preview::generate(&item.path, usize::MAX, &self.config, false)?;
+        self.mode = Mode::QuickLook {
+            title,
+            preview,
+            scroll: 0,
+        };
+        Ok(())
+    }
+    /// Keeps what was at `from`, and anything inside it, marked where it is now,
+    /// at `to`, both in the marks and the saved sets of them.
+    fn follow_move(&mut self, from: &Path, to: &Path) {
+        for marks in std::iter::once(&mut self.marks).chain(self.saved_marks.values_mut()) {
+            let moved: Vec<PathBuf> = marks
+                .iter()
+                .filter(|path| path.starts_with(from))
+                .cloned()
+                .collect();
+            for path in moved {
+                marks.remove(&path);
+                match path.strip_prefix(from) {
+                    Ok(inside) if !inside.as_os_str().is_empty() => marks.insert(to.join(inside)),
+                    _ => marks.insert(to.to_path_buf()),
+                };
+            }
+        }
+    }
+    /// Saves the marks under `name`, to be restored later in the session.
+    fn save_marks(&mut self, name: &str) {
+        if name.is_empty() {
+            self.show_message("save-marks needs a name".to_string());
+        } else if self.marks.is_empty() {
+            self.show_message("nothing is marked".to_string());
+        } else {
+            self.saved_marks
+                .insert(name.to_string(), self.marks.clone());
+            let message = format!("saved {} marks as {}", self.marks.len(), name);
+            self.show_message(message);
+        }
+    }
+    /// Replaces the marks with the ones saved under `name`, leaving out what
+    /// doesn't exist anymore.
+    fn restore_marks(&mut self, name: &str) {
+        let Some(saved) = self.saved_marks.get(name) else {
+            let names: Vec<&str> = self.saved_marks.keys().map(String::as_str).collect();
+            let message = if names.is_empty() {
+                "no marks have been saved".to_string()
+            } else {
+                format!("no marks saved as '{}', only {}", name, names.join(", "))
+            };
+            self.show_message(message);
+            return;
+        };
+        self.marks = saved
+            .iter()
+            .filter(|path| path.symlink_metadata().is_ok())
+            .cloned()
+            .collect();
+        let gone = saved.len() - self.marks.len();
+        let mut message = format!("restored {} marks", self.marks.len());
+        if gone > 0 {
+            message += &format!(", {} no longer exist", gone);
+        }
+        self.show_message(message);
+    }
+    /// Returns what batch operations work on: the marked items, or the selected
+    /// one if none are marked.
+    fn targets(&self) -> Vec<PathBuf> {
+        let mut targets: Vec<PathBuf> = self.marks.iter().cloned().collect();
+        if targets.is_empty() {
+            targets.extend(self.selected_item().map(|item| item.path.clone()));
+        }
+        targets.sort();
+        targets
+    }
+    /// Starts renaming the selected item, with the cursor before its extension.
+    fn start_rename(&mut self) {
+        let Some(item) = self.selected_item() else {
+            return;
+        };
+        let mut prompt = Prompt::default();
+        prompt.set_text(&item.name);
+        if item.is_file() {
+            if let Some(dot) = item.name.rfind('.').filter(|dot| *dot > 0) {
+                prompt.set_cursor(item.name[..dot].chars().count());
+            }
+        }
+        self.mode = Mode::Rename(item.path.clone(), prompt);
+    }
+    /// Moves the selection to `path` if it's listed, scrolling as little as possible.
+    fn select_path(&mut self, path: &Path) -> io::Result<()> {
+        match self
+            .current_contents
+            .iter()
+            .position(|item| item.path == path)
+        {
+            Some(index) => self.select_index(index),
+            None => Ok(()),
+        }
+    }
+    /// Moves the selection to the `index`th entry, scrolling as little as possible.
+    fn select_index(&mut self, index: usize) -> io::Result<()> {
+        let height = self.list_height()? as usize;
+        self.selection = index;
+        if self.selection < self.scroll {
+            self.scroll = self.selection;
+        } else if self.selection >= self.scroll + height {
+            self.scroll = self.selection + 1 - height;
+        }
+        Ok(())
+    }
+    /// Creates an empty file, or a directory, called `name` in the current
+    /// directory and selects it. An existing file is only touched, unless the
+    /// file is to be filled with `contents`.
+    fn create(&mut self, name: &str, directory: bool, contents: Option<&[u8]>) -> io::Result<()> {
+        let name = name.trim();
+        // only ever somewhere inside the current directory
+        let outside = Path::new(name)
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+        if name.is_empty() || outside {
+            self.show_message(format!("invalid name: '{}'", name));
+            return Ok(());
+        }
+        let path = self.cwd.join(name);
+        if (directory || contents.is_some()) && path.symlink_metadata().is_ok() {
+            self.show_message(format!("'{}' already exists", name));
+            return Ok(());
+        }
+        // nested directories are made in the innermost one there already is
+        let parent = path.parent().unwrap_or(&self.cwd);
+        let existing = parent
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(parent);
+        let program: Option<&[&str]> = match (directory, contents) {
+            (true, _) => Some(&["mkdir", "-p"]),
+            (false, None) if existing == parent => Some(&["touch"]),
+            // there's no simple command filling a file with the clipboard
+            (false, _) => None,
+        };
+        let elevated = program.map(|program| {
+            let action = format!("create '{}'", name);
+            Elevated::new(action, program, [path.as_path()])
+        });
+        if !self.permitted([fileops::check_writable(existing)], elevated) {
+            return Ok(());
+        }
+        let verb = match (directory, contents) {
+            (true, _) => "mkdir",
+            (false, Some(_)) => "paste clipboard into",
+            (false, None) => "touch",
+        };
+        if self.config.dry_run {
+            self.rehearse(vec![format!("{} {}", verb, path.display())]);
+            return Ok(());
+        }
+        if directory {
+            std::fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            match contents {
+                Some(contents) => std::fs::File::create_new(&path)?.write_all(contents)?,
+                None => std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)?
+                    .set_modified(SystemTime::now())?,
+            }
+        }
+        self.audit(&[format!("{} {}", verb, path.display())]);
+        self.virtual_view = None;
+        self.reload()?;
+        // select what was created in this directory, even if the new item is nested
+        let first = name
+            .split('/')
+            .find(|part| !part.is_empty())
+            .unwrap_or(name);
+        self.select_path(&self.cwd.join(first))
+    }
+    /// Splits the selected file into parts of the size in `argument`, or of the
+    /// biggest size FAT32 allows.
+    fn split_selected(&mut self, argument: &str) -> io::Result<()> {
+        let Some(path) = self.selected_item().map(|item| item.path.clone()) else {
+            return Ok(());
+        };
+        if !path.is_file() {
+            self.show_message("only files can be split".to_string());
+            return Ok(());
+        }
+        let part_size = if argument.trim().is_empty() {
+            split::FAT32_PART_SIZE
+        } else if let Some(size) = split::parse_size(argument) {
+            size
+        } else {
+            self.show_message(format!("invalid part size: '{}'", argument));
+            return Ok(());
+        };
+        let (parts, _) = split::part_paths(&path, part_size)?;
+        let action = format!(
+            "split {} into {} parts of {}",
+            path.display(),
+            parts.len(),
+            human_size(part_size)
+        );
+        if self.config.dry_run {
+            self.rehearse(vec![action]);
+            return Ok(());
+        }
+        let written = split::split(&path, part_size)?;
+        self.audit(&[action]);
+        self.show_message(format!("split into {} parts", parts.len()));
+        self.reload()?;
+        self.select_path(&written[0])
+    }
+    /// Joins the parts the selected file belongs to back together.
+    fn join_selected(&mut self) -> io::Result<()> {
+        let Some(path) = self.selected_item().map(|item| item.path.clone()) else {
+            return Ok(());
+        };
+        let Some(output) = split::joined_path(&path) else {
+            self.show_message("select a part (.001, .002, ...) or .sfv file to join".to_string());
+            return Ok(());
+        };
+        let action = format!("join parts of {}", output.display());
+        if self.config.dry_run {
+            self.rehearse(vec![action]);
+            return Ok(());
+        }
+        let (count, checked) = split::join(&output)?;
+        self.audit(&[action]);
+        if checked {
+            self.show_message(format!("joined {} parts, checksums match", count));
+        } else {
+            self.show_message(format!(
+                "joined {} parts, without checksums to check",
+                count
+            ));
+        }
+        self.reload()?;
+        self.select_path(&output)
+    }
+    fn open_batch_rename(&mut self) {
+        let targets = self.targets();
+        if targets.is_empty() {
+            return;
+        }
+        self.mode = Mode::BatchRename(rename::BatchRename::new(targets));
+    }
+    /// Copies or moves the targets into `directory`.
+    fn transfer(&mut self, directory: &str, moving: bool) -> io::Result<()> {
+        let directory = command::resolve_path(directory, &self.cwd);
+        let Ok(directory) = directory.canonicalize() else {
+            self.show_message(format!("no such directory: {}", directory.display()));
+            return Ok(());
+        };
+        if !directory.is_dir() {
+            self.show_message(format!("not a directory: {}", directory.display()));
+            return Ok(());
+        }
+        let (verb, past) = if moving {
+            ("move", "moved")
+        } else {
+            ("copy", "copied")
+        };
+        let targets = self.targets();
+        if !self.transfer_permitted(&targets, &directory, moving) {
+            return Ok(());
+        }
+        if self.config.dry_run {
+            let actions = targets
+                .iter()
+                .map(|from| {
+                    let to = fileops::destination(from, &directory);
+                    format!("{} {} -> {}", verb, from.display(), to.display())
+                })
+                .collect();
+            self.rehearse(actions);
+            return Ok(());
+        }
+        let progress = if moving { "moving" } else { "copying" };
+        let mut done = vec![];
+        let mut errors = vec![];
+        for from in &targets {
+            let to = fileops::destination(from, &directory);
+            self.show_progress(format!("{} {}", progress, from.display()))?;
+            let result = if moving {
+                fileops::move_to(from, &to)
+            } else {
+                fileops::copy(from, &to)
+            };
+            match result {
+                Ok(()) => {
+                    done.push(format!("{} {} -> {}", verb, from.display(), to.display()));
+                    if moving {
+                        self.follow_move(from, &to);
+                    }
+                }
+                Err(error) => {
+                    errors.push(format!("couldn't {} {}: {}", verb, from.display(), error))
+                }
+            }
+        }
+        self.audit(&done);
+        self.finish_batch(done.len(), past, errors)
+    }
+    /// Puts the targets on the clipboard, to be copied or moved when pasted.
+    fn yank(&mut self, cut: bool) {
+        let paths = self.targets();
+        if paths.is_empty() {
+            return;
+        }
+        let count = paths.len();
+        if let Err(error) = (clipboard::Clipboard { paths, cut }).save() {
+            self.show_message(format!("couldn't save the clipboard: {}", error));
+            return;
+        }
+        let verb = if cut { "cut" } else { "yanked" };
+        self.show_message(format!("{} {} items", verb, count));
+    }
+    /// Pastes the clipboard, which may have been filled by another instance,
+    /// into the current directory.
+    fn paste(&mut self) -> io::Result<()> {
+        let Some(clipboard) = clipboard::Clipboard::load() else {
+            self.show_message("nothing to paste".to_string());
+            return Ok(());
+        };
+        if self.virtual_view.is_some() {
+            self.show_message("can't paste into search results".to_string());
+            return Ok(());
+        }
+        let directory = self.cwd.clone();
+        if !self.transfer_permitted(&clipboard.paths, &directory, clipboard.cut) {
+            return Ok(());
+        }
+        if self.config.dry_run {
+            let verb = if clipboard.cut { "move" } else { "copy" };
+            let actions = clipboard
+                .paths
+                .iter()
+                .map(|from| {
+                    let to = fileops::destination(from, &self.cwd);
+                    let exists = if to.symlink_metadata().is_ok() {
+                        " (already exists)"
+                    } else {
+                        ""
+                    };
+                    format!("{} {} -> {}{}", verb, from.display(), to.display(), exists)
+                })
+                .collect();
+            self.rehearse(actions);
+            return Ok(());
+        }
+        // there's no trash to move overwritten files inside archives to
+        let in_archive = self
+            .archives
+            .iter()
+            .any(|mounted| self.cwd.starts_with(&mounted.root));
+        let use_trash = self.config.use_trash && !in_archive;
+        let paste = clipboard::Paste::new(clipboard, self.cwd.clone(), use_trash);
+        self.continue_paste(paste)
+    }
+    /// Pastes until done, or until a conflict needs resolving.
+    fn continue_paste(&mut self, mut paste: clipboard::Paste) -> io::Result<()> {
+        let verb = paste.clipboard.progress_verb();
+        let finished =
+            paste.run(|from| self.show_progress(format!("{} {}", verb, from.display())))?;
+        if !finished {
+            self.mode = Mode::Paste(paste);
+            return Ok(());
+        }
+        self.mode = Mode::Normal;
+        let verb = if paste.clipboard.cut { "move" } else { "copy" };
+        let actions: Vec<String> = paste
+            .pasted
+            .iter()
+            .map(|(from, to)| format!("{} {} -> {}", verb, from.display(), to.display()))
+            .collect();
+        self.audit(&actions);
+        if paste.clipboard.cut {
+            // what was cut isn't there anymore
+            if let Err(error) = paste.clipboard.clear() {
+                self.show_message(format!("couldn't clear the clipboard: {}", error));
+            }
+            for (from, to) in &paste.pasted {
+                self.follow_move(from, to);
+            }
+        }
+        self.finish_batch(paste.pasted.len(), "pasted", paste.errors)
+    }
+    /// Copies the targets to the system clipboard as `file://` URIs, a line each.
+    fn copy_uris(&mut self) -> io::Result<()> {
+        let targets = self.targets();
+        if targets.is_empty() {
+            return Ok(());
+        }
+        let uris: Vec<String> = targets.iter().map(|path| sysclip::file_uri(path)).collect();
+        sysclip::copy(&uris.join("\n"), &mut self.backend)?;
+        self.show_message(match &uris[..] {
+            [uri] => format!("copied {}", uri),
+            uris => format!("copied {} URIs", uris.len()),
+        });
+        Ok(())
+    }
+    /// Checks that each of `checks` passes before any files are changed. If one
+    /// doesn't, says why, offering to do it with sudo instead if there's an
+    /// `elevated` command for it and it's only a matter of permissions.
+    fn permitted(
+        &mut self,
+        checks: impl IntoIterator<Item = io::Result<()>>,
+        elevated: Option<Elevated>,
+    ) -> bool {
+        let Some(error) = checks.into_iter().find_map(Result::err) else {
+            return true;
+        };
+        match elevated {
+            Some(elevated) if cfg!(unix) && error.kind() == io::ErrorKind::PermissionDenied => {
+                let reason = error.to_string();
+                self.mode = Mode::Confirm(Confirmation::Elevate { reason, elevated });
+            }
+            _ => self.show_message(error.to_string()),
+        }
+        false
+    }
+    /// Runs `command` with sudo, in the terminal so it can ask for a password.
+    fn run_elevated(&mut self, command: Vec<OsString>) -> io::Result<()> {
+        let parts: Vec<String> = command
+            .iter()
+            .map(|part| part.to_string_lossy().to_string())
+            .collect();
+        let action = format!("sudo {}", parts.join(" "));
+        if self.config.dry_run {
+            self.rehearse(vec![action]);
+            return Ok(());
+        }
+        self.audit(&[action]);
+        let mut sudo = Command::new("sudo");
+        sudo.args(command);
+        self.run_in_terminal(sudo, false)
+    }
+    /// Checks that `paths` can be copied, or moved, into `directory`.
+    fn transfer_permitted(&mut self, paths: &[PathBuf], directory: &Path, moving: bool) -> bool {
+        let sources = paths
+            .iter()
+            .filter(|_| moving)
+            .filter_map(|path| path.parent());
+        let checks = std::iter::once(directory)
+            .chain(sources)
+            .map(fileops::check_writable);
+        let (verb, program): (&str, &[&str]) = if moving {
+            ("move", &["mv", "-n"])
+        } else {
+            ("copy", &["cp", "-R", "-n"])
+        };
+        let action = format!(
+            "{} {} into '{}'",
+            verb,
+            describe_items(paths),
+            directory.display()
+        );
+        let targets = paths.iter().map(PathBuf::as_path);
+        let elevated = Elevated::new(action, program, targets.chain([directory]));
+        self.permitted(checks, Some(elevated))
+    }
+    /// Asks whether to delete the targets, moving them to the trash unless
+    /// `permanently` is set, or `use_trash` is off.
+    fn confirm_delete(&mut self, permanently: bool) {
+        let paths = self.targets();
+        if paths.is_empty() {
+            return;
+        }
+        // there's no trash to move files inside archives to
+        let in_archive = self
+            .archives
+            .iter()
+            .any(|mounted| paths.iter().any(|path| path.starts_with(&mounted.root)));
+        let permanently = permanently || !self.config.use_trash || in_archive;
+        self.mode = Mode::Confirm(Confirmation::Delete { paths, permanently });
+    }
+    fn enter_archive(&mut self, path: &Path) -> io::Result<()> {
+        let mounted = archive::Mounted::mount(path)?;
+        self.cwd = mounted.root.clone();
+        self.archives.push(mounted);
+        self.virtual_view = None;
+        self.selection = 0;
+        self.scroll = 0;
+        self.set_contents(self.get_cwd_contents()?);
+        Ok(())
+    }
+    /// Leaves the innermost archive, asking whether to save any changes first,
+    /// for `destination`, or for the archive itself.
+    fn ask_to_leave_archive(&mut self, destination: Option<PathBuf>) -> io::Result<()> {
+        let Some(mounted) = self.archives.last() else {
+            return Ok(());
+        };
+        match mounted.changes() {
+            Some(changes) => {
+                let archive = mounted.archive.clone();
+                self.mode = Mode::Confirm(Confirmation::Repack(archive, changes, destination));
+                Ok(())
+            }
+            None => self.leave_archive(false, destination),
+        }
+    }
+    /// Whether `directory` is outside the innermost archive.
+    fn outside_archive(&self, directory: &Path) -> bool {
+        self.archives.last().is_some_and(|mounted| {
+            let root = mounted.root.canonicalize().unwrap_or(mounted.root.clone());
+            let directory = directory.canonicalize().unwrap_or(directory.to_path_buf());
+            !directory.starts_with(root)
+        })
+    }
+    /// Leaves the innermost archive, packing up the changes made in it if `save`,
+    /// for `destination`, or for the archive itself.
+    fn leave_archive(&mut self, save: bool, destination: Option<PathBuf>) -> io::Result<()> {
+        let Some(mounted) = self.archives.last() else {
+            return Ok(());
+        };
+        if save && !self.config.dry_run {
+            mounted.repack()?;
+            let archive = mounted.archive.display().to_string();
+            self.audit(&[format!("repack {}", archive)]);
+            self.show_message(format!("saved {}", archive));
+        } else if save {
+            let action = format!("repack {}", mounted.archive.display());
+            self.rehearse(vec![action]);
+        }
+        let Some(mounted) = self.archives.pop() else {
+            return Ok(());
+        };
+        match destination {
+            Some(destination) if destination.is_file() => self.jump_to(&destination),
+            Some(destination) => self.change_directory(&destination.to_string_lossy()),
+            None => self.jump_to(&mounted.archive),
+        }
+    }
+    /// Returns `path` as it's shown, with the directories that archives are
+    /// extracted to replaced by the archives.
+    fn display_path(&self, path: &Path) -> PathBuf {
+        for mounted in self.archives.iter().rev() {
+            if let Ok(inside) = path.strip_prefix(&mounted.root) {
+                let archive = self.display_path(&mounted.archive);
+                if inside.as_os_str().is_empty() {
+                    return archive;
+                }
+                return archive.join(inside);
+            }
+        }
+        path.to_path_buf()
+    }
+    /// Moves `targets` to the trash, or deletes them `permanently`.
+    fn delete(&mut self, targets: Vec<PathBuf>, permanently: bool) -> io::Result<()> {
+        let (verb, past) = if permanently {
+            ("delete", "deleted")
+        } else {
+            ("trash", "trashed")
+        };
+        let permitted = if permanently {
+            let action = format!("delete {}", describe_deletion(&targets));
+            let paths = targets.iter().map(PathBuf::as_path);
+            let elevated = Elevated::new(action, &["rm", "-rf"], paths);
+            let checks = targets.iter().map(|path| fileops::check_removable(path));
+            self.permitted(checks, Some(elevated))
+        } else {
+            // there's no trashing with sudo
+            let checks = targets
+                .iter()
+                .filter_map(|path| path.parent())
+                .map(fileops::check_writable);
+            self.permitted(checks, None)
+        };
+        if !permitted {
+            return Ok(());
+        }
+        if self.config.dry_run {
+            let actions = targets
+                .iter()
+                .map(|path| format!("{} {}", verb, path.display()))
+                .collect();
+            self.rehearse(actions);
+            return Ok(());
+        }
+        let mut done = vec![];
+        let mut errors = vec![];
+        for path in &targets {
+            let result = if permanently {
+                fileops::delete(path)
+            } else {
+                trash::trash(path)
+            };
+            match result {
+                Ok(()) => {
+                    done.push(format!("{} {}", verb, path.display()));
+                    self.marks.retain(|marked| !marked.starts_with(path));
+                }
+                Err(error) => {
+                    errors.push(format!("couldn't {} {}: {}", verb, path.display(), error))
+                }
+            }
+        }
+        self.audit(&done);
+        self.finish_batch(done.len(), past, errors)
+    }
+    /// Reports how a batch operation went, and shows its results.
+    fn finish_batch(&mut self, done: usize, verb: &str, errors: Vec<String>) -> io::Result<()> {
+        let failed = errors.len();
+        for error in errors {
+            self.show_message(error);
+        }
+        if failed > 0 {
+            self.show_message(format!(
+                "{} {} items, {} failed, see :messages",
+                verb, done, failed
+            ));
+        } else {
+            self.show_message(format!("{} {} items", verb, done));
+        }
+        self.reload()
+    }
+    /// Lists the current directory (or view) again, keeping the selection in range.
+    /// In dual pane mode, the other pane is listed again too, as what changed may
+    /// be listed there as well.
+    fn reload(&mut self) -> io::Result<()> {
+        self.directory_stats.clear();
+        // whatever was removed behind our back can't be worked on
+        self.marks.retain(|path| path.symlink_metadata().is_ok());
+        self.reload_listing()?;
+        if self.other_pane.is_some() {
+            self.swap_panes();
+            let result = self.reload_listing();
+            self.swap_panes();
+            result?;
+        }
+        Ok(())
+    }
+    fn reload_listing(&mut self) -> io::Result<()> {
+        self.git_commits.remove(&self.cwd);
+        self.set_contents(self.get_contents()?);
+        let last = self.current_contents.len().saturating_sub(1);
+        self.selection = cmp::min(self.selection, last);
+        Ok(())
+    }
+    /// Marks or unmarks the selected item, and moves on to the next one.
+    fn toggle_mark(&mut self) -> io::Result<()> {
+        let Some(path) = self.selected_item().map(|item| item.path.clone()) else {
+            return Ok(());
+        };
+        if !self.marks.remove(&path) {
+            self.marks.insert(path);
+        }
+        self.move_down()
+    }
+    fn show_bookmarks(&mut self) -> io::Result<()> {
+        self.sync_bookmarks()?;
+        self.mode = Mode::Bookmarks(bookmarks::Picker::new(&self.config.bookmarks));
+        Ok(())
+    }
+    /// Bookmarks the current directory under its name, with the first free digit
+    /// as its key.
+    fn bookmark_directory(&mut self) -> io::Result<()> {
+        // another instance may have taken the first free digit
+        self.sync_bookmarks()?;
+        let bookmarks = &mut self.config.bookmarks;
+        if let Some(bookmark) = bookmarks
+            .iter()
+            .find(|bookmark| command::resolve_path(&bookmark.path, &self.cwd) == self.cwd)
+        {
+            let message = format!("already bookmarked as '{}'", bookmark.name);
+            self.show_message(message);
+            return Ok(());
+        }
+        let name = self.cwd.file_name().unwrap_or(self.cwd.as_os_str());
+        let bookmark = bookmarks::Bookmark {
+            name: name.to_string_lossy().to_string(),
+            path: self.cwd.to_string_lossy().to_string(),
+            key: bookmarks::free_digit(bookmarks),
+            group: None,
+        };
+        let message = match bookmark.key {
+            Some(key) => format!("bookmarked '{}', jump to it with '{}", bookmark.name, key),
+            None => format!("bookmarked '{}'", bookmark.name),
+        };
+        bookmarks.push(bookmark);
+        self.show_message(message);
+        self.sync_bookmarks()
+    }
+    /// Enters the directory at `path`, or the one containing the file at `path`
+    /// with it selected.
+    fn go_to(&mut self, path: &str) -> io::Result<()> {
+        let resolved = fileops::resolve_directories(&command::resolve_path(path, &self.cwd));
+        if resolved.is_file() {
+            self.jump_to(&resolved)
+        } else {
+            self.change_directory(path)
+        }
+    }
+    /// Goes to the bookmark named `name`.
+    fn jump_to_bookmark(&mut self, name: &str) -> io::Result<()> {
+        let bookmark = self
+            .config
+            .bookmarks
+            .iter()
+            .find(|bookmark| bookmark.name == name);
+        match bookmark {
+            Some(bookmark) => {
+                let path = bookmark.path.clone();
+                self.go_to(&path)
+            }
+            None => {
+                self.show_message(format!("no bookmark named '{}'", name));
+                Ok(())
+            }
+        }
+    }
+    /// Saves the changes made to the bookmarks, and picks up those saved by other
+    /// instances.
+    fn sync_bookmarks(&mut self) -> io::Result<()> {
+        let Some(file) = &self.bookmarks_file else {
+            self.saved_bookmarks = self.config.bookmarks.clone();
+            return Ok(());
+        };
+        let merged = match bookmarks::sync(file, &self.saved_bookmarks, &self.config.bookmarks) {
+            Ok(merged) => merged,
+            Err(error) => {
+                self.show_message(format!("couldn't save bookmarks: {}", error));
+                return Ok(());
+            }
+        };
+        if let Mode::Bookmarks(picker) = &mut self.mode {
+            let selected = picker
+                .selected()
+                .and_then(|index| self.config.bookmarks.get(index));
+            let index = selected
+                .and_then(|selected| merged.iter().position(|bookmark| bookmark == selected))
+                .unwrap_or(0);
+            picker.refresh(&merged, index);
+        }
+        self.saved_bookmarks = merged.clone();
+        self.config.bookmarks = merged;
+        Ok(())
+    }
+    fn show_finder(&mut self) {
+        let ignore = self.ignore(&self.cwd);
+        let finder = finder::Finder::new(self.cwd.clone(), self.view.show_hidden, ignore);
+        self.mode = Mode::Finder(finder);
+    }
+    /// Lists the files the finder has found since last time.
+    fn update_finder(&mut self) -> io::Result<()> {
+        if let Mode::Finder(finder) = &mut self.mode {
+            if finder.receive() {
+                self.update()?;
+            }
+        }
+        Ok(())
+    }
+    fn show_drives(&mut self) -> io::Result<()> {
+        self.mode = Mode::Drives {
+            drives: drives::list()?,
+            selection: 0,
+        };
+        Ok(())
+    }
+    fn eject_drive(&mut self, drive: &drives::Drive) -> io::Result<()> {
+        // the drive can't be unmounted while we're inside it
+        if let Some(mount_point) = &drive.mount_point {
+            if self.cwd.starts_with(mount_point) {
+                let outside = mount_point.parent().unwrap_or(Path::new("/"));
+                self.change_directory(&outside.to_string_lossy())?;
+            }
+        }
+        // syncing can take a while, so say what's going on
+        self.status_message = Some(format!("syncing and ejecting {}...", drive.device));
+        self.update()?;
+        drives::eject(drive)?;
+        self.show_message(format!("{} can be safely removed", drive.device));
+        self.refresh_drives()
+    }
+    fn refresh_drives(&mut self) -> io::Result<()> {
+        let drives = drives::list()?;
+        if let Mode::Drives { selection, .. } = &self.mode {
+            let selection = cmp::min(*selection, drives.len().saturating_sub(1));
+            self.mode = Mode::Drives { drives, selection };
+        }
+        Ok(())
+    }
+    /// Shows the details of the selected item in the quick look pager.
+    fn show_properties(&mut self) -> io::Result<()> {
+        if !self.marks.is_empty() {
+            let mut paths: Vec<PathBuf> = self.marks.iter().cloned().collect();
+            paths.sort();
+            self.mode = Mode::Attributes(attributes::Batch::new(paths));
+            return Ok(());
+        }
+        let Some(item) = self.selected_item() else {
+            return Ok(());
+        };
+        let title = format!("properties of {}", item.name);
+        let preview = Preview::text(properties::describe(&item.path)?);
+        self.mode = Mode::QuickLook {
+            title,
+            preview,
+            scroll: 0,
+        };
+        Ok(())
+    }
+    /// Sets the permissions of each path to its mode.
+    fn change_modes(&mut self, changes: Vec<(PathBuf, u32)>) -> io::Result<()> {
+        if self.config.dry_run {
+            let actions = changes
+                .iter()
+                .map(|(path, mode)| format!("chmod {:o} {}", mode, path.display()))
+                .collect();
+            self.rehearse(actions);
+            return Ok(());
+        }
+        let mut done = vec![];
+        let mut errors = vec![];
+        for (path, mode) in &changes {
+            match attributes::set_mode(path, *mode) {
+                Ok(()) => done.push(format!("chmod {:o} {}", mode, path.display())),
+                Err(error) => errors.push(format!(
+                    "couldn't change permissions of {}: {}",
+                    path.display(),
+                    error
+                )),
+            }
+        }
+        self.audit(&done);
+        self.finish_batch(done.len(), "changed permissions of", errors)
+    }
+    /// Shows the message log in the quick look pager, scrolled to the newest message.
+    fn show_message_log(&mut self) -> io::Result<()> {
+        let lines: Vec<String> = if self.message_log.is_empty() {
+            vec!["no messages".to_string()]
+        } else {
+            self.message_log.iter().cloned().collect()
+        };
+        let preview = Preview::text(lines);
+        let width = self.backend.size()?.0 as usize;
+        let line_count = preview.layout(width, self.wrap_previews).len();
+        let scroll = line_count.saturating_sub(self.terminal_height()? as usize);
+        self.mode = Mode::QuickLook {
+            title: "messages".to_string(),
+            preview,
+            scroll,
+        };
+        Ok(())
+    }
+    /// Shows `message` on the bottom line until the next keypress, and logs it.
+    fn show_message(&mut self, message: String) {
+        if self.message_log.len() >= MESSAGE_LOG_CAPACITY {
+            self.message_log.pop_front();
+        }
+        self.message_log.push_back(message.clone());
+        self.status_message = Some(message);
+    }
+    /// Shows `message` on the bottom line straight away, while something takes
+    /// a while, without logging it.
+    fn show_progress(&mut self, message: String) -> io::Result<()> {
+        self.status_message = Some(message);
+        self.update()
+    }
+    /// Logs what a file operation would do instead of doing it, in dry run mode.
+    fn rehearse(&mut self, actions: Vec<String>) {
+        let count = actions.len();
+        for action in actions {
+            self.show_message(format!("dry run: {}", action));
+        }
+        self.show_message(format!(
+            "dry run: would make {} changes, see :messages",
+            count
+        ));
+    }
+    /// Appends `actions` to the audit log, if it's turned on.
+    fn audit(&mut self, actions: &[String]) {
+        if !self.config.audit_log || actions.is_empty() {
+            return;
+        }
+        if let Err(error) = audit::record(actions) {
+            self.show_message(format!("couldn't write to the audit log: {}", error));
+        }
+    }
+    fn select(&mut self) -> io::Result<()> {
+        for (index, item) in self.current_contents.iter().enumerate() {
+            if index == self.selection {
+                match item.item_type {
+                    ItemType::Directory => {
+                        self.cwd = item.path.clone();
+                        self.virtual_view = None;
+                        self.selection = 0;
+                        self.scroll = 0;
+                        self.set_contents(self.get_cwd_contents()?);
+                    }
+                    // opening these would hang or make no sense
+                    ItemType::Special(_) => {
+                        self.show_properties()?;
+                    }
+                    ItemType::Package => {
+                        let parts = command::expand_parts(
+                            &self.config.package_open_command,
+                            Some(&item.path),
+                            &[],
+                            &self.cwd,
+                        );
+                        self.run_editor(parts)?;
+                    }
+                    ItemType::File => {
+                        if self.virtual_view.is_some() && self.config.search_enter_jumps {
+                            let path = item.path.clone();
+                            self.jump_to(&path)?;
+                            break;
+                        }
+                        let filepath = item.path.clone();
+                        if self.choose_file.is_some() && !self.choosable(&filepath) {
+                            let extensions = self.choose_extensions.join(", ");
+                            self.show_message(format!("only {} files can be chosen", extensions));
+                            break;
+                        }
+                        if self.choose_file.is_some() {
+                            self.chosen = Some(filepath);
+                            self.listening = false;
+                            break;
+                        }
+                        if archive::is_archive(&filepath) {
+                            self.enter_archive(&filepath)?;
+                            break;
+                        }
+                        if archive::is_compressed(&filepath)
+                            && openwith::associated(&self.config.associations, &filepath).is_empty()
+                        {
+                            self.open_decompressed(&filepath)?;
+                            break;
+                        }
+                        if self.config.open_binary_with_system
+                            && openwith::associated(&self.config.associations, &filepath).is_empty()
+                            && !is_valid_utf8(&filepath)?
+                        {
+                            self.open_with_system(&filepath);
+                            break;
+                        }
+
+                        let command = self.editor_command(&filepath)?;
+                        let parts = command::expand_parts(command, Some(&filepath), &[], &self.cwd);
+                        self.run_editor(parts)?;
+                    }
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+    /// Picks the command to open `path` with: the most specific one associated
+    /// with it, or else the binary editor unless it's UTF-8, and the large text
+    /// editor if it's a text file above `large_text_above` bytes.
+    fn editor_command(&self, path: &PathBuf) -> io::Result<&Vec<String>> {
+        let config = &self.config;
+        if let Some(command) = openwith::associated(&config.associations, path).first() {
+            return Ok(command);
+        }
+        let large = !config.large_text_editor_command.is_empty()
+            && path.metadata()?.len() > config.large_text_above;
+        // no need to look through the file if it'll be opened the same way anyway
+        if config.text_editor_command == config.binary_editor_command && !large {
+            return Ok(&config.text_editor_command);
+        }
+        if !is_valid_utf8(path)? {
+            Ok(&config.binary_editor_command)
+        } else if large {
+            Ok(&config.large_text_editor_command)
+        } else {
+            Ok(&config.text_editor_command)
+        }
+    }
+    /// Opens `path` with the program the system opens its kind of file with, like
+    /// a PDF viewer, in the background.
+    fn open_with_system(&mut self, path: &Path) {
+        let mut parts = command::expand_parts(&system_open_command(), Some(path), &[], &self.cwd);
+        let Some(executable) = parts.pop_front() else {
+            return;
+        };
+        let spawned = Command::new(executable)
+            .args(parts)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(mut child) => {
+                std::thread::spawn(move || child.wait());
+            }
+            Err(error) => self.show_message(format!("couldn't open: {}", error)),
+        }
+    }
+    fn show_context_menu(&mut self) {
+        let Some(item) = self.selected_item() else {
+            return;
+        };
+        let is_dir = matches!(item.item_type, ItemType::Directory);
+        self.mode = Mode::Context {
+            actions: keymap::item_actions(is_dir),
+            selection: 0,
+        };
+    }
+    fn show_open_with(&mut self) {
+        let Some(path) = self.selected_item().map(|item| item.path.clone()) else {
+            return;
+        };
+        let mut commands = openwith::associated(&self.config.associations, &path);
+        commands.extend([
+            &self.config.text_editor_command,
+            &self.config.large_text_editor_command,
+            &self.config.binary_editor_command,
+        ]);
+        if self.is_package(&path) {
+            commands.insert(0, &self.config.package_open_command);
+        }
+        let menu = openwith::Menu::new(path, &commands, &self.last_openers);
+        self.mode = Mode::OpenWith(menu);
+    }
+    /// Runs `command` in the shell, in the terminal fee gives up meanwhile. `$f`
+    /// expands to the selected item, `$fs` to the marked ones (or else the
+    /// selected one) and `$d` to the current directory. Its output stays up
+    /// until a key is pressed.
+    fn run_shell(&mut self, command: &str) -> io::Result<()> {
+        if command.trim().is_empty() {
+            return Ok(());
+        }
+        let selected = self.selected_item().map(|item| item.path.clone());
+        let command =
+            command::expand_shell(command, selected.as_deref(), &self.targets(), &self.cwd);
+        if self.config.dry_run {
+            self.rehearse(vec![format!("run {}", command)]);
+            return Ok(());
+        }
+        self.audit(&[format!("run {}", command)]);
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let mut shell = Command::new(shell);
+        shell.args([flag, &command]);
+        self.run_in_terminal(shell, true)
+    }
+    /// Opens the configured shell, or else `$SHELL`, in the current directory,
+    /// coming back once it exits.
+    fn open_subshell(&mut self) -> io::Result<()> {
+        let mut parts = self.config.shell.iter().cloned();
+        let executable = parts.next().unwrap_or_else(default_shell);
+        let mut shell = Command::new(executable);
+        shell.args(parts);
+        self.run_in_terminal(shell, false)
+    }
+    /// Runs the `custom_commands` entry `custom` on the selected item, or the
+    /// marked ones for `$fs`.
+    fn run_custom_command(&mut self, custom: &keymap::CustomCommand) -> io::Result<()> {
+        let selected = self.selected_item().map(|item| item.path.clone());
+        let mut parts = command::expand_parts(
+            &custom.command,
+            selected.as_deref(),
+            &self.targets(),
+            &self.cwd,
+        );
+        let Some(executable) = parts.pop_front() else {
+            return Ok(());
+        };
+        let line = format!("run {}", custom.command.join(" "));
+        if self.config.dry_run {
+            self.rehearse(vec![line]);
+            return Ok(());
+        }
+        self.audit(&[line]);
+        let mut command = Command::new(executable);
+        command.args(parts);
+        self.run_in_terminal(command, custom.wait)
+    }
+    /// Runs `command` in the current directory, in the terminal fee gives up
+    /// meanwhile. If `wait`, its output stays up until a key is pressed.
+    fn run_in_terminal(&mut self, mut command: Command, wait: bool) -> io::Result<()> {
+        self.cleanup_terminal()?;
+        command.stdout(self.backend.child_output());
+        let outcome = match command.current_dir(&self.cwd).status() {
+            Ok(status) if status.success() => String::new(),
+            Ok(status) => format!("{}, ", status),
+            Err(error) => format!("couldn't run it: {}, ", error),
+        };
+        if wait {
+            queue!(
+                self.backend,
+                Print(format!("\r\n{}press any key to return", outcome))
+            )?;
+            self.backend.flush()?;
+            self.backend.enter()?;
+            loop {
+                match self.events.read()? {
+                    Some(Event::Key(key)) if key.kind == KeyEventKind::Press => break,
+                    Some(_) => {}
+                    None => {
+                        self.listening = false;
+                        break;
+                    }
+                }
+            }
+        } else if !outcome.is_empty() {
+            self.show_message(outcome.trim_end_matches(", ").to_string());
+        }
+        self.prepare_terminal()?;
+        // it may have changed what's listed
+        self.reload()
+    }
+    /// Opens a decompressed copy of the compressed file at `path`, removed once
+    /// the editor exits, or when fee does if the editor isn't waited for.
+    fn open_decompressed(&mut self, path: &Path) -> io::Result<()> {
+        let copy = match archive::TempFile::decompressed(path) {
+            Ok(copy) => copy,
+            Err(error) => {
+                self.show_message(format!("couldn't decompress: {}", error));
+                return Ok(());
+            }
+        };
+        let command = self.editor_command(&copy.path)?;
+        let parts = command::expand_parts(command, Some(&copy.path), &[], &self.cwd);
+        let modified = || {
+            copy.path
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        };
+        let before = modified();
+        if !self.run_editor(parts)? {
+            self.temp_files.push(copy);
+            return Ok(());
+        }
+        if modified() != before {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.show_message(format!(
+                "{} was opened decompressed, the changes weren't saved",
+                name
+            ));
+        }
+        Ok(())
+    }
+    /// Runs the editor command `parts`, returning whether it was waited for.
+    fn run_editor(&mut self, mut parts: VecDeque<OsString>) -> io::Result<bool> {
+        let first = parts.pop_front();
+        if let Some(executable) = first {
+            let gui = Path::new(&executable).file_name().is_some_and(|name| {
+                self.config
+                    .gui_editors
+                    .iter()
+                    .any(|gui| name == gui.as_str())
+            });
+            let mut command = Command::new(executable);
+            command.args(parts);
+            if gui {
+                // whatever it prints would end up all over the interface
+                let mut child = command
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+                std::thread::spawn(move || child.wait());
+                return Ok(false);
+            }
+            self.cleanup_terminal()?;
+            command.stdout(self.backend.child_output());
+            if self.config.wait_for_editor_exit {
+                command.spawn()?.wait()?;
+                self.prepare_terminal()?;
+                self.update()?;
+                return Ok(true);
+            } else {
+                command.spawn()?;
+                self.prepare_terminal()?;
+                self.update()?;
+            }
+        }
+        Ok(false)
+    }
+    /// Opens every marked file, or if none are marked every hit of the current
+    /// search results, in a single editor invocation.
+    ///
+    /// `$fs` in the command is replaced by each distinct file, and `$q` by the path
+    /// of a quickfix file listing every hit as `path:line: text`.
+    fn open_all_hits(&mut self) -> io::Result<()> {
+        let hits: Vec<(&Path, Option<usize>, Option<&str>)> = if !self.marks.is_empty() {
+            let mut marked: Vec<&PathBuf> = self.marks.iter().collect();
+            marked.sort();
+            marked
+                .into_iter()
+                .map(|path| (path.as_path(), None, None))
+                .collect()
+        } else if let Some(view) = &self.virtual_view {
+            view.hits
+                .iter()
+                .map(|hit| (hit.path.as_path(), hit.line, hit.text.as_deref()))
+                .collect()
+        } else {
+            return Ok(());
+        };
+        let mut files: Vec<String> = vec![];
+        let mut quickfix = String::new();
+        for (path, line, text) in hits {
+            if path.is_dir() {
+                continue;
+            }
+            let path = path
+                .to_str()
+                .ok_or(io::Error::other("Couldn't convert path to str."))?
+                .to_string();
+            quickfix.push_str(&format!(
+                "{}:{}: {}\n",
+                path,
+                line.unwrap_or(1),
+                text.unwrap_or_default()
+            ));
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        let mut parts: VecDeque<OsString> = [].into();
+        let mut quickfix_file = None;
+        for part in &self.config.open_all_command {
+            if part == "$fs" {
+                parts.extend(files.iter().map(OsString::from));
+            } else if part == "$q" {
+                let file = archive::TempFile::with_contents("quickfix.txt", quickfix.as_bytes())?;
+                parts.push_back(file.path.clone().into_os_string());
+                quickfix_file = Some(file);
+            } else {
+                parts.push_back(part.into());
+            }
+        }
+        if !self.run_editor(parts)? {
+            // it's removed once fee exits instead
+            self.temp_files.extend(quickfix_file);
+        }
+        Ok(())
+    }
+    /// Sorts the listing by the next mode, or the other way around if `reverse`.
+    fn cycle_sort(&mut self, reverse: bool) -> io::Result<()> {
+        if reverse {
+            self.view.sort_descending = !self.view.sort_descending;
+        } else {
+            self.view.sort_by = self.view.sort_by.next();
+        }
+        self.sorted()
     }
-}
-
-struct Fee {
-    listening: bool,
-    cwd: PathBuf,
-    config: Config,
-    stdout: Stdout,
-    selection: u16,
-    scroll: u16,
-    current_contents: Vec<Item>,
-}
-impl Fee {
-    fn new(cwd: PathBuf, config: Config) -> Self {
-        Fee {
-            listening: false,
-            cwd,
-            config,
-            stdout: stdout(),
+    /// Sorts the listing by the sort key named `name`.
+    fn sort_by(&mut self, name: &str) -> io::Result<()> {
+        match SortBy::ALL.into_iter().find(|by| by.name() == name) {
+            Some(by) => self.view.sort_by = by,
+            None => {
+                let names: Vec<&str> = SortBy::ALL.iter().map(|by| by.name()).collect();
+                let message = format!("can't sort by '{}', only {}", name, names.join(", "));
+                self.show_message(message);
+                return Ok(());
+            }
+        }
+        self.sorted()
+    }
+    /// Lists the items again after the sort order changed, keeping the same one
+    /// selected.
+    fn sorted(&mut self) -> io::Result<()> {
+        let order = if self.view.sort_descending {
+            "descending"
+        } else {
+            "ascending"
+        };
+        self.show_message(format!("sorted by {}, {}", self.view.sort_by.name(), order));
+        if self.virtual_view.is_some() {
+            return Ok(());
+        }
+        match self.selected_item().map(|item| item.path.clone()) {
+            Some(path) => self.jump_to(&path),
+            None => Ok(()),
+        }
+    }
+    /// Opens a second pane next to the listing, at the same directory, or closes
+    /// the inactive one.
+    fn toggle_dual_pane(&mut self) -> io::Result<()> {
+        if self.other_pane.take().is_some() {
+            self.right_pane_active = false;
+            return Ok(());
+        }
+        let view = self.view.clone();
+        let mut pane = Pane {
+            cwd: self.cwd.clone(),
             selection: 0,
             scroll: 0,
             current_contents: vec![],
-        }
-    }
-    fn cleanup_terminal(&mut self) -> io::Result<()> {
-        queue!(
-            self.stdout,
-            Clear(ClearType::All),
-            cursor::Show,
-            cursor::MoveTo(0, 0),
-            ResetColor
-        )?;
-        self.stdout.flush()?;
-        disable_raw_mode()?;
+            metadata: HashMap::new(),
+            requested_metadata: HashSet::new(),
+            virtual_view: None,
+            view,
+            navigation: navigation::Navigation::default(),
+        };
+        // the listing is gotten with the pane's own view
+        std::mem::swap(&mut self.view, &mut pane.view);
+        pane.current_contents = self.get_cwd_contents()?;
+        std::mem::swap(&mut self.view, &mut pane.view);
+        self.other_pane = Some(pane);
         Ok(())
     }
-    fn prepare_terminal(&mut self) -> io::Result<()> {
-        self.stdout = stdout();
-        self.stdout.flush()?;
-        queue!(
-            self.stdout,
-            Clear(ClearType::All),
-            cursor::Hide,
-            cursor::MoveTo(0, 0)
-        )?;
-        self.stdout.flush()?;
-        enable_raw_mode()?;
-        self.current_contents = self.get_cwd_contents()?;
-        Ok(())
+    /// Makes the inactive pane the active one, in dual pane mode.
+    fn switch_pane(&mut self) -> io::Result<()> {
+        if self.other_pane.is_none() {
+            return Ok(());
+        }
+        self.swap_panes();
+        self.right_pane_active = !self.right_pane_active;
+        self.reload()
     }
-    fn update(&mut self) -> io::Result<()> {
-        queue!(
-            self.stdout,
-            Clear(ClearType::All),
-            cursor::MoveTo(0, 0),
-            ResetColor
-        )?;
-        self.draw_text()?;
-        queue!(self.stdout, cursor::MoveTo(0, 0))?;
-        self.stdout.flush()?;
-        Ok(())
+    /// Swaps the listing of the active pane with the inactive one's, so it can
+    /// be worked on.
+    fn swap_panes(&mut self) {
+        let Some(pane) = &mut self.other_pane else {
+            return;
+        };
+        std::mem::swap(&mut self.cwd, &mut pane.cwd);
+        std::mem::swap(&mut self.selection, &mut pane.selection);
+        std::mem::swap(&mut self.scroll, &mut pane.scroll);
+        std::mem::swap(&mut self.current_contents, &mut pane.current_contents);
+        std::mem::swap(&mut self.metadata, &mut pane.metadata);
+        std::mem::swap(&mut self.requested_metadata, &mut pane.requested_metadata);
+        std::mem::swap(&mut self.virtual_view, &mut pane.virtual_view);
+        std::mem::swap(&mut self.view, &mut pane.view);
+        std::mem::swap(&mut self.navigation, &mut pane.navigation);
     }
-    fn get_cwd_contents(&self) -> io::Result<Vec<Item>> {
-        let mut dirs = vec![];
-        let mut files = vec![];
-
-        for item in std::fs::read_dir(&self.cwd)?.flatten() {
-            let item_type = item.file_type()?;
-            let item_name = item
-                .file_name()
-                .to_str()
-                .ok_or(io::Error::other("Couldn't get filename of item."))?
-                .to_string();
-
-            if item_type.is_dir() {
-                dirs.push(Item {
-                    name: item_name,
-                    item_type: ItemType::Directory,
-                })
-            } else if item_type.is_file() {
-                files.push(Item {
-                    name: item_name,
-                    item_type: ItemType::File,
-                })
+    /// Copies or moves the marked items, or the selected one, to the directory of
+    /// the inactive pane.
+    fn transfer_to_other_pane(&mut self, moving: bool) -> io::Result<()> {
+        match &self.other_pane {
+            Some(pane) => {
+                let directory = pane.cwd.to_string_lossy().to_string();
+                self.transfer(&directory, moving)
+            }
+            None => {
+                let name = if moving { "move" } else { "copy" };
+                self.show_message(format!("{} needs a directory", name));
+                Ok(())
             }
         }
-        let mut items = dirs;
-        items.append(&mut files);
-        Ok(items)
     }
-
-    fn print_line(
-        &mut self,
-        text: &str,
-        x: u16,
-        y: u16,
-        color: Color,
-        highlighted: bool,
-    ) -> io::Result<()> {
-        queue!(self.stdout, cursor::MoveTo(x, y))?;
-        queue!(self.stdout, SetForegroundColor(color))?;
-        if highlighted {
-            queue!(self.stdout, SetBackgroundColor(Color::White))?;
-            queue!(self.stdout, SetForegroundColor(Color::Black))?;
-        }
-        print!("{}", text);
-        if highlighted {
-            queue!(self.stdout, SetBackgroundColor(Color::Reset))?;
-        }
-        Ok(())
-    }
-    fn draw_text(&mut self) -> io::Result<()> {
-        let dir_color = Color::Rgb {
-            r: self.config.dir_color[0],
-            g: self.config.dir_color[1],
-            b: self.config.dir_color[2],
-        };
-        let file_color = Color::Rgb {
-            r: self.config.file_color[0],
-            g: self.config.file_color[1],
-            b: self.config.file_color[2],
-        };
-        for index in self.scroll..get_terminal_height()? + self.scroll {
-            let length = self.current_contents.len();
-
-            if index >= length as u16 {
-                continue;
-            }
-            let item = &self.current_contents[index as usize];
-            let name = &item.name.to_owned();
-            let mut color = dir_color;
-
-            if item.is_file() {
-                color = file_color;
+    /// Shows or hides dotfiles and the names listed in `.hidden`, keeping the selection.
+    fn toggle_hidden(&mut self) -> io::Result<()> {
+        self.view.show_hidden = !self.view.show_hidden;
+        if self.virtual_view.is_some() {
+            return Ok(());
+        }
+        match self.selected_item().map(|item| item.path.clone()) {
+            Some(path) => self.jump_to(&path),
+            None => {
+                self.set_contents(self.get_cwd_contents()?);
+                Ok(())
             }
-            self.print_line(name, 0, index - self.scroll, color, self.selection == index)?;
         }
-        queue!(self.stdout, ResetColor)?;
+    }
+    /// Navigates to the directory containing `path`, with `path` selected.
+    fn jump_to(&mut self, path: &Path) -> io::Result<()> {
+        let Some(parent) = path.parent() else {
+            return Ok(());
+        };
+        if self.outside_archive(parent) {
+            return self.ask_to_leave_archive(Some(path.to_path_buf()));
+        }
+        self.cwd = parent.to_path_buf();
+        self.virtual_view = None;
+        self.selection = 0;
+        self.scroll = 0;
+        self.set_contents(self.get_cwd_contents()?);
+        if let Some(index) = self
+            .current_contents
+            .iter()
+            .position(|item| item.path == path)
+        {
+            self.selection = index;
+            self.scroll = (index + 1).saturating_sub(self.list_height()? as usize);
+        }
         Ok(())
     }
-    fn select(&mut self) -> io::Result<()> {
-        for (index, item) in self.current_contents.iter().enumerate() {
-            if index as u16 == self.selection {
-                match item.item_type {
-                    ItemType::Directory => {
-                        self.cwd.push(&item.name);
-                        self.selection = 0;
-                        self.scroll = 0;
-                        self.current_contents = self.get_cwd_contents()?;
-                    }
-                    ItemType::File => {
-                        let mut filepath = self.cwd.clone();
-                        filepath.push(&item.name);
-
-                        let mut parts: VecDeque<String> = [].into();
-                        let mut command = &self.config.text_editor_command;
-                        if self.config.text_editor_command != self.config.binary_editor_command {
-                            // if the binary editor != the text editor
-                            // check if the file is utf-8 or if it should be read with the binary editor
-                            if !is_valid_utf8(&filepath)? {
-                                command = &self.config.binary_editor_command;
-                            }
-                        }
-
-                        let filepath_str = filepath
-                            .to_str()
-                            .ok_or(io::Error::other("Couldn't convert path to str."))?;
-
-                        for part in command {
-                            if part == "$f" {
-                                parts.push_back(filepath_str.to_string());
-                            } else {
-                                parts.push_back(part.to_string());
-                            }
-                        }
-
-                        let first = parts.pop_front();
-                        if let Some(executable) = first {
-                            let mut command = Command::new(executable);
-                            command.args(parts);
-                            self.cleanup_terminal()?;
-                            if self.config.wait_for_editor_exit {
-                                command.spawn()?.wait()?;
-                                self.prepare_terminal()?;
-                                self.update()?;
-                            } else {
-                                command.spawn()?;
-                                self.prepare_terminal()?;
-                                self.update()?;
-                            }
-                        }
-                    }
-                }
-                break;
-            }
+    /// Leaves the search results for the directory actually containing the
+    /// selected hit, with it selected.
+    fn reveal(&mut self) -> io::Result<()> {
+        if self.virtual_view.is_none() {
+            return Ok(());
         }
+        match self.selected_item().map(|item| item.path.clone()) {
+            Some(path) => self.jump_to(&path),
+            None => Ok(()),
+        }
+    }
+    fn search(&mut self, kind: SearchKind, query: String) -> io::Result<()> {
+        let hits = search::search(&self.cwd, &query, &kind, self.ignore(&self.cwd));
+        if hits.is_empty() {
+            self.show_message(format!("no matches for '{}'", query));
+            return Ok(());
+        }
+        let title = match kind {
+            SearchKind::Name => format!("find '{}': {} results", query, hits.len()),
+            SearchKind::Content => format!("grep '{}': {} results", query, hits.len()),
+        };
+        let (previous_selection, previous_scroll) = match self.virtual_view.take() {
+            Some(view) => (view.previous_selection, view.previous_scroll),
+            None => (self.selection, self.scroll),
+        };
+        self.virtual_view = Some(VirtualView {
+            title,
+            root: self.cwd.clone(),
+            hits,
+            previous_selection,
+            previous_scroll,
+        });
+        self.selection = 0;
+        self.scroll = 0;
+        self.set_contents(self.get_contents()?);
         Ok(())
     }
     fn go_back(&mut self) -> io::Result<()> {
+        if let Some(view) = self.virtual_view.take() {
+            self.selection = view.previous_selection;
+            self.scroll = view.previous_scroll;
+            self.set_contents(self.get_cwd_contents()?);
+            return Ok(());
+        }
+        if self
+            .archives
+            .last()
+            .is_some_and(|mounted| mounted.root == self.cwd)
+        {
+            return self.ask_to_leave_archive(None);
+        }
         let parent = self.cwd.parent();
         if let Some(parent) = parent {
             self.cwd = parent.to_path_buf();
             self.selection = 0;
             self.scroll = 0;
-            self.current_contents = self.get_cwd_contents()?;
+            self.set_contents(self.get_cwd_contents()?);
         }
         Ok(())
     }
     fn move_up(&mut self) -> io::Result<()> {
+        if self.current_contents.is_empty() {
+            return Ok(());
+        }
         if self.selection == 0 {
-            self.selection = self.current_contents.len() as u16 - 1;
-            self.scroll = cmp::max(
-                0,
-                self.current_contents.len() as i16 - get_terminal_height()? as i16,
-            ) as u16;
+            self.selection = self.current_contents.len() - 1;
+            self.scroll = self
+                .current_contents
+                .len()
+                .saturating_sub(self.list_height()? as usize);
         } else {
             self.selection -= 1;
             if self.scroll > self.selection {
@@ -255,75 +2171,860 @@ impl Fee {
         }
         Ok(())
     }
+    /// Moves the selection to the `index`th item, scrolling it into view.
+    fn move_to(&mut self, index: usize) -> io::Result<()> {
+        if self.current_contents.is_empty() {
+            return Ok(());
+        }
+        self.select_index(index)
+    }
     fn move_down(&mut self) -> io::Result<()> {
-        if self.selection >= self.current_contents.len() as u16 - 1 {
+        if self.selection + 1 >= self.current_contents.len() {
             self.selection = 0;
             self.scroll = 0;
         } else {
             self.selection += 1;
-            if self.selection - self.scroll >= get_terminal_height()? {
+            if self.selection - self.scroll >= self.list_height()? as usize {
                 self.scroll += 1;
             }
         }
         Ok(())
     }
-    fn handle_keypress(&mut self, event: Event) -> io::Result<()> {
-        if let Event::Key(key) = event {
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Up => self.move_up()?,
-                    KeyCode::Down => self.move_down()?,
-                    KeyCode::Enter => self.select()?,
-                    KeyCode::Right => self.select()?,
-                    KeyCode::Esc => self.go_back()?,
-                    KeyCode::Left => self.go_back()?,
-                    KeyCode::Char(char) => {
-                        if char == 'c' && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            self.listening = false;
-                        }
-                    }
-                    _ => {}
+    /// Returns to the directory visited before this one, or the one gone back
+    /// from if `forward`, selecting what was selected there.
+    fn move_through_history(&mut self, forward: bool) -> io::Result<()> {
+        let current = navigation::Visit {
+            directory: self.cwd.clone(),
+            selected: self.selected_item().map(|item| item.path.clone()),
+        };
+        let visit = if forward {
+            self.navigation.forward(current)
+        } else {
+            self.navigation.back(current)
+        };
+        let Some(visit) = visit else {
+            let direction = if forward { "forward" } else { "back" };
+            self.show_message(format!("nothing to go {} to", direction));
+            return Ok(());
+        };
+        self.change_directory(&visit.directory.to_string_lossy())?;
+        match visit.selected {
+            Some(selected) => self.select_path(&selected),
+            None => Ok(()),
+        }
+    }
+    /// Changes directory to `path`, which may be relative to the current directory.
+    fn change_directory(&mut self, path: &str) -> io::Result<()> {
+        let path = command::resolve_path(path, &self.cwd);
+        let Ok(path) = path.canonicalize() else {
+            self.show_message(format!("no such directory: {}", path.display()));
+            return Ok(());
+        };
+        if !path.is_dir() {
+            self.show_message(format!("not a directory: {}", path.display()));
+            return Ok(());
+        }
+        if self.outside_archive(&path) {
+            return self.ask_to_leave_archive(Some(path));
+        }
+        self.cwd = path;
+        self.virtual_view = None;
+        self.selection = 0;
+        self.scroll = 0;
+        self.set_contents(self.get_cwd_contents()?);
+        Ok(())
+    }
+    fn run_command(&mut self, input: &str) -> io::Result<()> {
+        if let Some(shell_command) = input.trim_start().strip_prefix('!') {
+            return self.run_shell(shell_command);
+        }
+        let (name, argument) = command::parse(input);
+        match name {
+            "" => {}
+            "cd" => self.go_to(if argument.is_empty() { "~" } else { argument })?,
+            "find" | "grep" if argument.is_empty() => {
+                self.show_message(format!("{} needs a query", name));
+            }
+            "find" => self.search(SearchKind::Name, argument.to_string())?,
+            "grep" => self.search(SearchKind::Content, argument.to_string())?,
+            "open-all" => self.open_all_hits()?,
+            "reveal" => self.reveal()?,
+            "preview" => self.show_preview = !self.show_preview,
+            "dual" => self.toggle_dual_pane()?,
+            "back" => self.move_through_history(false)?,
+            "forward" => self.move_through_history(true)?,
+            "wrap" => self.wrap_previews = !self.wrap_previews,
+            "hidden" => self.toggle_hidden()?,
+            "properties" => self.show_properties()?,
+            "select" => {
+                let path = command::resolve_path(argument, &self.cwd);
+                if path.exists() {
+                    self.jump_to(&path)?;
+                } else {
+                    self.show_message(format!("no such file: {}", path.display()));
                 }
-                self.update()?;
             }
+            "reload" => self.reload()?,
+            "sort" => self.sort_by(argument)?,
+            // without a directory, they go to the other pane
+            "copy" | "move" if argument.is_empty() => {
+                self.transfer_to_other_pane(name == "move")?
+            }
+            "copy" => self.transfer(argument, false)?,
+            "move" => self.transfer(argument, true)?,
+            "delete" => self.confirm_delete(false),
+            "delete!" => self.confirm_delete(true),
+            "touch" => self.create(argument, false, None)?,
+            "mkdir" => self.create(argument, true, None)?,
+            "yank" => self.yank(false),
+            "cut" => self.yank(true),
+            "paste" => self.paste()?,
+            "copy-uri" => self.copy_uris()?,
+            "echo" => self.show_message(argument.to_string()),
+            "rename" => self.open_batch_rename(),
+            "open-with" => self.show_open_with(),
+            "unmark" => self.marks.clear(),
+            "save-marks" => self.save_marks(argument),
+            "restore-marks" => self.restore_marks(argument),
+            "split" => self.split_selected(argument)?,
+            "join" => self.join_selected()?,
+            "accessible" => self.config.accessible = !self.config.accessible,
+            "dry-run" => {
+                self.config.dry_run = !self.config.dry_run;
+                let state = if self.config.dry_run { "on" } else { "off" };
+                self.show_message(format!("dry run {}", state));
+            }
+            "drives" => self.show_drives()?,
+            "quit" | "q" => self.quit(),
+            "quit!" | "q!" => self.listening = false,
+            "messages" => self.show_message_log()?,
+            "bookmarks" => self.show_bookmarks()?,
+            "jump" => self.jump_to_bookmark(argument)?,
+            "files" => self.show_finder(),
+            _ => self.show_message(format!("unknown command: {}", name)),
         }
         Ok(())
     }
-
+    /// Stops listening, unless an archive has unsaved changes.
+    fn quit(&mut self) {
+        let unsaved = self
+            .archives
+            .iter()
+            .find(|mounted| mounted.changes().is_some());
+        match unsaved {
+            Some(mounted) => self.show_message(format!(
+                "{} has unsaved changes, leave it to save them, or discard them with :quit!",
+                mounted.archive.display()
+            )),
+            None => self.listening = false,
+        }
+    }
+    /// Adds `query` to the history saved as `name`, if histories are kept between
+    /// runs.
+    fn save_history(&mut self, name: &str, query: &str) {
+        if !self.config.persist_history || query.is_empty() {
+            return;
+        }
+        if let Err(error) = History::save(name, query) {
+            self.show_message(format!("couldn't save the {} history: {}", name, error));
+        }
+    }
+    fn start_filter(&mut self) {
+        let mut filter = filter::Filter::new((self.selection, self.scroll));
+        filter.update(&self.names());
+        // nothing is filtered out yet, so the selection stays where it was
+        filter.selection = self.selection;
+        self.mode = Mode::Filter(filter);
+    }
+    fn names(&self) -> Vec<&str> {
+        self.current_contents
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect()
+    }
     fn listen(&mut self) -> io::Result<()> {
         self.listening = true;
         self.prepare_terminal()?;
+        self.export_cwd();
+        let result = self.run();
+        // written even if something went wrong, so the shell still follows
+        let written = self.write_chosen_dir();
+        self.cleanup_terminal()?;
+        // after cleaning up, as printing it could otherwise land on the screen
+        let chosen = self.write_chosen_file();
+        result.and(written).and(chosen)
+    }
+    fn run(&mut self) -> io::Result<()> {
         self.update()?;
         while self.listening {
-            self.handle_keypress(event::read()?)?;
+            match self.poll_timeout() {
+                Some(timeout) => {
+                    if self.events.poll(timeout)? {
+                        self.read_event()?;
+                    }
+                    self.update_previews()?;
+                    self.update_metadata()?;
+                    self.update_directory_stats()?;
+                    self.update_commits()?;
+                    self.update_finder()?;
+                }
+                // nothing is going on in the background, so sleep until there's input
+                None => self.read_event()?,
+            }
+            self.run_remote_commands()?;
+            self.export_cwd();
+        }
+        Ok(())
+    }
+    /// Writes the directory fee was last in to `choose_dir`, if it's set, so a
+    /// shell function can cd there after fee quits. Inside an archive, that's the
+    /// directory containing it.
+    fn write_chosen_dir(&self) -> io::Result<()> {
+        let Some(choose_dir) = &self.choose_dir else {
+            return Ok(());
+        };
+        let directory = match self.archives.first() {
+            Some(mounted) => mounted.archive.parent().unwrap_or(&self.cwd),
+            None => &self.cwd,
+        };
+        std::fs::write(choose_dir, format!("{}\n", directory.display()))
+    }
+    /// Writes the file opened while choosing to `choose_file`, or to stdout if
+    /// that's `-`.
+    fn write_chosen_file(&self) -> io::Result<()> {
+        let (Some(choose_file), Some(chosen)) = (&self.choose_file, &self.chosen) else {
+            return Ok(());
+        };
+        let line = format!("{}\n", chosen.display());
+        if choose_file.as_os_str() == "-" {
+            let mut stdout = io::stdout();
+            stdout.write_all(line.as_bytes())?;
+            return stdout.flush();
+        }
+        std::fs::write(choose_file, line)
+    }
+    /// Handles the next event, or stops listening once there are no more.
+    fn read_event(&mut self) -> io::Result<()> {
+        match self.events.read()? {
+            Some(event) => self.handle_keypress(event),
+            None => {
+                self.listening = false;
+                Ok(())
+            }
+        }
+    }
+    /// Lets other programs know about a change of directory, through the
+    /// `directory_file` and `directory_change_command` options.
+    fn export_cwd(&mut self) {
+        if self.exported_cwd == self.cwd {
+            return;
+        }
+        self.exported_cwd = self.cwd.clone();
+        let cwd = self.cwd.to_string_lossy().to_string();
+        if let Some(file) = &self.config.directory_file {
+            if let Err(error) = std::fs::write(file, format!("{}\n", cwd)) {
+                self.show_message(format!("couldn't write directory file: {}", error));
+            }
+        }
+        let mut parts = self
+            .config
+            .directory_change_command
+            .iter()
+            .map(|part| part.replace("$d", &cwd));
+        let Some(executable) = parts.next() else {
+            return;
+        };
+        let spawned = Command::new(executable)
+            .args(parts)
+            .env("FEE_DIR", &cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            // reap it once it's done, without holding anything up
+            Ok(mut child) => {
+                std::thread::spawn(move || child.wait());
+            }
+            Err(error) => self.show_message(format!("couldn't run directory hook: {}", error)),
+        }
+    }
+    fn run_remote_commands(&mut self) -> io::Result<()> {
+        let mut received = false;
+        while let Some(command) = self.remote.as_ref().and_then(|remote| remote.try_receive()) {
+            received = true;
+            if command.trim_start().starts_with('!') {
+                self.show_message("shell commands can't be sent remotely".to_string());
+                continue;
+            }
+            if let Err(error) = self.run_command(&command) {
+                self.show_message(format!("error: {}", error));
+            }
+        }
+        if received {
+            self.update()?;
         }
-        self.cleanup_terminal()?;
         Ok(())
     }
+    /// How long to wait for input before checking on background work, or `None`
+    /// if there's none, and we can wait for input indefinitely.
+    fn poll_timeout(&self) -> Option<Duration> {
+        // results are left waiting until the terminal is focused again
+        if !self.focused {
+            return None;
+        }
+        let finding = matches!(&self.mode, Mode::Finder(finder) if !finder.done);
+        if self.requested_preview.is_some()
+            || self.requested_stat.is_some()
+            || self.requested_commits.is_some()
+            || self.metadata_loader.is_loading()
+            || finding
+        {
+            return Some(POLL_INTERVAL);
+        }
+        let preview = self
+            .pending_preview
+            .as_ref()
+            .map(|(_, since)| PREVIEW_DELAY.saturating_sub(since.elapsed()));
+        let stat = self.pending_stat.as_ref().map(|(_, since)| {
+            let delay = Duration::from_millis(self.config.directory_stat_delay_ms.unwrap_or(0));
+            delay.saturating_sub(since.elapsed())
+        });
+        match (preview, stat) {
+            (Some(preview), Some(stat)) => Some(cmp::min(preview, stat)),
+            (preview, stat) => preview.or(stat),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 struct Config {
+    /// Version of the config format, used to upgrade files written by older versions.
+    #[serde(default)]
+    version: u32,
     text_editor_command: Vec<String>,
     binary_editor_command: Vec<String>,
+    /// Command opening text files bigger than `large_text_above` bytes, like a
+    /// pager. Text files are all opened with `text_editor_command` if it's empty.
+    #[serde(default)]
+    large_text_editor_command: Vec<String>,
+    #[serde(default = "default_large_text_above")]
+    large_text_above: u64,
     wait_for_editor_exit: bool,
+    /// Editors opening in a window of their own, like `code`. Commands run by
+    /// them keep the terminal to fee, which doesn't wait for them to exit.
+    #[serde(default = "default_gui_editors")]
+    gui_editors: Vec<String>,
     dir_color: [u8; 3],
     file_color: [u8; 3],
+    /// Color of symlinks and junctions.
+    #[serde(default = "default_link_color")]
+    link_color: [u8; 3],
+    /// Color of sockets, FIFOs and devices.
+    #[serde(default = "default_special_color")]
+    special_color: [u8; 3],
+    /// Directories with these extensions are listed as files, and opened with
+    /// `package_open_command` rather than entered. Defaults to `.app` bundles and
+    /// the like on macOS.
+    #[serde(default = "default_package_extensions")]
+    package_extensions: Vec<String>,
+    /// Command opening packages. `$f` expands to the package.
+    #[serde(default = "default_package_open_command")]
+    package_open_command: Vec<String>,
+    /// Commands opening files instead of the editors, keyed by an extension like
+    /// `png` or a glob matching the name like `*.tar.gz`. `$f` expands to the
+    /// file. The longest matching pattern opens it, and the rest are offered in
+    /// the open with menu.
+    #[serde(default)]
+    associations: BTreeMap<String, Vec<String>>,
+    /// When enabled, pressing enter on a file in search results navigates to
+    /// the directory containing it rather than opening it.
+    #[serde(default)]
+    search_enter_jumps: bool,
+    /// Remember search and filter queries between runs, rather than only during
+    /// one.
+    #[serde(default)]
+    persist_history: bool,
+    /// Open files that aren't text, like PDFs and images, with the program the
+    /// system opens them with rather than `binary_editor_command`.
+    #[serde(default)]
+    open_binary_with_system: bool,
+    /// Command used to open every marked file or search hit at once. `$fs` expands
+    /// to all of the files, `$q` to a quickfix file (e.g. `["vim", "-q", "$q"]`).
+    #[serde(default = "default_open_all_command")]
+    open_all_command: Vec<String>,
+    /// Command whose output is shown when previewing PDFs. `$f` expands to the file.
+    #[serde(default = "default_pdf_preview_command")]
+    pdf_preview_command: Vec<String>,
+    /// Previewers kept running, for the files with the given `extensions`. Each
+    /// `command` is sent paths on stdin, one per line, and answers each with the
+    /// preview followed by a line with only a NUL byte.
+    #[serde(default)]
+    preview_servers: Vec<previewserver::PreviewServer>,
+    /// ffprobe-compatible command printing JSON metadata of audio/video files.
+    #[serde(default = "default_media_probe_command")]
+    media_probe_command: Vec<String>,
+    /// Command grabbing a PNG frame of a video for its preview. `$f` expands to the
+    /// video and `$o` to the output file.
+    #[serde(default = "default_video_thumbnail_command")]
+    video_thumbnail_command: Vec<String>,
+    /// Preview images, with the terminal's graphics protocol if it has one, or
+    /// otherwise as colored blocks.
+    #[serde(default = "default_image_previews")]
+    image_previews: bool,
+    /// Command shrinking an image to a PPM thumbnail for its preview. `$f`
+    /// expands to the image and `$o` to the output file.
+    #[serde(default = "default_image_thumbnail_command")]
+    image_thumbnail_command: Vec<String>,
+    /// Arrays in JSON/YAML previews with more items than this are cut short.
+    #[serde(default = "default_preview_fold_arrays")]
+    preview_fold_arrays: Option<usize>,
+    /// Show line numbers in text previews.
+    #[serde(default)]
+    preview_line_numbers: bool,
+    /// Highlight the syntax of source files in previews.
+    #[serde(default = "default_preview_highlight")]
+    preview_highlight: bool,
+    /// Wrap long lines in previews rather than cutting them off. Can be toggled with `w`.
+    #[serde(default)]
+    preview_wrap: bool,
+    /// At most this many bytes of a file, or of a previewer's output, are previewed.
+    #[serde(default = "default_preview_max_bytes")]
+    preview_max_bytes: u64,
+    /// External previewers running longer than this many milliseconds are killed.
+    #[serde(default = "default_preview_timeout_ms")]
+    preview_timeout_ms: u64,
+    /// Files with these extensions are never previewed, like `["iso", "bin"]`.
+    #[serde(default)]
+    preview_disabled_extensions: Vec<String>,
+    /// Files larger than this many bytes are never previewed.
+    #[serde(default)]
+    preview_disabled_above: Option<u64>,
+    /// After the cursor rests on a directory for this many milliseconds, its item
+    /// count and total size are shown by `{contents}` in the status bar. `null`
+    /// turns it off.
+    #[serde(default = "default_directory_stat_delay_ms")]
+    directory_stat_delay_ms: Option<u64>,
+    /// Leave entries matched by `.gitignore` and `.ignore` files out of the
+    /// listing, searches, directory sizes and the file finder, even while hidden
+    /// files are shown.
+    #[serde(default)]
+    respect_ignore_files: bool,
+    /// In git repositories, show the date and hash of the last commit touching
+    /// each entry in a column next to its name.
+    #[serde(default)]
+    git_column: bool,
+    /// Whether dotfiles, names listed in a directory's `.hidden` file and, on
+    /// windows, files with the hidden attribute are shown on startup. Can be
+    /// toggled with `.`.
+    #[serde(default = "default_show_hidden")]
+    show_hidden: bool,
+    /// Whether sizes, modification times and permissions are shown next to names
+    /// on startup. Can be toggled with `L`.
+    #[serde(default)]
+    show_details: bool,
+    /// Whether the preview pane is shown on startup. Can be toggled with `p`.
+    #[serde(default = "default_show_preview")]
+    show_preview: bool,
+    /// Rules coloring the items they match, checked in order. The first one
+    /// whose conditions (`name` regex, `type`, `min_size`/`max_size`,
+    /// `min_age`/`max_age`) all hold sets the `color`, `bold`, `italic`,
+    /// `underline` and `icon` of the item.
+    #[serde(default)]
+    color_rules: Vec<rules::ColorRule>,
+    /// Tint the modification `time` in the details view, or the `name`, on a
+    /// gradient from `age_heat_colors[0]` for things changed just now to
+    /// `age_heat_colors[1]` for ones a year old or more. Color rules setting a
+    /// color win over the tinted name.
+    #[serde(default)]
+    age_heat: AgeHeat,
+    #[serde(default = "default_age_heat_colors")]
+    age_heat_colors: [[u8; 3]; 2],
+    /// Start with two panes side by side instead of the preview, switched between
+    /// with Tab. Can be toggled with `T`.
+    #[serde(default)]
+    dual_pane: bool,
+    /// Fraction of the screen taken up by the preview pane.
+    #[serde(default = "default_preview_ratio")]
+    preview_ratio: f32,
+    /// Whether the preview pane is to the right of, or below, the file listing.
+    #[serde(default)]
+    preview_orientation: Orientation,
+    /// How the listing is sorted on startup. Can be cycled through with `s`.
+    #[serde(default)]
+    sort_by: SortBy,
+    /// Whether the listing is sorted the other way around on startup, biggest or
+    /// newest first. Can be toggled with `S`.
+    #[serde(default)]
+    sort_descending: bool,
+    #[serde(default)]
+    theme: Theme,
+    #[serde(default)]
+    status_bar: StatusBar,
+    /// `vim` additionally moves around with h/j/k/l, `gg`/`G` and ctrl+d/ctrl+u.
+    #[serde(default)]
+    key_style: KeyStyle,
+    /// Commands run by keys, like `{"key": "<c-g>", "command": ["git", "status"],
+    /// "wait": true}`. `$f` expands to the selected item, `$fs` to the marked ones
+    /// and `$d` to the current directory. `wait` keeps the output up until a key
+    /// is pressed.
+    #[serde(default)]
+    custom_commands: Vec<keymap::CustomCommand>,
+    /// Shell opened in the current directory with `$`, with its arguments.
+    /// Defaults to `$SHELL`.
+    #[serde(default)]
+    shell: Vec<String>,
+    /// Capture the mouse, so right-clicking an item opens its context menu. This
+    /// keeps the terminal from selecting text.
+    #[serde(default)]
+    mouse: bool,
+    /// Stop checking on previews, directory sizes and other background work
+    /// while the terminal isn't focused, picking it back up once it is.
+    #[serde(default = "default_pause_when_unfocused")]
+    pause_when_unfocused: bool,
+    /// The current directory is written to this file whenever it changes.
+    #[serde(default)]
+    directory_file: Option<String>,
+    /// Command run in the background whenever the directory changes. `$d` (and
+    /// the `FEE_DIR` environment variable) expands to the new directory.
+    #[serde(default)]
+    directory_change_command: Vec<String>,
+    /// Screen reader friendly mode: no decorations, a line describing the selection,
+    /// and the terminal cursor kept on the selection. Can be toggled with `:accessible`.
+    #[serde(default)]
+    accessible: bool,
+    /// File operations only report what they would do, in the message log, without
+    /// touching any files. Can be toggled with `:dry-run`.
+    #[serde(default)]
+    dry_run: bool,
+    /// Deleting moves files to the trash, rather than deleting them permanently,
+    /// which can still be done with `D`.
+    #[serde(default = "default_use_trash")]
+    use_trash: bool,
+    /// Every change made to files is appended to `audit.log` in the config directory.
+    #[serde(default)]
+    audit_log: bool,
+    /// Directories listed in the bookmark picker, opened with `'`.
+    #[serde(default)]
+    bookmarks: Vec<bookmarks::Bookmark>,
+    /// Key sequences typed in the listing, and the commands they run.
+    #[serde(default = "default_chords")]
+    chords: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum Orientation {
+    #[default]
+    Right,
+    Bottom,
 }
 impl Config {
     fn default_config() -> Self {
         Config {
+            version: migration::CONFIG_VERSION,
             text_editor_command: vec!["nano".to_string(), "$f".to_string()],
             binary_editor_command: vec!["hexedit".to_string(), "$f".to_string()],
+            large_text_editor_command: vec![],
+            large_text_above: default_large_text_above(),
             wait_for_editor_exit: true,
+            gui_editors: default_gui_editors(),
             dir_color: [59, 120, 255],
             file_color: [46, 199, 219],
+            link_color: default_link_color(),
+            special_color: default_special_color(),
+            package_extensions: default_package_extensions(),
+            package_open_command: default_package_open_command(),
+            associations: BTreeMap::new(),
+            search_enter_jumps: false,
+            open_binary_with_system: false,
+            persist_history: false,
+            open_all_command: default_open_all_command(),
+            pdf_preview_command: default_pdf_preview_command(),
+            preview_servers: vec![],
+            media_probe_command: default_media_probe_command(),
+            video_thumbnail_command: default_video_thumbnail_command(),
+            image_previews: default_image_previews(),
+            image_thumbnail_command: default_image_thumbnail_command(),
+            preview_fold_arrays: default_preview_fold_arrays(),
+            preview_line_numbers: false,
+            preview_highlight: default_preview_highlight(),
+            preview_wrap: false,
+            preview_max_bytes: default_preview_max_bytes(),
+            preview_timeout_ms: default_preview_timeout_ms(),
+            preview_disabled_extensions: vec![],
+            preview_disabled_above: None,
+            directory_stat_delay_ms: default_directory_stat_delay_ms(),
+            respect_ignore_files: false,
+            git_column: false,
+            show_hidden: default_show_hidden(),
+            show_details: false,
+            show_preview: default_show_preview(),
+            dual_pane: false,
+            color_rules: vec![],
+            age_heat: AgeHeat::Off,
+            age_heat_colors: default_age_heat_colors(),
+            preview_ratio: default_preview_ratio(),
+            preview_orientation: Orientation::Right,
+            sort_by: SortBy::Name,
+            sort_descending: false,
+            theme: Theme::default(),
+            status_bar: StatusBar::default(),
+            key_style: KeyStyle::Arrows,
+            custom_commands: vec![],
+            shell: vec![],
+            mouse: false,
+            pause_when_unfocused: default_pause_when_unfocused(),
+            accessible: false,
+            dry_run: false,
+            use_trash: default_use_trash(),
+            audit_log: false,
+            bookmarks: vec![],
+            chords: default_chords(),
+            directory_file: None,
+            directory_change_command: vec![],
         }
     }
 }
 
-fn get_terminal_height() -> io::Result<u16> {
-    Ok(crossterm::terminal::size()?.1 - 1)
+fn default_link_color() -> [u8; 3] {
+    [190, 130, 255]
+}
+
+fn default_special_color() -> [u8; 3] {
+    [230, 190, 70]
+}
+
+fn default_gui_editors() -> Vec<String> {
+    ["code", "codium", "subl", "zed", "gedit", "kate"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_package_extensions() -> Vec<String> {
+    if cfg!(target_os = "macos") {
+        vec!["app".to_string(), "bundle".to_string(), "pkg".to_string()]
+    } else {
+        vec![]
+    }
+}
+
+/// The command opening files with the program chosen for their kind of file.
+fn system_open_command() -> Vec<String> {
+    let command: &[&str] = if cfg!(target_os = "macos") {
+        &["open", "$f"]
+    } else if cfg!(windows) {
+        // not through `cmd /c start`, as cmd would run whatever follows a `&`
+        // in the file's name
+        &["rundll32", "url.dll,FileProtocolHandler", "$f"]
+    } else {
+        &["xdg-open", "$f"]
+    };
+    command.iter().map(|part| part.to_string()).collect()
+}
+
+fn default_package_open_command() -> Vec<String> {
+    vec!["open".to_string(), "$f".to_string()]
+}
+
+/// The user's shell, for opening a subshell when `shell` isn't set.
+fn default_shell() -> String {
+    if cfg!(windows) {
+        std::env::var("COMSPEC").unwrap_or("cmd".to_string())
+    } else {
+        std::env::var("SHELL").unwrap_or("sh".to_string())
+    }
+}
+
+fn default_open_all_command() -> Vec<String> {
+    vec!["nano".to_string(), "$fs".to_string()]
+}
+
+fn default_pdf_preview_command() -> Vec<String> {
+    vec![
+        "pdftotext".to_string(),
+        "-l".to_string(),
+        "3".to_string(),
+        "-layout".to_string(),
+        "$f".to_string(),
+        "-".to_string(),
+    ]
+}
+
+fn default_media_probe_command() -> Vec<String> {
+    vec![
+        "ffprobe".to_string(),
+        "-v".to_string(),
+        "quiet".to_string(),
+        "-print_format".to_string(),
+        "json".to_string(),
+        "-show_format".to_string(),
+        "-show_streams".to_string(),
+        "$f".to_string(),
+    ]
+}
+
+fn default_video_thumbnail_command() -> Vec<String> {
+    vec![
+        "ffmpeg".to_string(),
+        "-v".to_string(),
+        "quiet".to_string(),
+        "-ss".to_string(),
+        "3".to_string(),
+        "-i".to_string(),
+        "$f".to_string(),
+        "-frames:v".to_string(),
+        "1".to_string(),
+        "-vf".to_string(),
+        "scale=480:-1".to_string(),
+        "-y".to_string(),
+        "$o".to_string(),
+    ]
+}
+
+fn default_preview_fold_arrays() -> Option<usize> {
+    Some(20)
+}
+
+fn default_preview_max_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_preview_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_image_previews() -> bool {
+    true
+}
+fn default_image_thumbnail_command() -> Vec<String> {
+    vec![
+        "ffmpeg".to_string(),
+        "-v".to_string(),
+        "quiet".to_string(),
+        "-i".to_string(),
+        "$f".to_string(),
+        "-frames:v".to_string(),
+        "1".to_string(),
+        "-vf".to_string(),
+        "scale=480:480:force_original_aspect_ratio=decrease".to_string(),
+        "-y".to_string(),
+        "$o".to_string(),
+    ]
+}
+fn default_pause_when_unfocused() -> bool {
+    true
+}
+fn default_preview_highlight() -> bool {
+    true
+}
+fn default_directory_stat_delay_ms() -> Option<u64> {
+    Some(500)
+}
+
+fn default_show_hidden() -> bool {
+    true
+}
+
+fn default_large_text_above() -> u64 {
+    10_000_000
+}
+
+fn default_use_trash() -> bool {
+    true
+}
+
+fn default_chords() -> HashMap<String, String> {
+    HashMap::from([
+        ("g/".to_string(), "cd /".to_string()),
+        ("g~".to_string(), "cd ~".to_string()),
+    ])
+}
+
+fn default_show_preview() -> bool {
+    true
+}
+
+fn default_preview_ratio() -> f32 {
+    0.5
+}
+
+/// Terminals narrower than this don't get a preview pane.
+const MIN_WIDTH_FOR_PREVIEW: usize = 60;
+/// Terminals narrower than this only show the active pane in dual pane mode.
+const MIN_WIDTH_FOR_DUAL_PANE: usize = 40;
+/// Terminals shorter than this don't get a preview pane below the listing.
+const MIN_HEIGHT_FOR_PREVIEW: usize = 16;
+/// How long the cursor has to rest on a file before its preview is generated.
+const PREVIEW_DELAY: Duration = Duration::from_millis(40);
+/// How often to check for finished previews while waiting on them.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Most completion candidates shown at once.
+const MAX_MENU_HEIGHT: usize = 10;
+/// How many status messages are kept for `:messages`.
+const MESSAGE_LOG_CAPACITY: usize = 200;
+/// Columns next to the names are left out rather than leaving less room than
+/// this for the names.
+const MIN_NAME_WIDTH: usize = 16;
+/// Spaces before each column next to the names.
+const COLUMN_GAP: usize = 2;
+
+fn default_age_heat_colors() -> [[u8; 3]; 2] {
+    [[255, 170, 60], [90, 90, 90]]
+}
+fn rgb(color: [u8; 3]) -> Color {
+    Color::Rgb {
+        r: color[0],
+        g: color[1],
+        b: color[2],
+    }
+}
+
+/// Formats a number of bytes in a human readable way, like `4.2G`.
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "K", "M", "G", "T", "P"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", bytes, UNITS[0])
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Compiles `pattern`, keeping only the last line of the error, which says
+/// what's wrong, so it fits in the status bar.
+fn parse_regex(pattern: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(pattern).map_err(|error| {
+        let error = error.to_string();
+        let reason = error.lines().last().unwrap_or_default();
+        format!(
+            "invalid regex: {}",
+            reason.strip_prefix("error: ").unwrap_or(reason)
+        )
+    })
+}
+
+#[cfg(windows)]
+fn has_hidden_attribute(entry: &std::fs::DirEntry) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    entry
+        .metadata()
+        .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_entry: &std::fs::DirEntry) -> bool {
+    false
 }
 
 fn is_valid_utf8(path: &PathBuf) -> io::Result<bool> {
@@ -352,9 +3053,11 @@ fn append_to_path(p: PathBuf, s: &str) -> PathBuf {
     p.into()
 }
 
-fn get_config() -> Result<Config, Box<dyn std::error::Error>> {
+/// Loads the config, with the fields of `profile` applied over it if one is
+/// given, along with the file its bookmarks are saved in.
+fn get_config(profile: Option<&str>) -> Result<(Config, PathBuf), Box<dyn std::error::Error>> {
     let base_config_directory =
-        config_dir().ok_or(Error::other("Couldn't get config directory"))?;
+        shared::config_dir().ok_or(Error::other("Couldn't get config directory"))?;
 
     if !Path::exists(&base_config_directory) {
         Err(Error::other("Base config directory doesn't exist"))?;
@@ -366,24 +3069,121 @@ fn get_config() -> Result<Config, Box<dyn std::error::Error>> {
         std::fs::create_dir(&config_directory)?;
     }
 
-    let config_file_path = append_to_path(config_directory, "/config.json");
+    let config_file_path = append_to_path(config_directory.clone(), "/config.json");
 
     if Path::exists(&config_file_path) {
-        return Ok(serde_json::from_str(&std::fs::read_to_string(
-            &config_file_path,
-        )?)?);
+        let contents = std::fs::read_to_string(&config_file_path)?;
+        let mut config: serde_json::Value = serde_json::from_str(&contents)?;
+        let defaults = serde_json::to_value(Config::default_config())?;
+        if migration::migrate(&mut config, &defaults) {
+            // keep the old file around in case the upgrade went wrong
+            std::fs::write(append_to_path(config_file_path.clone(), ".bak"), contents)?;
+            std::fs::write(&config_file_path, serde_json::to_string(&config)?)?;
+        }
+        let mut bookmarks_file = config_file_path;
+        if let Some(profile) = profile {
+            if profile::apply(&mut config, &config_directory, profile)? {
+                bookmarks_file = profile::path(&config_directory, profile)?;
+            }
+        }
+        return Ok((serde_json::from_value(config)?, bookmarks_file));
     }
 
-    let default_config = Config::default_config();
-    std::fs::write(&config_file_path, serde_json::to_string(&default_config)?)?;
+    let mut config = Config::default_config();
+    if io::stdin().is_terminal() {
+        config = setup::run(config)?;
+    }
+    std::fs::write(&config_file_path, serde_json::to_string(&config)?)?;
 
-    Ok(default_config)
+    let mut bookmarks_file = config_file_path;
+    if let Some(profile) = profile {
+        let mut value = serde_json::to_value(config)?;
+        if profile::apply(&mut value, &config_directory, profile)? {
+            bookmarks_file = profile::path(&config_directory, profile)?;
+        }
+        config = serde_json::from_value(value)?;
+    }
+    Ok((config, bookmarks_file))
 }
 
 fn main() {
-    let cwd = current_dir().unwrap();
-    let config = get_config().expect("Couldn't load config!");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args == ["config", "schema"] {
+        let defaults = serde_json::to_value(Config::default_config()).unwrap();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&schema::schema(&defaults)).unwrap()
+        );
+        return;
+    }
+
+    let mut options = match cli::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    if let Some(message) = options.remote {
+        if let Err(error) = remote::send(&message) {
+            eprintln!("fee: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let cwd = match &options.start {
+        Some(start) => {
+            // a link to a file is started next to, rather than followed
+            let resolved = std::path::absolute(start)
+                .map(|start| fileops::resolve_directories(&start))
+                .and_then(|start| start.metadata().map(|_| start));
+            let start = match resolved {
+                Ok(start) => start,
+                Err(error) => {
+                    eprintln!("fee: {}: {}", start.display(), error);
+                    std::process::exit(1);
+                }
+            };
+            // a file is started next to, with it selected
+            if start.is_dir() {
+                start.canonicalize().unwrap_or(start)
+            } else {
+                let parent = start.parent().unwrap_or(&start).to_path_buf();
+                options.select_file.get_or_insert(start);
+                parent
+            }
+        }
+        None => current_dir().unwrap(),
+    };
+    let (config, bookmarks_file) = match get_config(options.profile.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(error) if options.profile.is_some() => {
+            eprintln!("fee: {}", error);
+            std::process::exit(1);
+        }
+        Err(error) => panic!("Couldn't load config! {}", error),
+    };
 
-    let mut fee = Fee::new(cwd, config);
+    let dual_pane = config.dual_pane;
+    let mut events: Box<dyn Events> = Box::new(backend::TerminalEvents);
+    if !options.keys.is_empty() {
+        events = Box::new(replay::Replay::new(options.keys, events));
+    }
+    let mut fee = Fee::new(cwd, config, Box::new(backend::Terminal::new()), events);
+    if dual_pane {
+        if let Err(error) = fee.toggle_dual_pane() {
+            fee.show_message(format!("couldn't open the other pane: {}", error));
+        }
+    }
+    fee.bookmarks_file = Some(bookmarks_file);
+    fee.choose_file = options.choose_file;
+    fee.choose_dir = options.choose_dir;
+    fee.choose_extensions = options.choose_extensions;
+    if let Some(select_file) = options.select_file {
+        if let Ok(path) = std::path::absolute(select_file) {
+            fee.jump_to(&fileops::resolve_directories(&path)).unwrap();
+        }
+    }
     fee.listen().unwrap();
 }
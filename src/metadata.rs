@@ -0,0 +1,109 @@
+//! Metadata of listed items, loaded in batches on a background thread as they
+//! scroll into view, so even huge directories show up right away and the
+//! details fill in as they arrive.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::SystemTime,
+};
+
+use crate::properties;
+
+/// How many items are loaded before sending them back.
+const BATCH_SIZE: usize = 256;
+
+pub struct Metadata {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub permissions: String,
+}
+impl Metadata {
+    /// Loads the metadata of `path`, or of the link itself if it's broken.
+    fn load(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path)
+            .or_else(|_| fs::symlink_metadata(path))
+            .ok()?;
+        Some(Metadata {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            permissions: properties::permission_bits(&metadata),
+        })
+    }
+}
+
+type Batch = Vec<(PathBuf, Metadata)>;
+
+pub struct Loader {
+    requests: Sender<(u64, Vec<PathBuf>)>,
+    /// Loaded batches, as the listing they belong to, the batch, and whether it's the last.
+    results: Receiver<(u64, Batch, bool)>,
+    /// Counts up for every listing, so batches of old ones can be told apart.
+    generation: u64,
+    loading: bool,
+}
+impl Loader {
+    pub fn spawn() -> Self {
+        let (requests, pending) = mpsc::channel::<(u64, Vec<PathBuf>)>();
+        let (finished, results) = mpsc::channel();
+        thread::spawn(move || {
+            let mut next = pending.recv().ok();
+            while let Some((generation, paths)) = next.take() {
+                let mut batches = paths.chunks(BATCH_SIZE).peekable();
+                while let Some(batch) = batches.next() {
+                    // a newer listing makes the rest of this one pointless
+                    while let Ok(newer) = pending.try_recv() {
+                        next = Some(newer);
+                    }
+                    if next.is_some() {
+                        break;
+                    }
+                    let loaded = batch
+                        .iter()
+                        .filter_map(|path| Some((path.clone(), Metadata::load(path)?)))
+                        .collect();
+                    let last = batches.peek().is_none();
+                    if finished.send((generation, loaded, last)).is_err() {
+                        return;
+                    }
+                }
+                if next.is_none() {
+                    next = pending.recv().ok();
+                }
+            }
+        });
+        Loader {
+            requests,
+            results,
+            generation: 0,
+            loading: false,
+        }
+    }
+    /// Starts loading the metadata of `paths`, abandoning whatever was being loaded.
+    pub fn request(&mut self, paths: Vec<PathBuf>) {
+        self.generation += 1;
+        self.loading = !paths.is_empty();
+        if self.loading {
+            let _ = self.requests.send((self.generation, paths));
+        }
+    }
+    /// Whether batches of the current listing are still on their way.
+    pub fn is_loading(&self) -> bool {
+        self.loading
+    }
+    /// Returns the next loaded batch of the current listing, if there is one yet.
+    pub fn try_receive(&mut self) -> Option<Batch> {
+        while let Ok((generation, batch, last)) = self.results.try_recv() {
+            if generation != self.generation {
+                continue;
+            }
+            if last {
+                self.loading = false;
+            }
+            return Some(batch);
+        }
+        None
+    }
+}
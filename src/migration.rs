@@ -0,0 +1,84 @@
+//! Upgrading config files written by older versions of fee, so that changes to
+//! the config format don't make them fail to load.
+
+use serde_json::{Map, Value};
+
+/// Version of the config format written by this build.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Fields that have been renamed, as the version that renamed them, and their
+/// old and new names. Nested fields are separated by dots.
+const RENAMED_FIELDS: &[(u32, &str, &str)] = &[];
+
+/// Upgrades `config` to the current version, moving renamed fields and filling in
+/// missing ones from `defaults`. Returns whether it was changed.
+pub fn migrate(config: &mut Value, defaults: &Value) -> bool {
+    let Some(fields) = config.as_object_mut() else {
+        return false;
+    };
+    let version = fields.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version >= CONFIG_VERSION {
+        // written by this or a newer version, which we can't know how to upgrade
+        return false;
+    }
+    for (renamed_in, old, new) in RENAMED_FIELDS {
+        if *renamed_in > version {
+            rename(fields, old, new);
+        }
+    }
+    fill_missing(config, defaults);
+    config["version"] = Value::from(CONFIG_VERSION);
+    true
+}
+
+fn rename(fields: &mut Map<String, Value>, old: &str, new: &str) {
+    let (Some((old_parent, old_name)), Some((new_parent, new_name))) =
+        (split_path(old), split_path(new))
+    else {
+        return;
+    };
+    let Some(value) = object_at(fields, &old_parent).and_then(|parent| parent.remove(old_name))
+    else {
+        return;
+    };
+    if let Some(parent) = object_at(fields, &new_parent) {
+        parent.entry(new_name).or_insert(value);
+    }
+}
+
+/// Splits a dotted field path into its parents and the name of the field itself.
+fn split_path(path: &str) -> Option<(Vec<&str>, &str)> {
+    let mut parts: Vec<&str> = path.split('.').collect();
+    let name = parts.pop()?;
+    Some((parts, name))
+}
+
+/// Returns the object at `path`, creating any missing objects along the way.
+fn object_at<'a>(
+    fields: &'a mut Map<String, Value>,
+    path: &[&str],
+) -> Option<&'a mut Map<String, Value>> {
+    let Some((first, rest)) = path.split_first() else {
+        return Some(fields);
+    };
+    let child = fields
+        .entry(first.to_string())
+        .or_insert_with(|| Value::Object(Map::new()));
+    object_at(child.as_object_mut()?, rest)
+}
+
+/// Copies every field of `defaults` that `config` is missing, recursing into objects.
+fn fill_missing(config: &mut Value, defaults: &Value) {
+    let (Some(fields), Some(default_fields)) = (config.as_object_mut(), defaults.as_object())
+    else {
+        return;
+    };
+    for (name, default) in default_fields {
+        match fields.get_mut(name) {
+            Some(value) => fill_missing(value, default),
+            None => {
+                fields.insert(name.to_owned(), default.clone());
+            }
+        }
+    }
+}
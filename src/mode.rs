@@ -0,0 +1,146 @@
+//! What fee is in the middle of: typing in a prompt, picking from a menu,
+//! waiting for a confirmation, or nothing in particular.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    attributes, bookmarks, clipboard, command, drives, filter, finder, keymap::Action, openwith,
+    preview::Preview, prompt::Prompt, rename, search::SearchKind,
+};
+
+pub enum Mode {
+    Normal,
+    Search(SearchKind, Prompt),
+    /// Typing a filter, with only the entries matching it listed.
+    Filter(filter::Filter),
+    Command(Prompt, Option<command::Menu>),
+    QuickLook {
+        title: String,
+        preview: Preview,
+        scroll: usize,
+    },
+    Drives {
+        drives: Vec<drives::Drive>,
+        selection: usize,
+    },
+    BatchRename(rename::BatchRename),
+    OpenWith(openwith::Menu),
+    /// A paste waiting for a conflict to be resolved.
+    Paste(clipboard::Paste),
+    /// An action waiting to be confirmed with y, or cancelled.
+    Confirm(Confirmation),
+    /// Renaming the selected item, typing its new name over it.
+    Rename(PathBuf, Prompt),
+    /// Typing the name of a new file or directory.
+    Create {
+        directory: bool,
+        prompt: Prompt,
+        /// What the new file is filled with, pasted from the clipboard.
+        contents: Option<Vec<u8>>,
+    },
+    Bookmarks(bookmarks::Picker),
+    /// Fuzzy finding a file anywhere below the current directory.
+    Finder(finder::Finder),
+    /// Changing the permissions of the marked items.
+    Attributes(attributes::Batch),
+    /// Choosing what to do with the selected item.
+    Context {
+        actions: Vec<Action>,
+        selection: usize,
+    },
+}
+
+pub enum Confirmation {
+    /// Deleting the paths, or moving them to the trash unless permanently.
+    Delete {
+        paths: Vec<PathBuf>,
+        permanently: bool,
+    },
+    /// Packing up the changes made in the innermost archive, as summarized,
+    /// before going on to the destination, if any.
+    Repack(PathBuf, String, Option<PathBuf>),
+    /// Doing the action, which wasn't permitted for the reason, by running the
+    /// command with sudo.
+    Elevate { reason: String, elevated: Elevated },
+}
+
+/// A command doing what fee wasn't permitted to, run with sudo if the user
+/// agrees, and what it does, like `delete 'notes.txt'`.
+pub struct Elevated {
+    pub action: String,
+    pub command: Vec<OsString>,
+}
+impl Elevated {
+    /// Runs `program`, with its options, on `paths`.
+    pub fn new<'a>(
+        action: String,
+        program: &[&str],
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> Self {
+        let mut command: Vec<OsString> = program.iter().map(OsString::from).collect();
+        command.push("--".into());
+        command.extend(paths.into_iter().map(|path| path.as_os_str().to_owned()));
+        Elevated { action, command }
+    }
+}
+
+/// Describes the items at `paths`, like `'notes.txt'` or `3 items`.
+pub fn describe_items(paths: &[PathBuf]) -> String {
+    match paths {
+        [path] => format!(
+            "'{}'",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        paths => format!("{} items", paths.len()),
+    }
+}
+
+/// Describes deleting the items at `paths`, like `'notes' and everything in it`.
+pub fn describe_deletion(paths: &[PathBuf]) -> String {
+    let directories = paths.iter().filter(|path| path.is_dir()).count();
+    let contents = match (directories, paths.len()) {
+        (0, _) => String::new(),
+        (_, 1) => " and everything in it".to_string(),
+        (1, _) => ", including everything in 1 directory".to_string(),
+        (directories, _) => {
+            format!(", including everything in {} directories", directories)
+        }
+    };
+    format!("{}{}", describe_items(paths), contents)
+}
+impl Confirmation {
+    pub fn question(&self) -> String {
+        match self {
+            Confirmation::Delete { paths, permanently } => {
+                if *permanently {
+                    format!("permanently delete {}? (y/n)", describe_deletion(paths))
+                } else {
+                    format!("move {} to the trash? (y/n)", describe_deletion(paths))
+                }
+            }
+            Confirmation::Repack(archive, changes, _) => format!(
+                "save changes to '{}' ({})? (y: save, n: discard, esc: stay)",
+                archive.file_name().unwrap_or_default().to_string_lossy(),
+                changes
+            ),
+            Confirmation::Elevate { reason, elevated } => {
+                // the program and its options, without the paths after `--`
+                let program: Vec<String> = elevated
+                    .command
+                    .iter()
+                    .take_while(|part| *part != "--")
+                    .map(|part| part.to_string_lossy().to_string())
+                    .collect();
+                format!(
+                    "{}. {} with sudo {}? (y/n)",
+                    reason,
+                    elevated.action,
+                    program.join(" ")
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Going back and forward through the directories visited, like in a web
+//! browser, with alt+left and alt+right or the `:back` and `:forward` commands.
+//! Each directory is returned to with the item that was selected in it.
+
+use std::path::{Path, PathBuf};
+
+/// How many directories can be gone back to.
+const MAX_VISITS: usize = 100;
+
+pub struct Visit {
+    pub directory: PathBuf,
+    pub selected: Option<PathBuf>,
+}
+
+#[derive(Default)]
+pub struct Navigation {
+    back: Vec<Visit>,
+    forward: Vec<Visit>,
+    /// Where going back or forward last led, so arriving there isn't recorded as
+    /// a new visit.
+    arrived: Option<PathBuf>,
+}
+impl Navigation {
+    /// Records leaving `from` for `to`, which forgets the directories that could
+    /// be gone forward to.
+    pub fn visit(&mut self, from: Visit, to: &Path) {
+        if self.arrived.take().is_some_and(|arrived| arrived == to) {
+            return;
+        }
+        if self.back.len() == MAX_VISITS {
+            self.back.remove(0);
+        }
+        self.back.push(from);
+        self.forward.clear();
+    }
+    /// The directory before `current`, if there is one.
+    pub fn back(&mut self, current: Visit) -> Option<Visit> {
+        let visit = self.back.pop()?;
+        self.forward.push(current);
+        self.arrived = Some(visit.directory.clone());
+        Some(visit)
+    }
+    /// The directory gone back from to `current`, if there is one.
+    pub fn forward(&mut self, current: Visit) -> Option<Visit> {
+        let visit = self.forward.pop()?;
+        self.back.push(current);
+        self.arrived = Some(visit.directory.clone());
+        Some(visit)
+    }
+}
@@ -0,0 +1,114 @@
+//! The open with menu, for opening a file with something other than the default
+//! editor. The last command used for each extension is remembered during the
+//! session and offered first, so reviewing a batch of files takes one key each.
+//! After it come the programs associated with the file in the config, by its
+//! extension (`"png"`) or a glob matching its name (`"*.tar.gz"`).
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use crate::{ignore, prompt::Prompt};
+
+pub struct Opener {
+    pub label: String,
+    pub command: Vec<String>,
+}
+
+pub struct Menu {
+    pub path: PathBuf,
+    pub openers: Vec<Opener>,
+    pub selection: usize,
+    /// A command typed in instead of choosing one of the openers.
+    pub prompt: Prompt,
+}
+impl Menu {
+    /// Creates the menu for `path`, offering its extension's last opener first.
+    pub fn new(path: PathBuf, commands: &[&Vec<String>], last_openers: &LastOpeners) -> Self {
+        let mut openers = vec![];
+        if let Some(command) = last_openers.get(&path) {
+            openers.push(Opener {
+                label: format!("repeat: {}", command.join(" ")),
+                command: command.clone(),
+            });
+        }
+        for command in commands {
+            if command.is_empty() || openers.iter().any(|opener| opener.command == **command) {
+                continue;
+            }
+            openers.push(Opener {
+                label: command.join(" "),
+                command: command.to_vec(),
+            });
+        }
+        Menu {
+            path,
+            openers,
+            selection: 0,
+            prompt: Prompt::default(),
+        }
+    }
+    /// Returns the typed command, or else the selected one.
+    pub fn chosen(&self) -> Option<Vec<String>> {
+        let typed = self.prompt.text();
+        if typed.trim().is_empty() {
+            return Some(self.openers.get(self.selection)?.command.clone());
+        }
+        let mut command: Vec<String> = typed.split_whitespace().map(str::to_string).collect();
+        if !command.iter().any(|part| part == "$f") {
+            command.push("$f".to_string());
+        }
+        Some(command)
+    }
+}
+
+/// The last command each extension was opened with.
+#[derive(Default)]
+pub struct LastOpeners {
+    commands: HashMap<String, Vec<String>>,
+}
+impl LastOpeners {
+    pub fn get(&self, path: &Path) -> Option<&Vec<String>> {
+        self.commands.get(&extension(path))
+    }
+    pub fn remember(&mut self, path: &Path, command: Vec<String>) {
+        self.commands.insert(extension(path), command);
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase()
+}
+
+/// The commands associated with `path`, the most specific (longest) pattern
+/// first.
+pub fn associated<'a>(
+    associations: &'a BTreeMap<String, Vec<String>>,
+    path: &Path,
+) -> Vec<&'a Vec<String>> {
+    let name: Vec<char> = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase()
+        .chars()
+        .collect();
+    let mut matching: Vec<(&String, &Vec<String>)> = associations
+        .iter()
+        .filter(|(pattern, command)| {
+            let pattern = pattern.to_lowercase();
+            !command.is_empty()
+                && if pattern.contains(['*', '?', '[']) {
+                    ignore::glob_matches(&pattern.chars().collect::<Vec<char>>(), &name)
+                } else {
+                    pattern.trim_start_matches('.') == extension(path)
+                }
+        })
+        .collect();
+    matching.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.len()));
+    matching.into_iter().map(|(_, command)| command).collect()
+}
@@ -0,0 +1,671 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    rc::Rc,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crossterm::style::Color;
+use serde_json::Value;
+
+use crate::{archive, graphics::Pixmap, highlight, previewserver, shared, structured, Config};
+
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "mp3", "flac", "wav", "ogg", "opus", "m4a", "aac", "wma", "mp4", "mkv", "webm", "avi", "mov",
+    "wmv", "flv", "m4v", "mpg", "mpeg",
+];
+const HEX_BYTES_PER_LINE: usize = 8;
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "webp", "tif", "tiff", "ico", "avif",
+];
+const LINE_NUMBER_COLOR: Color = Color::DarkGrey;
+const NOTICE_COLOR: Color = Color::DarkGrey;
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "mkv", "webm", "avi", "mov", "wmv", "flv", "m4v", "mpg", "mpeg",
+];
+
+/// A line of text made up of differently colored spans.
+#[derive(Default, Clone)]
+pub struct Line {
+    pub spans: Vec<(String, Color)>,
+}
+impl Line {
+    pub fn plain(text: &str) -> Self {
+        Line {
+            spans: vec![(text.to_string(), Color::Reset)],
+        }
+    }
+    pub fn push(&mut self, text: &str, color: Color) {
+        if !text.is_empty() {
+            self.spans.push((text.to_string(), color));
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Preview {
+    pub lines: Vec<Line>,
+    /// A PNG to draw below the lines, if the terminal supports graphics.
+    pub image: Option<PathBuf>,
+    /// A decoded image to draw below the lines, which is drawn with blocks if
+    /// the terminal doesn't support graphics.
+    pub pixmap: Option<Pixmap>,
+    /// Width of the line number column at the start of each line, if any.
+    pub gutter: usize,
+}
+impl Preview {
+    pub fn text(lines: Vec<String>) -> Self {
+        Preview {
+            lines: lines.iter().map(|line| Line::plain(line)).collect(),
+            ..Default::default()
+        }
+    }
+    fn numbered(lines: Vec<Line>) -> Self {
+        let digits = lines.len().to_string().len();
+        Preview {
+            lines: lines
+                .into_iter()
+                .enumerate()
+                .map(|(index, text)| {
+                    let mut line = Line::default();
+                    line.push(&format!("{:>digits$} ", index + 1), LINE_NUMBER_COLOR);
+                    line.spans.extend(text.spans);
+                    line
+                })
+                .collect(),
+            gutter: digits + 1,
+            ..Default::default()
+        }
+    }
+    /// Returns the lines to show in a pane `width` characters wide.
+    pub fn layout(&self, width: usize, wrap: bool) -> Vec<Line> {
+        if wrap {
+            self.wrapped(width)
+        } else {
+            self.lines.clone()
+        }
+    }
+    /// Breaks lines longer than `width` into several, indenting the continuations
+    /// past the line numbers.
+    fn wrapped(&self, width: usize) -> Vec<Line> {
+        let mut wrapped = vec![];
+        for line in &self.lines {
+            let mut current = Line::default();
+            let mut length = 0;
+            for (text, color) in &line.spans {
+                for char in text.chars() {
+                    if length >= width && width > self.gutter {
+                        wrapped.push(current);
+                        current = Line::plain(&" ".repeat(self.gutter));
+                        length = self.gutter;
+                    }
+                    match current.spans.last_mut() {
+                        Some((text, last_color)) if last_color == color => text.push(char),
+                        _ => current.push(&char.to_string(), *color),
+                    }
+                    length += 1;
+                }
+            }
+            wrapped.push(current);
+        }
+        wrapped
+    }
+}
+
+/// How many previews are kept around by [`Cache`].
+const CACHE_CAPACITY: usize = 64;
+
+/// Identifies a preview of a specific version of a file.
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub struct CacheKey {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+    max_lines: usize,
+    /// Whether hidden entries are listed, in previews of directories.
+    show_hidden: bool,
+}
+impl CacheKey {
+    pub fn new(path: &Path, max_lines: usize, show_hidden: bool) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(CacheKey {
+            path: path.to_path_buf(),
+            modified: metadata.modified()?,
+            size: metadata.len(),
+            max_lines,
+            show_hidden: show_hidden && metadata.is_dir(),
+        })
+    }
+}
+
+/// Remembers recently generated previews, so moving the cursor back and forth
+/// doesn't regenerate them. Entries are keyed by modification time, so edited
+/// files get a fresh preview.
+#[derive(Default)]
+pub struct Cache {
+    previews: HashMap<CacheKey, Rc<Preview>>,
+    order: VecDeque<CacheKey>,
+}
+impl Cache {
+    pub fn get(&self, key: &CacheKey) -> Option<Rc<Preview>> {
+        self.previews.get(key).cloned()
+    }
+    pub fn insert(&mut self, key: CacheKey, preview: Preview) {
+        if self.order.len() >= CACHE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.previews.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.previews.insert(key, Rc::new(preview));
+    }
+}
+
+/// Generates previews on a background thread, so slow previews never block input.
+pub struct Worker {
+    requests: Sender<(CacheKey, bool)>,
+    results: Receiver<(CacheKey, Preview)>,
+}
+impl Worker {
+    pub fn spawn(config: Config) -> Self {
+        let (requests, pending) = mpsc::channel::<(CacheKey, bool)>();
+        let (finished, results) = mpsc::channel();
+        thread::spawn(move || {
+            let mut servers = previewserver::Pool::new(&config.preview_servers);
+            while let Ok(mut request) = pending.recv() {
+                // only the newest request matters, older ones were scrolled past
+                while let Ok(newer) = pending.try_recv() {
+                    request = newer;
+                }
+                let (key, images) = request;
+                let preview = if key.path.is_dir() {
+                    list_directory(&key.path, key.max_lines, key.show_hidden, &config)
+                } else if let Some(lines) = serve(&mut servers, &key.path, key.max_lines, &config) {
+                    Preview::text(lines)
+                } else {
+                    generate(&key.path, key.max_lines, &config, images).unwrap_or_default()
+                };
+                if finished.send((key, preview)).is_err() {
+                    break;
+                }
+            }
+        });
+        Worker { requests, results }
+    }
+    /// Queues generating a preview for `key`. Images are only generated if `images` is set.
+    pub fn request(&self, key: CacheKey, images: bool) {
+        let _ = self.requests.send((key, images));
+    }
+    pub fn try_receive(&self) -> Option<(CacheKey, Preview)> {
+        self.results.try_recv().ok()
+    }
+}
+
+/// Previews `path` with one of the `preview_servers`, if one previews files like it.
+fn serve(
+    servers: &mut previewserver::Pool,
+    path: &Path,
+    max_lines: usize,
+    config: &Config,
+) -> Option<Vec<String>> {
+    if !path.is_file() || is_disabled(path, config) {
+        return None;
+    }
+    let timeout = Duration::from_millis(config.preview_timeout_ms);
+    let lines = servers.preview(path, max_lines, timeout)?;
+    Some(lines.iter().map(|line| sanitize(line)).collect())
+}
+
+/// Generates a preview of the file at `path` with up to `max_lines` lines.
+/// Images, such as video thumbnails, are only generated if `images` is set.
+pub fn generate(
+    path: &Path,
+    max_lines: usize,
+    config: &Config,
+    images: bool,
+) -> io::Result<Preview> {
+    if !path.is_file() {
+        return Ok(Preview::default());
+    }
+    if is_disabled(path, config) {
+        let mut notice = Line::default();
+        notice.push("<previews of this file are disabled>", NOTICE_COLOR);
+        return Ok(Preview {
+            lines: vec![notice],
+            ..Default::default()
+        });
+    }
+    if let Some(mut command) = archive::decompress_command(path) {
+        return preview_compressed(&mut command, path, max_lines, config, images);
+    }
+    if has_extension(path, &["pdf"]) {
+        return Ok(Preview::text(run_helper(
+            &config.pdf_preview_command,
+            path,
+            max_lines,
+            config,
+        )));
+    }
+    if has_extension(path, MEDIA_EXTENSIONS) {
+        let mut preview = Preview::text(media_info(&config.media_probe_command, path, config));
+        if images && has_extension(path, VIDEO_EXTENSIONS) {
+            preview.image = thumbnail(&config.video_thumbnail_command, path, "png", config);
+        }
+        return Ok(preview);
+    }
+    if config.image_previews && has_extension(path, IMAGE_EXTENSIONS) {
+        let pixmap = thumbnail(&config.image_thumbnail_command, path, "ppm", config)
+            .and_then(|thumbnail| fs::read(thumbnail).ok())
+            .and_then(|bytes| Pixmap::read_ppm(&bytes));
+        if pixmap.is_none() {
+            let mut notice = Line::default();
+            notice.push(
+                "<couldn't make a thumbnail, see image_thumbnail_command>",
+                NOTICE_COLOR,
+            );
+            return Ok(Preview {
+                lines: vec![notice],
+                ..Default::default()
+            });
+        }
+        return Ok(Preview {
+            pixmap,
+            ..Default::default()
+        });
+    }
+
+    let mut bytes = vec![];
+    fs::File::open(path)?
+        .take(config.preview_max_bytes)
+        .read_to_end(&mut bytes)?;
+    let truncated = fs::metadata(path)?.len() > config.preview_max_bytes;
+    let text = match std::str::from_utf8(&bytes) {
+        Ok(text) => text,
+        // the limit may have cut a character in half
+        Err(error) if truncated && error.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..error.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return Ok(hex_dump(&bytes, max_lines)),
+    };
+
+    if !truncated && has_extension(path, &["json", "yaml", "yml", "toml"]) {
+        let text = sanitize_lines(text);
+        let fold = config.preview_fold_arrays;
+        let lines = if has_extension(path, &["json"]) {
+            structured::json(&text, fold)
+        } else if has_extension(path, &["toml"]) {
+            Some(structured::toml(&text))
+        } else {
+            Some(structured::yaml(&text, fold))
+        };
+        if let Some(mut lines) = lines {
+            lines.truncate(max_lines);
+            return Ok(Preview {
+                lines,
+                ..Default::default()
+            });
+        }
+    }
+    let lines: Vec<String> = text.lines().take(max_lines).map(sanitize).collect();
+    let shown_all = lines.len() < max_lines;
+    let highlighted = config
+        .preview_highlight
+        .then(|| highlight::highlight(path, &lines, config.theme.syntax_theme))
+        .flatten();
+    let lines = highlighted.unwrap_or_else(|| lines.iter().map(|line| Line::plain(line)).collect());
+    let mut preview = if config.preview_line_numbers {
+        Preview::numbered(lines)
+    } else {
+        Preview {
+            lines,
+            ..Default::default()
+        }
+    };
+    if truncated && shown_all {
+        let mut notice = Line::default();
+        notice.push(
+            &format!(
+                "<truncated, only the first {} bytes are previewed>",
+                config.preview_max_bytes
+            ),
+            NOTICE_COLOR,
+        );
+        preview.lines.push(notice);
+    }
+    Ok(preview)
+}
+
+/// Previews the start of the compressed file at `path` like the file it
+/// decompresses to, decompressing it with `command`.
+fn preview_compressed(
+    command: &mut Command,
+    path: &Path,
+    max_lines: usize,
+    config: &Config,
+    images: bool,
+) -> io::Result<Preview> {
+    let contents = match run_with_limits(command, config) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::TimedOut => {
+            return Ok(Preview::text(vec![
+                "<decompressing took too long>".to_string()
+            ]));
+        }
+        Err(error) => {
+            return Ok(Preview::text(vec![format!(
+                "<couldn't decompress: {}>",
+                error
+            )]));
+        }
+    };
+    let copy = archive::TempFile::with_decompressed(path, &contents)?;
+    generate(&copy.path, max_lines, config, images)
+}
+
+/// Shows the first `max_lines` rows of `bytes` as offsets, hex and the
+/// printable characters, like `hexdump -C` but narrow enough for the pane.
+fn hex_dump(bytes: &[u8], max_lines: usize) -> Preview {
+    let lines = bytes
+        .chunks(HEX_BYTES_PER_LINE)
+        .take(max_lines)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let text: String = chunk
+                .iter()
+                .map(|byte| {
+                    if byte.is_ascii_graphic() || *byte == b' ' {
+                        *byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            let mut line = Line::default();
+            line.push(
+                &format!("{:08x} ", index * HEX_BYTES_PER_LINE),
+                LINE_NUMBER_COLOR,
+            );
+            line.push(
+                &format!(
+                    "{:width$} ",
+                    hex.join(" "),
+                    width = HEX_BYTES_PER_LINE * 3 - 1
+                ),
+                Color::Reset,
+            );
+            line.push(&text, NOTICE_COLOR);
+            line
+        })
+        .collect();
+    Preview {
+        lines,
+        gutter: 9,
+        ..Default::default()
+    }
+}
+
+/// Lists the first `max_lines` entries of the directory at `path`, directories
+/// first, so it can be peeked into without entering it.
+fn list_directory(path: &Path, max_lines: usize, show_hidden: bool, config: &Config) -> Preview {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(error) => {
+            let mut notice = Line::default();
+            notice.push(&format!("<can't list directory: {}>", error), NOTICE_COLOR);
+            return Preview {
+                lines: vec![notice],
+                ..Default::default()
+            };
+        }
+    };
+    let mut entries: Vec<(bool, String)> = entries
+        .flatten()
+        .map(|entry| {
+            let name = sanitize(&entry.file_name().to_string_lossy());
+            (!entry.path().is_dir(), name)
+        })
+        .filter(|(_, name)| show_hidden || !name.starts_with('.'))
+        .collect();
+    entries.sort_by_key(|(is_file, name)| (*is_file, name.to_lowercase()));
+
+    let mut lines = vec![];
+    for (is_file, name) in entries.iter().take(max_lines) {
+        let mut line = Line::default();
+        if *is_file {
+            line.push(name, crate::rgb(config.file_color));
+        } else {
+            line.push(&format!("{}/", name), crate::rgb(config.dir_color));
+        }
+        lines.push(line);
+    }
+    let mut notice = Line::default();
+    if entries.is_empty() {
+        notice.push("<empty directory>", NOTICE_COLOR);
+    } else if entries.len() > max_lines {
+        // the last line says how many more there are instead
+        lines.pop();
+        let more = entries.len() - lines.len();
+        notice.push(&format!("<{} more>", more), NOTICE_COLOR);
+    }
+    if !notice.spans.is_empty() {
+        lines.push(notice);
+    }
+    Preview {
+        lines,
+        ..Default::default()
+    }
+}
+
+/// Returns a thumbnail of `path` in the format of `extension`, like a PNG frame of
+/// a video, making it with `command` unless a thumbnail of the same file and
+/// modification time is already cached.
+fn thumbnail(command: &[String], path: &Path, extension: &str, config: &Config) -> Option<PathBuf> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let modified = modified.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    let key = fnv1a(format!("{}:{}", path.display(), modified).as_bytes());
+
+    let directory = shared::cache_dir()?.join("fee").join("thumbnails");
+    let thumbnail = directory.join(format!("{:016x}.{}", key, extension));
+    if thumbnail.exists() {
+        return Some(thumbnail);
+    }
+    fs::create_dir_all(&directory).ok()?;
+
+    let (executable, args) = command.split_first()?;
+    let args = args.iter().map(|arg| match arg.as_str() {
+        "$f" => path.as_os_str(),
+        "$o" => thumbnail.as_os_str(),
+        _ => arg.as_ref(),
+    });
+    let mut command = Command::new(executable);
+    command.args(args);
+    if run_with_limits(&mut command, config).is_err() {
+        // don't leave a half-written thumbnail in the cache
+        let _ = fs::remove_file(&thumbnail);
+        return None;
+    }
+    thumbnail.exists().then_some(thumbnail)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Whether previews of `path` are turned off by `preview_disabled_extensions` or
+/// `preview_disabled_above`, so the file is never read.
+fn is_disabled(path: &Path, config: &Config) -> bool {
+    let extension = path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    if config
+        .preview_disabled_extensions
+        .iter()
+        .any(|disabled| disabled.trim_start_matches('.').to_lowercase() == extension)
+    {
+        return true;
+    }
+    config
+        .preview_disabled_above
+        .is_some_and(|limit| fs::metadata(path).is_ok_and(|metadata| metadata.len() > limit))
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extensions.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Runs an external previewer, substituting `$f` with the path, and returns its output.
+fn run_helper(command: &[String], path: &Path, max_lines: usize, config: &Config) -> Vec<String> {
+    match helper_output(command, path, config) {
+        Ok(output) => output.lines().take(max_lines).map(sanitize).collect(),
+        Err(message) => vec![message],
+    }
+}
+
+/// Runs `command` on `path`, returning its stdout or a message describing why it couldn't run.
+fn helper_output(command: &[String], path: &Path, config: &Config) -> Result<String, String> {
+    let Some((executable, args)) = command.split_first() else {
+        return Ok(String::new());
+    };
+    let args = args.iter().map(|arg| {
+        if arg == "$f" {
+            path.as_os_str()
+        } else {
+            arg.as_ref()
+        }
+    });
+    let mut command = Command::new(executable);
+    command.args(args);
+    let output = match run_with_limits(&mut command, config) {
+        Ok(output) => output,
+        Err(error) if error.kind() == io::ErrorKind::TimedOut => {
+            return Err(format!("<{} took too long>", executable))
+        }
+        Err(error) => return Err(format!("<couldn't run {}: {}>", executable, error)),
+    };
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
+
+/// Runs `command`, killing it if it runs longer than the configured preview timeout,
+/// and returns at most the configured maximum number of bytes of its stdout.
+fn run_with_limits(command: &mut Command, config: &Config) -> io::Result<Vec<u8>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // read on another thread, so a previewer filling the pipe can't block us
+    let stdout = child.stdout.take();
+    let max_bytes = config.preview_max_bytes;
+    let (sender, output) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = vec![];
+        if let Some(stdout) = stdout {
+            let mut stdout = stdout.take(max_bytes);
+            let _ = stdout.read_to_end(&mut output);
+            let _ = sender.send(output);
+            // drain the rest so the previewer isn't killed by a closed pipe
+            let _ = io::copy(&mut stdout.into_inner(), &mut io::sink());
+        } else {
+            let _ = sender.send(output);
+        }
+    });
+
+    let deadline = Instant::now() + Duration::from_millis(config.preview_timeout_ms);
+    while child.try_wait()?.is_none() {
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::from(io::ErrorKind::TimedOut));
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    // something the previewer started can keep the pipe open after it's exited,
+    // in which case the reader is left to finish whenever that does
+    output
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))
+}
+
+/// Summarizes the duration, bitrate and streams of an audio/video file, as reported by
+/// an ffprobe-compatible command printing JSON. Output that isn't JSON is shown as is.
+fn media_info(command: &[String], path: &Path, config: &Config) -> Vec<String> {
+    let output = match helper_output(command, path, config) {
+        Ok(output) => output,
+        Err(message) => return vec![message],
+    };
+    let Ok(probe) = serde_json::from_str::<Value>(&output) else {
+        return output.lines().map(sanitize).collect();
+    };
+    let mut lines = vec![];
+    let format = &probe["format"];
+    if let Some(name) = format["format_long_name"].as_str() {
+        lines.push(format!("format: {}", name));
+    }
+    if let Some(duration) = as_number(&format["duration"]) {
+        let seconds = duration as u64;
+        lines.push(format!(
+            "duration: {}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ));
+    }
+    if let Some(bitrate) = as_number(&format["bit_rate"]) {
+        lines.push(format!("bitrate: {} kb/s", (bitrate / 1000.0).round()));
+    }
+    for stream in probe["streams"].as_array().into_iter().flatten() {
+        let kind = stream["codec_type"].as_str().unwrap_or("stream");
+        let mut details = vec![stream["codec_name"]
+            .as_str()
+            .unwrap_or("unknown")
+            .to_string()];
+        if let (Some(width), Some(height)) = (stream["width"].as_u64(), stream["height"].as_u64()) {
+            details.push(format!("{}x{}", width, height));
+        }
+        if let Some(rate) = as_number(&stream["sample_rate"]) {
+            details.push(format!("{} Hz", rate));
+        }
+        if let Some(channels) = stream["channels"].as_u64() {
+            details.push(format!("{} channels", channels));
+        }
+        if let Some(bitrate) = as_number(&stream["bit_rate"]) {
+            details.push(format!("{} kb/s", (bitrate / 1000.0).round()));
+        }
+        lines.push(format!("{}: {}", kind, details.join(", ")));
+    }
+    lines
+}
+
+/// ffprobe reports most numbers as strings, so accept both.
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(string) => string.parse().ok(),
+        _ => value.as_f64(),
+    }
+}
+
+/// Sanitizes every line of `text`, keeping the line breaks.
+fn sanitize_lines(text: &str) -> String {
+    text.lines().map(sanitize).collect::<Vec<_>>().join("\n")
+}
+
+/// Expands tabs and strips control characters, which would otherwise mess up the terminal.
+fn sanitize(line: &str) -> String {
+    line.replace('\t', "    ")
+        .chars()
+        .filter(|char| !char.is_control())
+        .collect()
+}
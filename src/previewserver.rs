@@ -0,0 +1,158 @@
+//! Previewers kept running between previews, so ones slow to start, like
+//! pygments in a Python process, don't pay for it on every move of the
+//! selection. A server is started the first time a file it previews is
+//! selected, and is sent the path of each file to preview on its own line of
+//! stdin. It answers with the preview followed by a line with only a NUL byte.
+//! One that takes too long is stopped, and started again when next needed.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Ends each preview a server writes.
+const END_OF_PREVIEW: &str = "\0";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PreviewServer {
+    /// Extensions of the files previewed, without the dot.
+    pub extensions: Vec<String>,
+    pub command: Vec<String>,
+}
+
+struct Running {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines of stdout, read on another thread.
+    lines: Receiver<String>,
+}
+impl Running {
+    fn start(command: &[String]) -> io::Result<Self> {
+        let Some((executable, args)) = command.split_first() else {
+            return Err(io::Error::other("no command"));
+        };
+        let mut child = Command::new(executable)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        let stdout = child.stdout.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+            let mut line = vec![];
+            while stdout
+                .read_until(b'\n', &mut line)
+                .is_ok_and(|read| read > 0)
+            {
+                let text = String::from_utf8_lossy(&line);
+                let text = text.trim_end_matches(['\n', '\r']).to_string();
+                if sender.send(text).is_err() {
+                    break;
+                }
+                line.clear();
+            }
+        });
+        Ok(Running {
+            child,
+            stdin,
+            lines,
+        })
+    }
+}
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The configured servers, each started when first needed.
+pub struct Pool {
+    servers: Vec<(PreviewServer, Option<Running>)>,
+}
+impl Pool {
+    pub fn new(servers: &[PreviewServer]) -> Self {
+        Pool {
+            servers: servers
+                .iter()
+                .map(|server| (server.clone(), None))
+                .collect(),
+        }
+    }
+    /// Previews `path` with the server for its extension, if there is one,
+    /// returning up to `max_lines` lines or a message describing what went wrong.
+    pub fn preview(
+        &mut self,
+        path: &Path,
+        max_lines: usize,
+        timeout: Duration,
+    ) -> Option<Vec<String>> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        let (server, running) = self.servers.iter_mut().find(|(server, _)| {
+            server
+                .extensions
+                .iter()
+                .any(|wanted| wanted.trim_start_matches('.').to_lowercase() == extension)
+        })?;
+        // a newline would end the path early
+        let path = path.to_str().filter(|path| !path.contains('\n'))?;
+        let name = server.command.first().cloned().unwrap_or_default();
+        if running.is_none() {
+            match Running::start(&server.command) {
+                Ok(started) => *running = Some(started),
+                Err(error) => return Some(vec![format!("<couldn't run {}: {}>", name, error)]),
+            }
+        }
+        let result = request(running.as_mut()?, path, max_lines, timeout);
+        match result {
+            Ok(lines) => Some(lines),
+            Err(error) => {
+                // stopped, to be started again next time
+                *running = None;
+                if error.kind() == io::ErrorKind::TimedOut {
+                    Some(vec![format!("<{} took too long>", name)])
+                } else {
+                    Some(vec![format!("<{} stopped: {}>", name, error)])
+                }
+            }
+        }
+    }
+}
+
+fn request(
+    running: &mut Running,
+    path: &str,
+    max_lines: usize,
+    timeout: Duration,
+) -> io::Result<Vec<String>> {
+    // anything left over from an earlier preview isn't part of this one
+    while running.lines.try_recv().is_ok() {}
+    writeln!(running.stdin, "{}", path)?;
+    running.stdin.flush()?;
+    let deadline = Instant::now() + timeout;
+    let mut lines = vec![];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match running.lines.recv_timeout(remaining) {
+            Ok(line) if line == END_OF_PREVIEW => return Ok(lines),
+            Ok(line) => {
+                if lines.len() < max_lines {
+                    lines.push(line);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => return Err(io::ErrorKind::TimedOut.into()),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("exited"));
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Named profiles, which override parts of the config for a context, like
+//! `--profile work`. Each is a partial config stored in `profiles/<name>.json`
+//! under the config directory, so it only needs the fields that differ, such as
+//! the theme, key style or editor commands.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+pub fn path(config_directory: &Path, name: &str) -> io::Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(io::Error::other(format!("invalid profile name: {}", name)));
+    }
+    Ok(config_directory
+        .join("profiles")
+        .join(format!("{}.json", name)))
+}
+
+/// Applies the profile `name` on top of `config`, returning whether it has
+/// bookmarks of its own.
+pub fn apply(config: &mut Value, config_directory: &Path, name: &str) -> io::Result<bool> {
+    let path = path(config_directory, name)?;
+    let contents = fs::read_to_string(&path).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("couldn't read profile {}: {}", path.display(), error),
+        )
+    })?;
+    let overrides: Value = serde_json::from_str(&contents)?;
+    let bookmarks = overrides.get("bookmarks").is_some();
+    merge(config, overrides);
+    Ok(bookmarks)
+}
+
+/// Overwrites the fields of `config` with those in `overrides`, recursing into
+/// objects so a profile can change a single nested field.
+fn merge(config: &mut Value, overrides: Value) {
+    match (config, overrides) {
+        (Value::Object(fields), Value::Object(overrides)) => {
+            for (name, value) in overrides {
+                match fields.get_mut(&name) {
+                    Some(field) => merge(field, value),
+                    None => {
+                        fields.insert(name, value);
+                    }
+                }
+            }
+        }
+        (config, overrides) => *config = overrides,
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::VecDeque, fs, io, path::PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::shared;
+
+/// How many killed pieces of text are remembered.
+const KILL_RING_CAPACITY: usize = 16;
+
+pub enum PromptEvent {
+    None,
+    Submit(String),
+    Cancel,
+}
+
+/// How many entries of a history are kept between runs.
+const SAVED_HISTORY_CAPACITY: usize = 500;
+
+/// Previously submitted inputs of a prompt, oldest first.
+#[derive(Default)]
+pub struct History {
+    entries: Vec<String>,
+}
+impl History {
+    pub fn push(&mut self, entry: &str) {
+        if entry.is_empty() {
+            return;
+        }
+        self.entries.retain(|existing| existing != entry);
+        self.entries.push(entry.to_string());
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// The history saved as `name` by earlier runs, or an empty one.
+    pub fn load(name: &str) -> History {
+        let mut history = History::default();
+        if let Some(contents) = saved_path(name).and_then(|path| fs::read_to_string(path).ok()) {
+            contents.lines().for_each(|line| history.push(line));
+        }
+        history
+    }
+    /// Adds `entry` to the history saved as `name`, keeping what other instances
+    /// added to it meanwhile.
+    pub fn save(name: &str, entry: &str) -> io::Result<()> {
+        let Some(path) = saved_path(name) else {
+            return Err(io::Error::other("couldn't get data directory"));
+        };
+        if entry.is_empty() || entry.contains('\n') {
+            return Ok(());
+        }
+        let _lock = shared::lock(&path)?;
+        let mut saved = History::load(name);
+        saved.push(entry);
+        let skipped = saved.entries.len().saturating_sub(SAVED_HISTORY_CAPACITY);
+        let lines: Vec<&str> = saved.entries[skipped..]
+            .iter()
+            .map(String::as_str)
+            .collect();
+        shared::replace(&path, (lines.join("\n") + "\n").as_bytes())
+    }
+}
+
+fn saved_path(name: &str) -> Option<PathBuf> {
+    Some(
+        shared::data_dir()?
+            .join("fee")
+            .join(format!("{}_history", name)),
+    )
+}
+
+/// Text deleted with the kill commands (ctrl+w, ctrl+k, ...), which can be yanked back
+/// with ctrl+y, and cycled through with alt+y. Shared between all prompts.
+#[derive(Default)]
+pub struct KillRing {
+    entries: VecDeque<String>,
+}
+impl KillRing {
+    fn push(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        if self.entries.len() >= KILL_RING_CAPACITY {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(text);
+    }
+}
+
+/// A single line text input.
+#[derive(Default)]
+pub struct Prompt {
+    text: Vec<char>,
+    cursor: usize,
+    /// Which history entry is shown, counting back from the newest.
+    history_index: Option<usize>,
+    /// What was typed before browsing the history.
+    draft: String,
+    /// The position and length of the last yank, and which kill ring entry it was,
+    /// so alt+y can replace it with an older one.
+    last_yank: Option<(usize, usize, usize)>,
+}
+impl Prompt {
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+    /// Position of the cursor, in characters.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+    }
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.text.len());
+    }
+    /// Whether an entry of the history is shown, having been gone back to.
+    pub fn browsing_history(&self) -> bool {
+        self.history_index.is_some()
+    }
+    /// Treats the entry shown as typed, so going back starts over from the newest.
+    pub fn stop_browsing_history(&mut self) {
+        self.history_index = None;
+    }
+
+    pub fn handle_key(
+        &mut self,
+        key: KeyEvent,
+        history: &History,
+        kill_ring: &mut KillRing,
+    ) -> PromptEvent {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let yanked = self.last_yank.take();
+
+        match key.code {
+            KeyCode::Enter => return PromptEvent::Submit(self.text()),
+            KeyCode::Esc => return PromptEvent::Cancel,
+            KeyCode::Up => self.browse_history(history, true),
+            KeyCode::Down => self.browse_history(history, false),
+
+            KeyCode::Left if control => self.cursor = self.previous_word(),
+            KeyCode::Right if control => self.cursor = self.next_word(),
+            KeyCode::Char('b') if alt => self.cursor = self.previous_word(),
+            KeyCode::Char('f') if alt => self.cursor = self.next_word(),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Char('b') if control => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+            KeyCode::Char('f') if control => self.cursor = (self.cursor + 1).min(self.text.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::Char('a') if control => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Char('e') if control => self.cursor = self.text.len(),
+
+            KeyCode::Backspace if control || alt => self.kill(self.previous_word(), kill_ring),
+            KeyCode::Char('w') if control => self.kill(self.previous_word(), kill_ring),
+            KeyCode::Char('d') if alt => self.kill(self.next_word(), kill_ring),
+            KeyCode::Char('u') if control => self.kill(0, kill_ring),
+            KeyCode::Char('k') if control => self.kill(self.text.len(), kill_ring),
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.text.len() => {
+                self.text.remove(self.cursor);
+            }
+
+            KeyCode::Char('y') if control => self.yank(0, kill_ring),
+            KeyCode::Char('y') if alt => {
+                if let Some((start, length, index)) = yanked {
+                    self.text.drain(start..start + length);
+                    self.cursor = start;
+                    self.yank(index + 1, kill_ring);
+                }
+            }
+            KeyCode::Char(char) if !control && !alt => {
+                self.text.insert(self.cursor, char);
+                self.cursor += 1;
+            }
+            _ => {}
+        }
+        PromptEvent::None
+    }
+
+    fn previous_word(&self) -> usize {
+        let mut index = self.cursor;
+        while index > 0 && !self.text[index - 1].is_alphanumeric() {
+            index -= 1;
+        }
+        while index > 0 && self.text[index - 1].is_alphanumeric() {
+            index -= 1;
+        }
+        index
+    }
+    fn next_word(&self) -> usize {
+        let mut index = self.cursor;
+        while index < self.text.len() && !self.text[index].is_alphanumeric() {
+            index += 1;
+        }
+        while index < self.text.len() && self.text[index].is_alphanumeric() {
+            index += 1;
+        }
+        index
+    }
+    /// Deletes the text between the cursor and `to`, adding it to the kill ring.
+    fn kill(&mut self, to: usize, kill_ring: &mut KillRing) {
+        let (start, end) = (self.cursor.min(to), self.cursor.max(to));
+        let killed: String = self.text.drain(start..end).collect();
+        self.cursor = start;
+        kill_ring.push(killed);
+    }
+    /// Inserts the `index`th most recently killed text at the cursor.
+    fn yank(&mut self, index: usize, kill_ring: &KillRing) {
+        if kill_ring.entries.is_empty() {
+            return;
+        }
+        let index = index % kill_ring.entries.len();
+        let yanked: Vec<char> = kill_ring.entries[index].chars().collect();
+        let start = self.cursor;
+        self.text.splice(start..start, yanked.iter().copied());
+        self.cursor += yanked.len();
+        self.last_yank = Some((start, yanked.len(), index));
+    }
+    fn browse_history(&mut self, history: &History, older: bool) {
+        let count = history.entries.len();
+        let index = match (self.history_index, older) {
+            (None, true) if count > 0 => {
+                self.draft = self.text();
+                Some(0)
+            }
+            (None, _) => return,
+            (Some(index), true) => Some((index + 1).min(count - 1)),
+            (Some(0), false) => None,
+            (Some(index), false) => Some(index - 1),
+        };
+        self.history_index = index;
+        match index {
+            Some(index) => self.set_text(&history.entries[count - 1 - index].clone()),
+            None => self.set_text(&self.draft.clone()),
+        }
+    }
+}
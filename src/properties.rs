@@ -0,0 +1,236 @@
+//! Details about a file, shown by the properties view.
+
+use std::{
+    fs::{self, FileType, Metadata},
+    io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::human_size;
+
+/// Kinds of files which can't be opened like regular files.
+#[derive(Clone, Copy)]
+pub enum Special {
+    Socket,
+    Fifo,
+    BlockDevice,
+    CharDevice,
+}
+impl Special {
+    #[cfg(unix)]
+    pub fn of(file_type: FileType) -> Option<Special> {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_socket() {
+            Some(Special::Socket)
+        } else if file_type.is_fifo() {
+            Some(Special::Fifo)
+        } else if file_type.is_block_device() {
+            Some(Special::BlockDevice)
+        } else if file_type.is_char_device() {
+            Some(Special::CharDevice)
+        } else {
+            None
+        }
+    }
+    #[cfg(not(unix))]
+    pub fn of(_file_type: FileType) -> Option<Special> {
+        None
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Special::Socket => "socket",
+            Special::Fifo => "fifo",
+            Special::BlockDevice => "block device",
+            Special::CharDevice => "character device",
+        }
+    }
+    /// Suffix marking the kind in listings, like `ls -F`.
+    pub fn symbol(self) -> char {
+        match self {
+            Special::Socket => '=',
+            Special::Fifo => '|',
+            Special::BlockDevice | Special::CharDevice => '#',
+        }
+    }
+}
+
+/// Returns a line per property of the file at `path`.
+pub fn describe(path: &Path) -> io::Result<Vec<String>> {
+    let link_metadata = fs::symlink_metadata(path)?;
+    // describe what links point at, unless they're broken
+    let metadata = fs::metadata(path).unwrap_or_else(|_| link_metadata.clone());
+
+    let mut lines = vec![
+        format!(
+            "name:        {}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        format!("path:        {}", path.display()),
+        format!("type:        {}", type_name(metadata.file_type())),
+    ];
+    if link_metadata.is_symlink() {
+        let target = fs::read_link(path)?;
+        lines.push(format!("link to:     {}", target.display()));
+    }
+    if metadata.is_file() {
+        lines.push(format!(
+            "size:        {} ({} bytes)",
+            human_size(metadata.len()),
+            metadata.len()
+        ));
+    }
+    lines.extend(permissions(&metadata));
+    if let Ok(modified) = metadata.modified() {
+        lines.push(format!("modified:    {}", format_time(modified)));
+    }
+    if let Ok(accessed) = metadata.accessed() {
+        lines.push(format!("accessed:    {}", format_time(accessed)));
+    }
+    Ok(lines)
+}
+
+fn type_name(file_type: FileType) -> &'static str {
+    if let Some(special) = Special::of(file_type) {
+        special.name()
+    } else if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_symlink() {
+        "broken link"
+    } else {
+        "file"
+    }
+}
+
+#[cfg(unix)]
+fn permissions(metadata: &Metadata) -> Vec<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    vec![
+        format!(
+            "permissions: {} ({:o})",
+            permission_bits(metadata),
+            metadata.mode() & 0o7777
+        ),
+        format!("owner:       {}:{}", metadata.uid(), metadata.gid()),
+    ]
+}
+
+#[cfg(not(unix))]
+fn permissions(metadata: &Metadata) -> Vec<String> {
+    vec![format!("permissions: {}", permission_bits(metadata))]
+}
+
+/// Returns the permissions as `rwxr-xr-x`.
+#[cfg(unix)]
+pub fn permission_bits(metadata: &Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+
+    mode_bits(metadata.mode())
+}
+
+/// Returns the permission bits of `mode` as `rwxr-xr-x`.
+pub fn mode_bits(mode: u32) -> String {
+    (0..9)
+        .map(|index| {
+            if mode & (0o400 >> index) == 0 {
+                '-'
+            } else {
+                ['r', 'w', 'x'][index % 3]
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+pub fn permission_bits(metadata: &Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "read only".to_string()
+    } else {
+        "read/write".to_string()
+    }
+}
+
+/// Formats `time` as a local date and time.
+pub fn format_time(time: SystemTime) -> String {
+    let Some((year, month, day, hour, minute, second)) = local(time) else {
+        return "before 1970".to_string();
+    };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Formats `time` as a local date and time to the minute, for columns.
+pub fn format_short_time(time: SystemTime) -> String {
+    let Some((year, month, day, hour, minute, _)) = local(time) else {
+        return "before 1970".to_string();
+    };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year, month, day, hour, minute
+    )
+}
+
+/// Splits `time` into its local year, month, day, hour, minute and second.
+#[cfg(unix)]
+pub fn local(time: SystemTime) -> Option<(i64, u32, u32, u64, u64, u64)> {
+    let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let seconds = libc::time_t::try_from(seconds).ok()?;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // SAFETY: `seconds` and `tm` are valid for the call, and localtime_r only writes to `tm`
+    if unsafe { libc::localtime_r(&seconds, &mut tm) }.is_null() {
+        return utc(time);
+    }
+    Some((
+        i64::from(tm.tm_year) + 1900,
+        (tm.tm_mon + 1) as u32,
+        tm.tm_mday as u32,
+        tm.tm_hour as u64,
+        tm.tm_min as u64,
+        tm.tm_sec as u64,
+    ))
+}
+
+/// Without a time zone database to look in, local times are UTC.
+#[cfg(not(unix))]
+pub fn local(time: SystemTime) -> Option<(i64, u32, u32, u64, u64, u64)> {
+    utc(time)
+}
+
+/// Splits `time` into its UTC year, month, day, hour, minute and second.
+pub fn utc(time: SystemTime) -> Option<(i64, u32, u32, u64, u64, u64)> {
+    let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time_of_day = seconds % 86400;
+    Some((
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60,
+    ))
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date.
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,156 @@
+//! Remote control of running instances, in the style of `lf -remote`.
+//!
+//! Every instance listens on a unix socket in the runtime directory, named after
+//! its process id, and runs each line written to it as a command, as if typed on
+//! the command line. Only the user can reach the sockets, and shell commands
+//! aren't taken from them.
+
+use std::{fs, io, path::PathBuf, sync::mpsc::Receiver};
+
+use crate::shared;
+
+/// The user's own directory for the sockets, without which there's no remote
+/// control.
+fn socket_directory() -> io::Result<PathBuf> {
+    let directory =
+        shared::runtime_dir().ok_or(io::Error::other("no directory to keep sockets in"))?;
+    Ok(directory.join("fee"))
+}
+
+fn socket_path(id: u32) -> io::Result<PathBuf> {
+    Ok(socket_directory()?.join(format!("{}.sock", id)))
+}
+
+/// Makes the socket directory if it's missing, and checks that it's the user's
+/// own and closed to everyone else.
+#[cfg(unix)]
+fn private_socket_directory() -> io::Result<PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    let directory = socket_directory()?;
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&directory)?;
+    let metadata = fs::symlink_metadata(&directory)?;
+    // SAFETY: geteuid has no preconditions and can't fail
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::geteuid() } {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("'{}' isn't the user's own", directory.display()),
+        ));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        fs::set_permissions(&directory, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(directory)
+}
+
+/// Listens for commands from other processes.
+pub struct Server {
+    path: PathBuf,
+    receiver: Receiver<String>,
+}
+
+#[cfg(unix)]
+impl Server {
+    pub fn start() -> io::Result<Server> {
+        use std::{
+            io::{BufRead, BufReader},
+            os::unix::{fs::PermissionsExt, net::UnixListener},
+            sync::mpsc::channel,
+        };
+
+        let path = private_socket_directory()?.join(format!("{}.sock", std::process::id()));
+        // left behind by a crashed instance which had the same id
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                    if sender.send(line).is_err() {
+                        return;
+                    }
+                    wake();
+                }
+            }
+        });
+        Ok(Server { path, receiver })
+    }
+}
+
+#[cfg(not(unix))]
+impl Server {
+    pub fn start() -> io::Result<Server> {
+        Err(io::Error::other("remote control needs unix sockets"))
+    }
+}
+
+impl Server {
+    pub fn try_receive(&self) -> Option<String> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Interrupts the main thread waiting for input, by faking a terminal resize,
+/// so it gets around to running the received command.
+#[cfg(unix)]
+fn wake() {
+    // SAFETY: sending a signal to our own process has no memory safety concerns
+    unsafe {
+        libc::kill(libc::getpid(), libc::SIGWINCH);
+    }
+}
+
+/// Sends `message` to running instances. Like lf, it has the form
+/// `send [id] command`, where the command goes to every instance if no id is given.
+#[cfg(unix)]
+pub fn send(message: &str) -> io::Result<()> {
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    let Some(rest) = message.trim().strip_prefix("send") else {
+        return Err(io::Error::other("only `send [id] command` is supported"));
+    };
+    let rest = rest.trim_start();
+    let addressed = rest
+        .split_once(' ')
+        .and_then(|(id, command)| Some((id.parse::<u32>().ok()?, command)));
+    let (paths, command) = match addressed {
+        Some((id, command)) => (vec![socket_path(id)?], command),
+        None => {
+            let paths = fs::read_dir(socket_directory()?)?
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == "sock")
+                })
+                .collect();
+            (paths, rest)
+        }
+    };
+    for path in paths {
+        match UnixStream::connect(&path) {
+            Ok(mut stream) => writeln!(stream, "{}", command)?,
+            // the instance is gone, and didn't get to clean up after itself
+            Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+                let _ = fs::remove_file(&path);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn send(_message: &str) -> io::Result<()> {
+    Err(io::Error::other("remote control needs unix sockets"))
+}
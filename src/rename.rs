@@ -0,0 +1,215 @@
+//! Renaming several files at once, with a find and replace on their names or a
+//! template like `photo_{n:03}.{ext}`.
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{parse_regex, prompt::Prompt, properties};
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum Matching {
+    /// Replace a plain substring.
+    #[default]
+    Text,
+    Regex,
+    /// Build every name from a template, ignoring the replace field.
+    Template,
+}
+impl Matching {
+    pub fn next(self) -> Matching {
+        match self {
+            Matching::Text => Matching::Regex,
+            Matching::Regex => Matching::Template,
+            Matching::Template => Matching::Text,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Matching::Text => "text",
+            Matching::Regex => "regex",
+            Matching::Template => "template",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BatchRename {
+    pub targets: Vec<PathBuf>,
+    pub find: Prompt,
+    pub replace: Prompt,
+    /// Whether the replace field is being edited, rather than the find field.
+    pub editing_replace: bool,
+    pub matching: Matching,
+}
+
+impl BatchRename {
+    pub fn new(targets: Vec<PathBuf>) -> Self {
+        BatchRename {
+            targets,
+            ..Default::default()
+        }
+    }
+
+    pub fn focused(&mut self) -> &mut Prompt {
+        if self.editing_replace && self.matching != Matching::Template {
+            &mut self.replace
+        } else {
+            &mut self.find
+        }
+    }
+
+    /// Returns the new name of every target, or why the find pattern is invalid.
+    pub fn new_names(&self) -> Result<Vec<String>, String> {
+        let find = self.find.text();
+        let replace = self.replace.text();
+        let regex = if self.matching == Matching::Regex {
+            Some(parse_regex(&find)?)
+        } else {
+            None
+        };
+        self.targets
+            .iter()
+            .enumerate()
+            .map(|(index, path)| {
+                let name = file_name(path);
+                Ok(match (&regex, self.matching) {
+                    (_, _) if find.is_empty() => name,
+                    (Some(regex), _) => regex.replace_all(&name, replace.as_str()).into_owned(),
+                    (None, Matching::Template) => expand_template(&find, path, index + 1)?,
+                    (None, _) => name.replace(&find, &replace),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the renames to do as (from, to) pairs, checking that they don't
+    /// clash with each other or with existing files.
+    pub fn plan(&self) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+        let names = self.new_names()?;
+        let mut renames = vec![];
+        let mut destinations = HashSet::new();
+        for (path, name) in self.targets.iter().zip(names) {
+            if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+                return Err(format!("invalid name: '{}'", name));
+            }
+            let destination = path.with_file_name(&name);
+            if !destinations.insert(destination.clone()) {
+                return Err(format!("more than one file would be named '{}'", name));
+            }
+            if destination != *path {
+                renames.push((path.clone(), destination));
+            }
+        }
+        for (_, destination) in &renames {
+            // files being renamed themselves are out of the way by then
+            if destination.symlink_metadata().is_ok() && !self.targets.contains(destination) {
+                return Err(format!("'{}' already exists", file_name(destination)));
+            }
+        }
+        Ok(renames)
+    }
+}
+
+/// Does the renames, moving everything to a temporary name first so files can
+/// swap names.
+pub fn apply(renames: &[(PathBuf, PathBuf)]) -> io::Result<()> {
+    let temporary: Vec<PathBuf> = renames
+        .iter()
+        .enumerate()
+        .map(|(index, (from, _))| {
+            from.with_file_name(format!(
+                ".fee-rename-{}-{}-{}",
+                std::process::id(),
+                index,
+                file_name(from)
+            ))
+        })
+        .collect();
+    for (index, ((from, _), moved)) in renames.iter().zip(&temporary).enumerate() {
+        if let Err(error) = fs::rename(from, moved) {
+            // put back what was moved so far
+            for ((from, _), temporary) in renames.iter().zip(&temporary).take(index) {
+                let _ = fs::rename(temporary, from);
+            }
+            return Err(error);
+        }
+    }
+    let mut result = Ok(());
+    for ((from, to), temporary) in renames.iter().zip(&temporary) {
+        if let Err(error) = fs::rename(temporary, to) {
+            let _ = fs::rename(temporary, from);
+            result = Err(error);
+        }
+    }
+    result
+}
+
+/// Expands the placeholders of `template` for the `number`th file, `path`:
+///
+/// * `{n}` - the number, or zero padded to a width with `{n:03}`
+/// * `{name}` - the original name, without extension
+/// * `{ext}` - the original extension
+/// * `{file}` - the original name, with extension
+/// * `{date}` / `{time}` - when the file was last modified, as `2024-01-31` / `13.45.10`
+fn expand_template(template: &str, path: &Path, number: usize) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let Some(length) = rest[start..].find('}') else {
+            return Err("unclosed '{'".to_string());
+        };
+        let placeholder = &rest[start + 1..start + length];
+        let (key, argument) = match placeholder.split_once(':') {
+            Some((key, argument)) => (key, Some(argument)),
+            None => (placeholder, None),
+        };
+        let modified = || {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(properties::local)
+                .ok_or(format!("couldn't get the date of '{}'", file_name(path)))
+        };
+        match (key, argument) {
+            ("n", None) => expanded.push_str(&number.to_string()),
+            ("n", Some(width)) => {
+                let width: usize = width
+                    .parse()
+                    .map_err(|_| format!("invalid width '{}'", width))?;
+                // no name can be longer anyway
+                let width = width.min(255);
+                expanded.push_str(&format!("{:0width$}", number, width = width));
+            }
+            ("name", None) => {
+                expanded.push_str(&path.file_stem().unwrap_or_default().to_string_lossy())
+            }
+            ("ext", None) => {
+                expanded.push_str(&path.extension().unwrap_or_default().to_string_lossy())
+            }
+            ("file", None) => expanded.push_str(&file_name(path)),
+            ("date", None) => {
+                let (year, month, day, ..) = modified()?;
+                expanded.push_str(&format!("{:04}-{:02}-{:02}", year, month, day));
+            }
+            ("time", None) => {
+                let (.., hour, minute, second) = modified()?;
+                expanded.push_str(&format!("{:02}.{:02}.{:02}", hour, minute, second));
+            }
+            _ => return Err(format!("unknown placeholder {{{}}}", placeholder)),
+        }
+        rest = &rest[start + length + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
@@ -0,0 +1,172 @@
+//! Typing the keys given with `--keys` at startup, for demos and for showing
+//! how to reproduce a bug. Keys are written like in vim: characters stand for
+//! themselves, and others are named in angle brackets, like `<enter>`, `<c-p>`
+//! for ctrl+p and `<a-left>` for alt+left, with `<lt>` for `<`. `<500ms>` or
+//! `<2s>` waits before the next key. Once they've all been typed, the keyboard
+//! takes over.
+
+use std::{
+    collections::VecDeque,
+    io, thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::backend::Events;
+
+pub enum Step {
+    Key(KeyEvent),
+    Wait(Duration),
+}
+
+/// Parses a sequence of keys like `jjj<enter><1s>q`.
+pub fn parse(text: &str) -> Result<Vec<Step>, String> {
+    let mut steps = vec![];
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        if char != '<' {
+            steps.push(Step::Key(KeyEvent::new(
+                KeyCode::Char(char),
+                KeyModifiers::NONE,
+            )));
+            continue;
+        }
+        let name: String = chars.by_ref().take_while(|char| *char != '>').collect();
+        steps.push(parse_name(&name).ok_or(format!("unknown key: <{}>", name))?);
+    }
+    Ok(steps)
+}
+
+/// Parses a single key, like `g` or `<c-g>`.
+pub fn parse_key(text: &str) -> Option<KeyEvent> {
+    match parse(text).ok()?.as_slice() {
+        [Step::Key(key)] => Some(*key),
+        _ => None,
+    }
+}
+
+/// Parses what's between the angle brackets of a named key or a wait.
+fn parse_name(name: &str) -> Option<Step> {
+    let name = name.to_lowercase();
+    if let Some(milliseconds) = name.strip_suffix("ms") {
+        return Some(Step::Wait(Duration::from_millis(
+            milliseconds.parse().ok()?,
+        )));
+    }
+    if let Some(seconds) = name
+        .strip_suffix('s')
+        .and_then(|seconds| seconds.parse::<f64>().ok())
+    {
+        // negative, infinite and overly long waits aren't waits
+        return Duration::try_from_secs_f64(seconds).ok().map(Step::Wait);
+    }
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name.as_str();
+    loop {
+        if let Some(after) = rest.strip_prefix("c-") {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("a-") {
+            modifiers |= KeyModifiers::ALT;
+            rest = after;
+        } else {
+            break;
+        }
+    }
+    let code = match rest {
+        "enter" | "cr" | "return" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "s-tab" => KeyCode::BackTab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        _ => match rest
+            .strip_prefix('f')
+            .and_then(|number| number.parse().ok())
+        {
+            Some(number) => KeyCode::F(number),
+            None => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(char), None) => KeyCode::Char(char),
+                    _ => return None,
+                }
+            }
+        },
+    };
+    Some(Step::Key(KeyEvent::new(code, modifiers)))
+}
+
+/// Types the steps, then reads from `then`.
+pub struct Replay {
+    steps: VecDeque<Step>,
+    then: Box<dyn Events>,
+    /// When the wait being waited out is over.
+    waiting_until: Option<Instant>,
+}
+impl Replay {
+    pub fn new(steps: Vec<Step>, then: Box<dyn Events>) -> Self {
+        Replay {
+            steps: steps.into(),
+            then,
+            waiting_until: None,
+        }
+    }
+    /// Waits out the wait at the front of the steps, if there is one, for up to
+    /// `timeout`. Returns whether it's over.
+    fn wait(&mut self, timeout: Option<Duration>) -> bool {
+        let Some(Step::Wait(duration)) = self.steps.front() else {
+            return true;
+        };
+        let until = *self
+            .waiting_until
+            .get_or_insert_with(|| Instant::now() + *duration);
+        let remaining = until.saturating_duration_since(Instant::now());
+        if let Some(timeout) = timeout.filter(|timeout| *timeout < remaining) {
+            thread::sleep(timeout);
+            return false;
+        }
+        thread::sleep(remaining);
+        self.waiting_until = None;
+        self.steps.pop_front();
+        true
+    }
+}
+impl Events for Replay {
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        loop {
+            match self.steps.front() {
+                None => return self.then.poll(timeout),
+                Some(Step::Key(_)) => return Ok(true),
+                Some(Step::Wait(_)) => {
+                    if !self.wait(Some(timeout)) {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+    }
+    fn read(&mut self) -> io::Result<Option<Event>> {
+        loop {
+            match self.steps.pop_front() {
+                None => return self.then.read(),
+                Some(Step::Key(key)) => return Ok(Some(Event::Key(key))),
+                Some(wait) => {
+                    self.steps.push_front(wait);
+                    self.wait(None);
+                }
+            }
+        }
+    }
+}
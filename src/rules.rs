@@ -0,0 +1,221 @@
+//! Coloring items by rules in the config, like making huge logs red or files
+//! modified today bold. Rules are checked in order, and the first one whose
+//! conditions all hold decides how an item looks. Items can also be tinted by
+//! how recently they were modified, so fresh changes stand out.
+
+use std::time::{Duration, SystemTime};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{parse_regex, properties, split};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    File,
+    Directory,
+    Link,
+    Special,
+}
+
+/// What's tinted by how recently an item was modified, if anything.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AgeHeat {
+    #[default]
+    Off,
+    /// The modification time in the details view.
+    Time,
+    Name,
+}
+
+/// Items modified this many seconds ago or less get the recent color.
+const HOT_AGE: f64 = 60.0;
+/// Items modified this many seconds ago or more get the old color.
+const COLD_AGE: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+/// The color of something modified at `modified`, blended from `recent` to
+/// `old`. Ages are on a logarithmic scale, so a minute and an hour ago look
+/// about as different as a week and a year ago.
+pub fn age_color(modified: SystemTime, now: SystemTime, recent: [u8; 3], old: [u8; 3]) -> [u8; 3] {
+    let age = now
+        .duration_since(modified)
+        .unwrap_or_default()
+        .as_secs_f64()
+        .max(HOT_AGE);
+    let fraction = ((age / HOT_AGE).ln() / (COLD_AGE / HOT_AGE).ln()).min(1.0);
+    let mut color = [0; 3];
+    for channel in 0..3 {
+        let (from, to) = (recent[channel] as f64, old[channel] as f64);
+        color[channel] = (from + (to - from) * fraction).round() as u8;
+    }
+    color
+}
+
+/// A rule as written in the config. Conditions left out always hold.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ColorRule {
+    /// Regex the name has to match.
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: Option<Kind>,
+    /// Sizes like `100M`. Only files have a size, so directories never match.
+    pub min_size: Option<String>,
+    pub max_size: Option<String>,
+    /// Ages like `30m`, `12h`, `1d` or `2w`, or `today`.
+    pub min_age: Option<String>,
+    pub max_age: Option<String>,
+    pub color: Option<[u8; 3]>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// Shown before the name.
+    pub icon: Option<String>,
+}
+
+/// How long ago something was modified, at most or at least.
+enum Age {
+    Duration(Duration),
+    /// Since the start of the day, in local time like the times shown.
+    Today,
+}
+impl Age {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text == "today" {
+            return Some(Age::Today);
+        }
+        let unit = match text.chars().last()? {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        let count: u64 = text[..text.len() - 1].trim().parse().ok()?;
+        Some(Age::Duration(Duration::from_secs(count.checked_mul(unit)?)))
+    }
+    /// How long ago the boundary is, at `now`.
+    fn duration(&self, now: SystemTime) -> Duration {
+        match self {
+            Age::Duration(duration) => *duration,
+            Age::Today => {
+                let (_, _, _, hour, minute, second) = properties::local(now).unwrap_or_default();
+                Duration::from_secs(hour * 3600 + minute * 60 + second)
+            }
+        }
+    }
+}
+
+/// What a matching rule changes about how an item looks.
+#[derive(Clone)]
+pub struct Style {
+    pub color: Option<[u8; 3]>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub icon: Option<String>,
+}
+
+/// A rule with its regex and limits parsed.
+pub struct Rule {
+    name: Option<Regex>,
+    kind: Option<Kind>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    min_age: Option<Age>,
+    max_age: Option<Age>,
+    pub style: Style,
+}
+impl Rule {
+    /// Parses the rules in the config, describing the first invalid one, if any.
+    pub fn parse_all(rules: &[ColorRule]) -> Result<Vec<Rule>, String> {
+        rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                Rule::parse(rule).map_err(|error| format!("color rule {}: {}", index + 1, error))
+            })
+            .collect()
+    }
+    fn parse(rule: &ColorRule) -> Result<Self, String> {
+        let size = |text: &Option<String>| match text {
+            Some(text) => split::parse_size(text)
+                .map(Some)
+                .ok_or(format!("invalid size '{}'", text)),
+            None => Ok(None),
+        };
+        let age = |text: &Option<String>| match text {
+            Some(text) => Age::parse(text)
+                .map(Some)
+                .ok_or(format!("invalid age '{}'", text)),
+            None => Ok(None),
+        };
+        Ok(Rule {
+            name: rule.name.as_deref().map(parse_regex).transpose()?,
+            kind: rule.kind,
+            min_size: size(&rule.min_size)?,
+            max_size: size(&rule.max_size)?,
+            min_age: age(&rule.min_age)?,
+            max_age: age(&rule.max_age)?,
+            style: Style {
+                color: rule.color,
+                bold: rule.bold,
+                italic: rule.italic,
+                underline: rule.underline,
+                icon: rule.icon.clone(),
+            },
+        })
+    }
+    /// Whether an item with these details matches. Unknown sizes and times
+    /// don't match conditions on them.
+    pub fn matches(
+        &self,
+        name: &str,
+        kind: Kind,
+        size: Option<u64>,
+        modified: Option<SystemTime>,
+        now: SystemTime,
+    ) -> bool {
+        if self.kind.is_some_and(|wanted| wanted != kind) {
+            return false;
+        }
+        if let Some(regex) = &self.name {
+            if !regex.is_match(name) {
+                return false;
+            }
+        }
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let Some(size) = size.filter(|_| kind == Kind::File) else {
+                return false;
+            };
+            if self.min_size.is_some_and(|min| size < min)
+                || self.max_size.is_some_and(|max| size > max)
+            {
+                return false;
+            }
+        }
+        if self.min_age.is_some() || self.max_age.is_some() {
+            let Some(age) =
+                modified.map(|modified| now.duration_since(modified).unwrap_or_default())
+            else {
+                return false;
+            };
+            if self
+                .min_age
+                .as_ref()
+                .is_some_and(|min| age < min.duration(now))
+                || self
+                    .max_age
+                    .as_ref()
+                    .is_some_and(|max| age > max.duration(now))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,376 @@
+//! A JSON Schema of the config file, for editors to offer completion and
+//! validation while it's edited by hand.
+//!
+//! The shape of the schema is inferred from the default config, so only what
+//! can't be inferred (descriptions, allowed values) is listed here.
+
+use serde_json::{json, Map, Value};
+
+/// Descriptions of the config fields. Nested fields are separated by dots.
+const DESCRIPTIONS: &[(&str, &str)] = &[
+    ("version", "Version of the config format. Don't change this by hand."),
+    ("text_editor_command", "Command opening text files. `$f` expands to the file."),
+    ("binary_editor_command", "Command opening binary files. `$f` expands to the file."),
+    (
+        "large_text_editor_command",
+        "Command opening text files bigger than `large_text_above` bytes, like a pager. `$f` expands to the file. Unused if empty.",
+    ),
+    ("large_text_above", "Size in bytes above which text files are opened with `large_text_editor_command`."),
+    ("wait_for_editor_exit", "Wait for the editor to exit before drawing fee again."),
+    (
+        "gui_editors",
+        "Editors opening in a window of their own. Commands run by them don't take over the terminal, and aren't waited for.",
+    ),
+    ("dir_color", "Color of directories, as [r, g, b]."),
+    ("file_color", "Color of files, as [r, g, b]."),
+    ("link_color", "Color of symlinks and junctions, as [r, g, b]."),
+    ("special_color", "Color of sockets, FIFOs and devices, as [r, g, b]."),
+    (
+        "package_extensions",
+        "Directories with these extensions (like macOS `.app` bundles) are opened rather than entered.",
+    ),
+    ("package_open_command", "Command opening packages. `$f` expands to the package."),
+    (
+        "associations",
+        "Commands opening files instead of the editors, keyed by an extension like `png` or a glob like `*.tar.gz`. `$f` expands to the file. The longest matching pattern opens it, the others are offered in the open with menu.",
+    ),
+    (
+        "search_enter_jumps",
+        "Pressing enter on a file in search results goes to its directory rather than opening it.",
+    ),
+    (
+        "persist_history",
+        "Remember search and filter queries between runs, rather than only during one. Up in their prompts goes back through them.",
+    ),
+    (
+        "open_binary_with_system",
+        "Open files that aren't text, like PDFs and images, with the program the system opens them with (xdg-open, open or start) rather than `binary_editor_command`.",
+    ),
+    (
+        "open_all_command",
+        "Command opening every marked file or search hit at once. `$fs` expands to all of the files, `$q` to a quickfix file.",
+    ),
+    ("pdf_preview_command", "Command whose output previews PDFs. `$f` expands to the file."),
+    (
+        "preview_servers",
+        "Previewers kept running, so slow to start ones don't start over for every preview. Each is sent paths on stdin, one per line, and answers with the preview followed by a line with only a NUL byte.",
+    ),
+    ("media_probe_command", "ffprobe-compatible command printing JSON metadata of audio/video files."),
+    (
+        "video_thumbnail_command",
+        "Command grabbing a PNG frame of a video. `$f` expands to the video, `$o` to the output file.",
+    ),
+    ("image_previews", "Preview images, with the terminal's graphics protocol if it has one, or otherwise as colored blocks."),
+    (
+        "image_thumbnail_command",
+        "Command shrinking an image to a PPM thumbnail. `$f` expands to the image, `$o` to the output file.",
+    ),
+    ("preview_fold_arrays", "Arrays in JSON/YAML previews with more items than this are cut short."),
+    ("preview_line_numbers", "Show line numbers in text previews."),
+    ("preview_highlight", "Highlight the syntax of source files in previews."),
+    ("preview_wrap", "Wrap long lines in previews rather than cutting them off."),
+    ("preview_max_bytes", "At most this many bytes of a file, or of a previewer's output, are previewed."),
+    ("preview_timeout_ms", "External previewers running longer than this many milliseconds are killed."),
+    ("preview_disabled_extensions", "Files with these extensions are never previewed."),
+    ("preview_disabled_above", "Files larger than this many bytes are never previewed."),
+    (
+        "directory_stat_delay_ms",
+        "After the cursor rests on a directory for this many milliseconds, its item count and total size are shown by {contents} in the status bar. null turns it off.",
+    ),
+    (
+        "respect_ignore_files",
+        "Leave entries matched by `.gitignore` and `.ignore` files out of the listing, searches, directory sizes and the file finder, even while hidden files are shown.",
+    ),
+    (
+        "git_column",
+        "In git repositories, show the date and hash of the last commit touching each entry in a column next to its name.",
+    ),
+    (
+        "show_hidden",
+        "Whether dotfiles, names listed in a directory's `.hidden` file and, on Windows, files with the hidden attribute are shown on startup.",
+    ),
+    ("show_details", "Whether sizes, modification times and permissions are shown next to names on startup."),
+    ("show_preview", "Whether the preview pane is shown on startup."),
+    (
+        "color_rules",
+        "Rules coloring the items they match, checked in order. The first one whose conditions all hold decides the color, style and icon.",
+    ),
+    (
+        "age_heat",
+        "Tint the modification time in the details view, or the name, on a gradient from recent to old.",
+    ),
+    (
+        "age_heat_colors",
+        "The colors of things modified just now and of ones a year old or more, with the ages in between blended.",
+    ),
+    ("dual_pane", "Start with two panes side by side instead of the preview, switched between with Tab."),
+    ("preview_ratio", "Fraction of the screen taken up by the preview pane."),
+    ("preview_orientation", "Whether the preview pane is to the right of, or below, the file listing."),
+    ("sort_by", "How the listing is sorted on startup. Directories always come first."),
+    ("sort_descending", "Whether the listing is sorted the other way around on startup, biggest or newest first."),
+    ("theme", "Colors and borders."),
+    ("theme.border_style", "Style of the borders drawn around and between panes."),
+    ("theme.border_color", "Color of the borders, as [r, g, b]."),
+    ("theme.syntax_theme", "Colors of highlighted code in previews, to suit a dark or light terminal background."),
+    ("status_bar", "Templates for the two sides of the status bar."),
+    (
+        "status_bar.left",
+        "Left side. Placeholders: {path}, {mode}, {name}, {size}, {modified}, {permissions}, {index}, {total}, {filter}, {marked}, {contents}, {dry-run}, {free}.",
+    ),
+    (
+        "status_bar.own_line",
+        "Give the status bar a line of its own above the bottom line, so it stays shown while typing in prompts.",
+    ),
+    (
+        "status_bar.right",
+        "Right side. Placeholders: {path}, {mode}, {name}, {size}, {modified}, {permissions}, {index}, {total}, {filter}, {marked}, {contents}, {dry-run}, {free}.",
+    ),
+    (
+        "key_style",
+        "`vim` additionally moves around with h/j/k/l, `gg`/`G` and ctrl+d/ctrl+u.",
+    ),
+    (
+        "custom_commands",
+        "Commands run by keys. `key` is written like with --keys, like `g` or `<c-g>`. In `command`, `$f` expands to the selected item, `$fs` to the marked ones and `$d` to the current directory. `wait` keeps the output up until a key is pressed.",
+    ),
+    (
+        "shell",
+        "Shell opened in the current directory with `$`, with its arguments. Defaults to `$SHELL`.",
+    ),
+    (
+        "mouse",
+        "Capture the mouse, so right-clicking an item opens its context menu. This keeps the terminal from selecting text.",
+    ),
+    (
+        "pause_when_unfocused",
+        "Stop checking on previews, directory sizes and other background work while the terminal isn't focused.",
+    ),
+    ("directory_file", "The current directory is written to this file whenever it changes."),
+    (
+        "directory_change_command",
+        "Command run in the background whenever the directory changes. `$d` and $FEE_DIR expand to the new directory.",
+    ),
+    (
+        "accessible",
+        "Screen reader friendly mode: no decorations, a line describing the selection, and the cursor kept on the selection.",
+    ),
+    (
+        "use_trash",
+        "Deleting moves files to the trash, rather than deleting them permanently, which can still be done with `D`.",
+    ),
+    (
+        "dry_run",
+        "Only report what file operations would do, without touching any files.",
+    ),
+    (
+        "audit_log",
+        "Append every change made to files to `audit.log` in the config directory.",
+    ),
+    (
+        "chords",
+        "Key sequences typed in the listing, like `g/`, and the commands they run, like `cd /`.",
+    ),
+    (
+        "bookmarks",
+        "Directories listed in the bookmark picker, opened with `'`. Each can have a `key` jumping straight to it, and a `group` it's listed under.",
+    ),
+];
+
+/// Fields only accepting some values.
+const ALLOWED_VALUES: &[(&str, &[&str])] = &[
+    ("preview_orientation", &["right", "bottom"]),
+    ("sort_by", &["name", "size", "modified", "extension"]),
+    ("theme.border_style", &["rounded", "ascii", "off"]),
+    ("theme.syntax_theme", &["dark", "light"]),
+    ("age_heat", &["off", "time", "name"]),
+    ("key_style", &["arrows", "vim"]),
+];
+
+/// Schemas of fields whose default doesn't give away their type, like `null` or `[]`.
+const EXPLICIT: &[(&str, &str)] = &[
+    (
+        "large_text_editor_command",
+        r#"{ "type": "array", "items": { "type": "string" } }"#,
+    ),
+    (
+        "preview_disabled_extensions",
+        r#"{ "type": "array", "items": { "type": "string" } }"#,
+    ),
+    (
+        "preview_disabled_above",
+        r#"{ "type": "integer", "minimum": 0 }"#,
+    ),
+    (
+        "bookmarks",
+        r#"{
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "path": { "type": "string" },
+                    "key": { "type": ["string", "null"], "minLength": 1, "maxLength": 1 },
+                    "group": { "type": ["string", "null"] }
+                },
+                "required": ["name", "path"]
+            }
+        }"#,
+    ),
+    (
+        "chords",
+        r#"{ "type": "object", "additionalProperties": { "type": "string" } }"#,
+    ),
+    (
+        "associations",
+        r#"{ "type": "object", "additionalProperties": { "type": "array", "items": { "type": "string" } } }"#,
+    ),
+    (
+        "color_rules",
+        r#"{
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "Regex the name has to match." },
+                    "type": { "enum": ["file", "directory", "link", "special"] },
+                    "min_size": { "type": "string", "description": "A size like 100M." },
+                    "max_size": { "type": "string", "description": "A size like 100M." },
+                    "min_age": { "type": "string", "description": "An age like 30m, 12h, 1d or 2w, or today." },
+                    "max_age": { "type": "string", "description": "An age like 30m, 12h, 1d or 2w, or today." },
+                    "color": {
+                        "type": "array",
+                        "items": { "type": "integer", "minimum": 0, "maximum": 255 },
+                        "minItems": 3,
+                        "maxItems": 3
+                    },
+                    "bold": { "type": "boolean" },
+                    "italic": { "type": "boolean" },
+                    "underline": { "type": "boolean" },
+                    "icon": { "type": "string", "description": "Shown before the name." }
+                },
+                "additionalProperties": false
+            }
+        }"#,
+    ),
+    (
+        "preview_servers",
+        r#"{
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "extensions": { "type": "array", "items": { "type": "string" } },
+                    "command": { "type": "array", "items": { "type": "string" } }
+                },
+                "required": ["extensions", "command"],
+                "additionalProperties": false
+            }
+        }"#,
+    ),
+    (
+        "custom_commands",
+        r#"{
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "key": { "type": "string" },
+                    "command": { "type": "array", "items": { "type": "string" } },
+                    "wait": { "type": "boolean" }
+                },
+                "required": ["key", "command"],
+                "additionalProperties": false
+            }
+        }"#,
+    ),
+    (
+        "shell",
+        r#"{ "type": "array", "items": { "type": "string" } }"#,
+    ),
+    ("directory_file", r#"{ "type": "string" }"#),
+    (
+        "directory_change_command",
+        r#"{ "type": "array", "items": { "type": "string" } }"#,
+    ),
+];
+
+/// Fields which may be `null`.
+const NULLABLE: &[&str] = &[
+    "preview_fold_arrays",
+    "preview_disabled_above",
+    "directory_stat_delay_ms",
+    "directory_file",
+];
+
+/// Builds the schema of a config whose default is `defaults`.
+pub fn schema(defaults: &Value) -> Value {
+    let mut schema = describe("", defaults);
+    schema["$schema"] = json!("https://json-schema.org/draft/2020-12/schema");
+    schema["title"] = json!("fee config");
+    // lets config files point at the schema themselves
+    schema["properties"]["$schema"] = json!({ "type": "string" });
+    schema
+}
+
+fn describe(path: &str, default: &Value) -> Value {
+    let mut schema = EXPLICIT
+        .iter()
+        .find(|(field, _)| *field == path)
+        .and_then(|(_, schema)| serde_json::from_str(schema).ok())
+        .unwrap_or_else(|| infer(path, default));
+    if let Some((_, values)) = ALLOWED_VALUES.iter().find(|(field, _)| *field == path) {
+        schema = json!({ "enum": values });
+    }
+    if NULLABLE.contains(&path) {
+        schema["type"] = json!([schema["type"], "null"]);
+    }
+    if let Some((_, description)) = DESCRIPTIONS.iter().find(|(field, _)| *field == path) {
+        schema["description"] = json!(description);
+    }
+    if !default.is_object() {
+        schema["default"] = default.clone();
+    }
+    schema
+}
+
+/// Works out the schema of a field from its default value.
+fn infer(path: &str, default: &Value) -> Value {
+    let name = path.rsplit('.').next().unwrap_or_default();
+    match default {
+        Value::Object(fields) => {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|(field, value)| {
+                    let path = if path.is_empty() {
+                        field.to_owned()
+                    } else {
+                        format!("{}.{}", path, field)
+                    };
+                    (field.to_owned(), describe(&path, value))
+                })
+                .collect();
+            json!({ "type": "object", "properties": properties, "additionalProperties": false })
+        }
+        Value::Array(_) if name.ends_with("_color") => json!({
+            "type": "array",
+            "items": { "type": "integer", "minimum": 0, "maximum": 255 },
+            "minItems": 3,
+            "maxItems": 3,
+        }),
+        Value::Array(values) => match values.first() {
+            Some(value) => {
+                let mut items = describe("", value);
+                if let Some(items) = items.as_object_mut() {
+                    items.remove("default");
+                }
+                json!({ "type": "array", "items": items })
+            }
+            None => json!({ "type": "array" }),
+        },
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(number) if number.is_f64() => json!({ "type": "number" }),
+        Value::Number(_) => json!({ "type": "integer", "minimum": 0 }),
+        Value::Null => json!({}),
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{ignore::Ignore, is_valid_utf8};
+
+pub enum SearchKind {
+    Name,
+    Content,
+}
+
+pub struct Hit {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub text: Option<String>,
+}
+
+/// Recursively walks `root`, returning every entry matching `query`.
+///
+/// Name searches match case-insensitively against the file name, content searches
+/// match each line of every UTF-8 file. Symlinks (and junctions on Windows) are
+/// never followed, so links pointing back up the tree can't make it loop forever.
+/// Entries matched by `ignore` are skipped, along with everything in them.
+pub fn search(
+    root: &Path,
+    query: &str,
+    kind: &SearchKind,
+    ignore: Option<Arc<Ignore>>,
+) -> Vec<Hit> {
+    let query = query.to_lowercase();
+    let mut hits = vec![];
+    let mut pending = vec![(root.to_path_buf(), ignore)];
+
+    while let Some((dir, ignore)) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.flatten().collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if ignore
+                .as_ref()
+                .is_some_and(|ignore| ignore.is_ignored(&path, file_type.is_dir()))
+            {
+                continue;
+            }
+            match kind {
+                SearchKind::Name => {
+                    let name = entry.file_name().to_string_lossy().to_lowercase();
+                    if name.contains(&query) {
+                        hits.push(Hit {
+                            path: path.clone(),
+                            line: None,
+                            text: None,
+                        });
+                    }
+                }
+                SearchKind::Content => {
+                    if file_type.is_file() {
+                        grep_file(&path, &query, &mut hits);
+                    }
+                }
+            }
+            if file_type.is_dir() {
+                let ignore = ignore.as_ref().map(|ignore| ignore.child(&path));
+                pending.push((path, ignore));
+            }
+        }
+    }
+    hits.sort_by(|a, b| a.path.cmp(&b.path));
+    hits
+}
+
+fn grep_file(path: &PathBuf, query: &str, hits: &mut Vec<Hit>) {
+    if !is_valid_utf8(path).unwrap_or(false) {
+        return;
+    }
+    let Ok(file) = fs::File::open(path) else {
+        return;
+    };
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.to_lowercase().contains(query) {
+            hits.push(Hit {
+                path: path.clone(),
+                line: Some(index + 1),
+                text: Some(line.trim().to_string()),
+            });
+        }
+    }
+}
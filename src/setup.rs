@@ -0,0 +1,90 @@
+//! Interactive setup, offered the first time fee is launched.
+
+use std::io::{self, BufRead, Write};
+
+use crate::{keymap::KeyStyle, theme::PRESETS, Config};
+
+/// Asks the user to pick an editor, theme and keybinding style, starting from `config`.
+/// Pressing enter picks the default for every question.
+pub fn run(mut config: Config) -> io::Result<Config> {
+    println!(
+        "welcome to fee! answer a few questions to set it up, or press enter to keep the defaults."
+    );
+    println!("(the answers are written to the config file, which can be changed at any time)");
+
+    let mut editors: Vec<String> = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|variable| std::env::var(variable).ok())
+        .filter(|editor| !editor.is_empty())
+        .collect();
+    for editor in ["nano", "vim", "emacs"] {
+        if !editors.iter().any(|existing| existing == editor) {
+            editors.push(editor.to_string());
+        }
+    }
+    let mut options: Vec<&str> = editors.iter().map(String::as_str).collect();
+    options.push("something else");
+    let choice = choose("which editor should files be opened in?", &options)?;
+    let editor = match editors.get(choice) {
+        Some(editor) => editor.to_owned(),
+        None => ask("editor command (`$f` is replaced by the file): ")?,
+    };
+    if !editor.trim().is_empty() {
+        let mut command: Vec<String> = editor.split_whitespace().map(str::to_string).collect();
+        if !command.iter().any(|part| part == "$f") {
+            command.push("$f".to_string());
+        }
+        config.text_editor_command = command;
+    }
+
+    let names: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
+    let preset = &PRESETS[choose("which theme?", &names)?];
+    config.dir_color = preset.dir_color;
+    config.file_color = preset.file_color;
+    config.theme.border_style = preset.border_style;
+    config.theme.border_color = preset.border_color;
+    config.theme.syntax_theme = preset.syntax_theme;
+
+    let choice = choose(
+        "which keys should move around?",
+        &[
+            "arrow keys",
+            "vim style (h/j/k/l, as well as the arrow keys)",
+        ],
+    )?;
+    config.key_style = if choice == 1 {
+        KeyStyle::Vim
+    } else {
+        KeyStyle::Arrows
+    };
+    Ok(config)
+}
+
+/// Prints `question` with numbered `options`, returning the index of the one picked.
+/// Defaults to the first option.
+fn choose(question: &str, options: &[&str]) -> io::Result<usize> {
+    println!();
+    println!("{}", question);
+    for (index, option) in options.iter().enumerate() {
+        println!("  {}) {}", index + 1, option);
+    }
+    loop {
+        let answer = ask(&format!("[1-{}, default 1]: ", options.len()))?;
+        if answer.is_empty() {
+            return Ok(0);
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=options.len()).contains(&number) => return Ok(number - 1),
+            _ => println!("please pick a number between 1 and {}", options.len()),
+        }
+    }
+}
+
+/// Reads a line of input after printing `prompt`. Empty at the end of input.
+fn ask(prompt: &str) -> io::Result<String> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
@@ -0,0 +1,70 @@
+//! Files shared by every running instance, like the config holding the
+//! bookmarks and the cache of directory sizes. They're changed while holding a
+//! lock, so instances running at the same time don't undo each other's changes,
+//! and replaced whole, so none of them ever reads half of one.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::fileops::with_suffix;
+
+/// The user's config directory, holding fee's in a `fee` directory.
+pub fn config_dir() -> Option<PathBuf> {
+    user_dir(dirs::config_dir, "config")
+}
+
+/// The user's cache directory.
+pub fn cache_dir() -> Option<PathBuf> {
+    user_dir(dirs::cache_dir, "cache")
+}
+
+/// The user's data directory, holding histories and the trash.
+pub fn data_dir() -> Option<PathBuf> {
+    user_dir(dirs::data_dir, "data")
+}
+
+/// The user's runtime directory, or else the cache directory, for files only
+/// meant to last while the user's logged in.
+pub fn runtime_dir() -> Option<PathBuf> {
+    user_dir(|| dirs::runtime_dir().or_else(dirs::cache_dir), "runtime")
+}
+
+#[cfg(not(test))]
+fn user_dir(find: fn() -> Option<PathBuf>, _name: &str) -> Option<PathBuf> {
+    find()
+}
+
+/// Tests keep their files in a directory of their own, rather than the user's.
+#[cfg(test)]
+fn user_dir(_find: fn() -> Option<PathBuf>, name: &str) -> Option<PathBuf> {
+    let home = format!("fee-test-{}-home", std::process::id());
+    Some(std::env::temp_dir().join(home).join(name))
+}
+
+/// Waits until no other instance is changing `path`, then keeps them out until
+/// the returned file is dropped.
+pub fn lock(path: &Path) -> io::Result<File> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(with_suffix(path, ".lock"))?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Writes `contents` to `path` by moving a finished file into its place.
+pub fn replace(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let temporary = with_suffix(path, &format!(".{}", std::process::id()));
+    fs::write(&temporary, contents)?;
+    fs::rename(temporary, path)
+}
@@ -0,0 +1,83 @@
+//! Ordering the listing. Directories always come before files, and each are
+//! sorted by the current mode, cycled through with `s` and reversed with `S`.
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Item;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Extension,
+}
+impl SortBy {
+    pub const ALL: [SortBy; 4] = [
+        SortBy::Name,
+        SortBy::Size,
+        SortBy::Modified,
+        SortBy::Extension,
+    ];
+    /// The mode after this one, when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            SortBy::Name => SortBy::Size,
+            SortBy::Size => SortBy::Modified,
+            SortBy::Modified => SortBy::Extension,
+            SortBy::Extension => SortBy::Name,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            SortBy::Name => "name",
+            SortBy::Size => "size",
+            SortBy::Modified => "modified",
+            SortBy::Extension => "extension",
+        }
+    }
+    /// Whether items need their size and modification time to be sorted this
+    /// way. Getting them is slow in huge directories, so it's otherwise left to
+    /// the metadata loader.
+    pub fn needs_metadata(self) -> bool {
+        matches!(self, SortBy::Size | SortBy::Modified)
+    }
+}
+
+/// Sorts `items`, breaking ties by name. `descending` reverses the whole order,
+/// putting the biggest or newest first.
+pub fn sort(items: &mut [Item], by: SortBy, descending: bool) {
+    items.sort_by(|a, b| {
+        let ordering = match by {
+            SortBy::Name => Ordering::Equal,
+            SortBy::Size => a.size.cmp(&b.size),
+            SortBy::Modified => a.modified.cmp(&b.modified),
+            SortBy::Extension => extension(&a.name).cmp(&extension(&b.name)),
+        };
+        let ordering = ordering.then_with(|| compare_names(&a.name, &b.name));
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+/// Compares names ignoring case, falling back to case so the order is stable.
+fn compare_names(a: &str, b: &str) -> Ordering {
+    a.to_lowercase()
+        .cmp(&b.to_lowercase())
+        .then_with(|| a.cmp(b))
+}
+
+/// The lowercase extension of `name`. Dotfiles without another dot have none.
+fn extension(name: &str) -> String {
+    match name.rfind('.') {
+        Some(index) if index > 0 => name[index + 1..].to_lowercase(),
+        _ => String::new(),
+    }
+}
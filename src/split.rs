@@ -0,0 +1,204 @@
+//! Splitting big files into numbered parts, like `video.mkv.001`, so they fit on
+//! FAT32 drives, and joining them back together. An `.sfv` file with the CRC-32
+//! of every part and of the whole file is written alongside, and checked when
+//! joining.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{crc32::Crc32, fileops::with_suffix};
+
+/// The biggest file FAT32 can hold, used when no part size is given.
+pub const FAT32_PART_SIZE: u64 = 4 * 1024 * 1024 * 1024 - 1;
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// The most parts a file can be split into, as parts are numbered with three
+/// digits.
+const MAX_PARTS: u64 = 999;
+
+/// Parses a size like `700M` or `4G`, in bytes unless followed by K, M, G or T
+/// (powers of 1024).
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let (number, multiplier) = match text.chars().last()?.to_ascii_uppercase() {
+        'K' => (&text[..text.len() - 1], 1 << 10),
+        'M' => (&text[..text.len() - 1], 1 << 20),
+        'G' => (&text[..text.len() - 1], 1 << 30),
+        'T' => (&text[..text.len() - 1], 1 << 40),
+        _ => (text, 1),
+    };
+    let size = number.trim().parse::<u64>().ok()?.checked_mul(multiplier)?;
+    (size > 0).then_some(size)
+}
+
+/// The parts `path` would be split into, and the checksum file.
+pub fn part_paths(path: &Path, part_size: u64) -> io::Result<(Vec<PathBuf>, PathBuf)> {
+    let size = fs::metadata(path)?.len();
+    let count = size.div_ceil(part_size).max(1);
+    if count > MAX_PARTS {
+        return Err(io::Error::other(format!(
+            "'{}' would be split into more than {} parts",
+            file_name(path),
+            MAX_PARTS
+        )));
+    }
+    let parts = (1..=count).map(|number| part_path(path, number)).collect();
+    Ok((parts, with_suffix(path, ".sfv")))
+}
+
+/// Splits the file at `path` into parts of `part_size` bytes next to it.
+/// Returns the paths written, the checksum file last.
+pub fn split(path: &Path, part_size: u64) -> io::Result<Vec<PathBuf>> {
+    let (parts, checksums) = part_paths(path, part_size)?;
+    for existing in parts.iter().chain([&checksums]) {
+        if existing.symlink_metadata().is_ok() {
+            return Err(already_exists(existing));
+        }
+    }
+    let result = write_parts(path, part_size, &parts, &checksums);
+    let mut written = parts;
+    written.push(checksums);
+    if result.is_err() {
+        for path in &written {
+            let _ = fs::remove_file(path);
+        }
+    }
+    result.map(|_| written)
+}
+
+fn write_parts(path: &Path, part_size: u64, parts: &[PathBuf], checksums: &Path) -> io::Result<()> {
+    let mut source = fs::File::open(path)?;
+    let mut whole = Crc32::default();
+    let mut lines = vec![];
+    let mut buffer = vec![0; BUFFER_SIZE];
+    for part in parts {
+        let mut output = fs::File::create_new(part)?;
+        let mut crc = Crc32::default();
+        let mut remaining = part_size;
+        while remaining > 0 {
+            let wanted = remaining.min(BUFFER_SIZE as u64) as usize;
+            let read = source.read(&mut buffer[..wanted])?;
+            if read == 0 {
+                break;
+            }
+            output.write_all(&buffer[..read])?;
+            crc.update(&buffer[..read]);
+            whole.update(&buffer[..read]);
+            remaining -= read as u64;
+        }
+        lines.push(sfv_line(part, crc.finish()));
+    }
+    lines.push(sfv_line(path, whole.finish()));
+    fs::write(checksums, lines.join("\n") + "\n")
+}
+
+/// The file the parts of `part` (any of the numbered parts, or the checksum
+/// file) are joined into, if it's one of them.
+pub fn joined_path(part: &Path) -> Option<PathBuf> {
+    let name = part.file_name()?.to_str()?;
+    let (stem, extension) = name.rsplit_once('.')?;
+    let is_part = extension.len() == 3 && extension.chars().all(|char| char.is_ascii_digit());
+    (is_part || extension.eq_ignore_ascii_case("sfv")).then(|| part.with_file_name(stem))
+}
+
+/// Joins the numbered parts of `output` into it, checking them against the
+/// checksum file if there is one. Returns how many parts were joined, and
+/// whether they were checked. Nothing is left behind if a checksum is wrong.
+pub fn join(output: &Path) -> io::Result<(usize, bool)> {
+    if output.symlink_metadata().is_ok() {
+        return Err(already_exists(output));
+    }
+    let parts: Vec<PathBuf> = (1..)
+        .map(|number| part_path(output, number))
+        .take_while(|part| part.is_file())
+        .collect();
+    if parts.is_empty() {
+        return Err(io::Error::other(format!(
+            "no parts of '{}' found",
+            file_name(output)
+        )));
+    }
+    let checksums = fs::read_to_string(with_suffix(output, ".sfv"))
+        .ok()
+        .map(|text| parse_sfv(&text));
+    let result = join_parts(output, &parts, checksums.as_ref());
+    if result.is_err() {
+        let _ = fs::remove_file(output);
+    }
+    result.map(|_| (parts.len(), checksums.is_some()))
+}
+
+fn join_parts(
+    output: &Path,
+    parts: &[PathBuf],
+    checksums: Option<&HashMap<String, u32>>,
+) -> io::Result<()> {
+    let mut destination = fs::File::create_new(output)?;
+    let mut whole = Crc32::default();
+    let mut buffer = vec![0; BUFFER_SIZE];
+    for part in parts {
+        let mut source = fs::File::open(part)?;
+        let mut crc = Crc32::default();
+        loop {
+            let read = source.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            destination.write_all(&buffer[..read])?;
+            crc.update(&buffer[..read]);
+            whole.update(&buffer[..read]);
+        }
+        verify(part, crc, checksums)?;
+    }
+    verify(output, whole, checksums)
+}
+
+fn verify(path: &Path, crc: Crc32, checksums: Option<&HashMap<String, u32>>) -> io::Result<()> {
+    let expected = checksums.and_then(|checksums| checksums.get(&file_name(path)));
+    match expected {
+        Some(expected) if *expected != crc.finish() => Err(io::Error::other(format!(
+            "checksum of '{}' doesn't match, it may be corrupted",
+            file_name(path)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the `name checksum` lines of an `.sfv` file. Lines starting with `;`
+/// are comments.
+fn parse_sfv(text: &str) -> HashMap<String, u32> {
+    text.lines()
+        .filter(|line| !line.starts_with(';'))
+        .filter_map(|line| {
+            let (name, checksum) = line.trim_end().rsplit_once(' ')?;
+            Some((name.to_string(), u32::from_str_radix(checksum, 16).ok()?))
+        })
+        .collect()
+}
+
+fn sfv_line(path: &Path, crc: u32) -> String {
+    format!("{} {:08X}", file_name(path), crc)
+}
+
+fn part_path(path: &Path, number: u64) -> PathBuf {
+    with_suffix(path, &format!(".{:03}", number))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("'{}' already exists", file_name(path)),
+    )
+}
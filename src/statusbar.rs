@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Layout of the status bar. Each section is a template where placeholders like
+/// `{path}` are replaced by their current value:
+///
+/// * `{path}` - the current directory
+/// * `{mode}` - what's being done, like `FILTER` or `RENAME`, if anything
+/// * `{name}` - name of the selected item
+/// * `{size}` / `{modified}` / `{permissions}` - metadata of the selected item,
+///   once it's loaded
+/// * `{contents}` - item count and total size of the selected directory, once
+///   the cursor has rested on it
+/// * `{index}` / `{total}` - position of the selection, and number of items
+/// * `{filter}` - the active search or filter, if any
+/// * `{marked}` - how many items are marked, if any
+/// * `{dry-run}` - whether dry run mode is on
+/// * `{free}` - free space on the current drive
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StatusBar {
+    pub left: String,
+    pub right: String,
+    /// Whether the status bar has a line of its own above the bottom line, so it
+    /// stays shown while prompts and messages are.
+    pub own_line: bool,
+}
+impl Default for StatusBar {
+    fn default() -> Self {
+        StatusBar {
+            own_line: true,
+            left: "{mode} {path}".to_string(),
+            right: "{contents} {dry-run} {marked} {filter} {index}/{total}".to_string(),
+        }
+    }
+}
+
+/// Replaces every `{placeholder}` in `template` with its value from `lookup`.
+/// Unknown placeholders are kept as is. The gaps left by placeholders without a
+/// value are closed up.
+pub fn expand(template: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::new();
+    // whether the last placeholder was empty, leaving a gap to close
+    let mut gap = false;
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        push_text(&mut expanded, &rest[..start], &mut gap);
+        let Some(length) = rest[start..].find('}') else {
+            push_text(&mut expanded, &rest[start..], &mut gap);
+            return expanded;
+        };
+        let placeholder = &rest[start..start + length + 1];
+        match lookup(&placeholder[1..placeholder.len() - 1]) {
+            Some(value) if value.is_empty() => gap = true,
+            Some(value) => push_text(&mut expanded, &value, &mut gap),
+            None => push_text(&mut expanded, placeholder, &mut gap),
+        }
+        rest = &rest[start + length + 1..];
+    }
+    push_text(&mut expanded, rest, &mut gap);
+    if gap {
+        expanded.truncate(expanded.trim_end().len());
+    }
+    expanded
+}
+
+/// Appends `text` to `expanded`, dropping the whitespace that would be left
+/// doubled, or at the start, by an empty placeholder before it.
+fn push_text(expanded: &mut String, text: &str, gap: &mut bool) {
+    if text.is_empty() {
+        return;
+    }
+    if *gap {
+        if expanded.trim().is_empty() {
+            expanded.clear();
+        }
+        expanded.push_str(text.trim_start());
+        *gap = false;
+    } else {
+        expanded.push_str(text);
+    }
+}
+
+/// Puts `left` and `right` on either side of a line `width` characters wide.
+/// If they don't fit, `left` is cut short.
+pub fn compose(left: &str, right: &str, width: usize) -> String {
+    let right_width = right.chars().count();
+    if right_width >= width {
+        return right.chars().take(width).collect();
+    }
+    let left: String = left.chars().take(width - right_width - 1).collect();
+    let padding = width - right_width - left.chars().count();
+    format!("{}{}{}", left, " ".repeat(padding), right)
+}
+
+/// Returns the number of bytes available on the filesystem containing `path`.
+#[cfg(unix)]
+pub fn free_space(path: &Path) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is a valid C string and `stats` is a valid statvfs struct to fill in
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,357 @@
+//! Pretty-printing of structured data files (JSON, YAML and TOML) for previews.
+
+use std::{iter::Peekable, str::Chars};
+
+use crossterm::style::Color;
+
+use crate::preview::Line;
+
+const KEY_COLOR: Color = Color::Cyan;
+const STRING_COLOR: Color = Color::Green;
+const NUMBER_COLOR: Color = Color::Yellow;
+const LITERAL_COLOR: Color = Color::Magenta;
+const COMMENT_COLOR: Color = Color::DarkGrey;
+
+/// How deep arrays and objects may nest before a file is previewed as plain
+/// text instead, as each level is parsed and drawn by another call deeper.
+const MAX_DEPTH: usize = 256;
+
+enum Json {
+    Literal(String),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// Pretty-prints `text` as JSON, keeping the key order of the file.
+/// Arrays longer than `fold` are cut short. Returns `None` if `text` isn't valid JSON,
+/// or nests too deep.
+pub fn json(text: &str, fold: Option<usize>) -> Option<Vec<Line>> {
+    let mut chars = text.chars().peekable();
+    let value = parse_value(&mut chars, 0)?;
+    skip_whitespace(&mut chars);
+    if chars.peek().is_some() {
+        return None;
+    }
+    let mut lines = vec![];
+    render_json(&value, 0, Line::default(), false, fold, &mut lines);
+    Some(lines)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|char| char.is_whitespace()).is_some() {}
+}
+
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Option<Json> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    skip_whitespace(chars);
+    match chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut entries = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Some(Json::Object(entries));
+            }
+            loop {
+                skip_whitespace(chars);
+                if chars.next()? != '"' {
+                    return None;
+                }
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                if chars.next()? != ':' {
+                    return None;
+                }
+                entries.push((key, parse_value(chars, depth + 1)?));
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    '}' => return Some(Json::Object(entries)),
+                    _ => return None,
+                }
+            }
+        }
+        '[' => {
+            chars.next();
+            let mut values = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Some(Json::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars, depth + 1)?);
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    ']' => return Some(Json::Array(values)),
+                    _ => return None,
+                }
+            }
+        }
+        '"' => {
+            chars.next();
+            Some(Json::String(parse_string(chars)?))
+        }
+        char if char.is_ascii_digit() || *char == '-' => {
+            let mut number = String::new();
+            while let Some(char) =
+                chars.next_if(|char| char.is_ascii_digit() || "+-.eE".contains(*char))
+            {
+                number.push(char);
+            }
+            Some(Json::Number(number))
+        }
+        _ => {
+            let mut literal = String::new();
+            while let Some(char) = chars.next_if(|char| char.is_ascii_alphabetic()) {
+                literal.push(char);
+            }
+            matches!(literal.as_str(), "true" | "false" | "null").then_some(Json::Literal(literal))
+        }
+    }
+}
+
+/// Parses the rest of a string whose opening quote has already been consumed.
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut string = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(string),
+            '\\' => match chars.next()? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                'b' => string.push('\u{8}'),
+                'f' => string.push('\u{c}'),
+                'u' => {
+                    let mut code = hex_escape(chars)?;
+                    if (0xd800..0xdc00).contains(&code) {
+                        // a high surrogate, combined with the low one escaped after it
+                        let mut after = chars.clone();
+                        if after.next() == Some('\\') && after.next() == Some('u') {
+                            if let Some(low @ 0xdc00..0xe000) = hex_escape(&mut after) {
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                                *chars = after;
+                            }
+                        }
+                    }
+                    string.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                char => string.push(char),
+            },
+            char => string.push(char),
+        }
+    }
+}
+
+/// Reads the four hex digits after `\u`.
+fn hex_escape(chars: &mut Peekable<Chars>) -> Option<u32> {
+    let code: String = (0..4).filter_map(|_| chars.next()).collect();
+    u32::from_str_radix(&code, 16).ok()
+}
+
+fn quote(string: &str) -> String {
+    serde_json::to_string(string).unwrap_or_default()
+}
+
+fn render_json(
+    value: &Json,
+    indent: usize,
+    mut line: Line,
+    comma: bool,
+    fold: Option<usize>,
+    lines: &mut Vec<Line>,
+) {
+    let comma = if comma { "," } else { "" };
+    let padding = "  ".repeat(indent);
+    match value {
+        Json::Literal(literal) => {
+            line.push(literal, LITERAL_COLOR);
+            line.push(comma, Color::Reset);
+            lines.push(line);
+        }
+        Json::Number(number) => {
+            line.push(number, NUMBER_COLOR);
+            line.push(comma, Color::Reset);
+            lines.push(line);
+        }
+        Json::String(string) => {
+            line.push(&quote(string), STRING_COLOR);
+            line.push(comma, Color::Reset);
+            lines.push(line);
+        }
+        Json::Array(values) if values.is_empty() => {
+            line.push(&format!("[]{}", comma), Color::Reset);
+            lines.push(line);
+        }
+        Json::Object(entries) if entries.is_empty() => {
+            line.push(&format!("{{}}{}", comma), Color::Reset);
+            lines.push(line);
+        }
+        Json::Array(values) => {
+            line.push("[", Color::Reset);
+            lines.push(line);
+            let shown = fold.unwrap_or(usize::MAX).min(values.len());
+            for (index, value) in values.iter().take(shown).enumerate() {
+                let child = Line::plain(&format!("{}  ", padding));
+                render_json(value, indent + 1, child, index + 1 < shown, fold, lines);
+            }
+            if shown < values.len() {
+                let mut folded = Line::plain(&format!("{}  ", padding));
+                folded.push(
+                    &format!("… {} more items", values.len() - shown),
+                    COMMENT_COLOR,
+                );
+                lines.push(folded);
+            }
+            lines.push(Line::plain(&format!("{}]{}", padding, comma)));
+        }
+        Json::Object(entries) => {
+            line.push("{", Color::Reset);
+            lines.push(line);
+            for (index, (key, value)) in entries.iter().enumerate() {
+                let mut child = Line::plain(&format!("{}  ", padding));
+                child.push(&quote(key), KEY_COLOR);
+                child.push(": ", Color::Reset);
+                render_json(
+                    value,
+                    indent + 1,
+                    child,
+                    index + 1 < entries.len(),
+                    fold,
+                    lines,
+                );
+            }
+            lines.push(Line::plain(&format!("{}}}{}", padding, comma)));
+        }
+    }
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Colors the keys and comments of a YAML document, folding sequences with more
+/// than `fold` items.
+pub fn yaml(text: &str, fold: Option<usize>) -> Vec<Line> {
+    let fold = fold.unwrap_or(usize::MAX);
+    let mut lines = vec![];
+    // indentation of the sequence being counted, and how many items it has had
+    let mut sequence: Option<(usize, usize)> = None;
+    // how many items have been folded away, and their indentation
+    let mut hidden = (0, 0);
+
+    for text in text.lines() {
+        let indent = indentation(text);
+        let trimmed = text.trim_start();
+        let is_item = trimmed.starts_with("- ") || trimmed == "-";
+
+        if let Some((sequence_indent, count)) = sequence {
+            let nested = indent > sequence_indent || trimmed.is_empty();
+            if is_item && indent == sequence_indent {
+                sequence = Some((sequence_indent, count + 1));
+            } else if !nested {
+                sequence = None;
+            }
+        }
+        if sequence.is_none() && is_item {
+            sequence = Some((indent, 1));
+        }
+        match sequence {
+            Some((sequence_indent, count)) if count > fold => {
+                if is_item && indent == sequence_indent {
+                    hidden = (hidden.0 + 1, sequence_indent);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if hidden.0 > 0 {
+            lines.push(folded_line(hidden));
+            hidden = (0, 0);
+        }
+        lines.push(yaml_line(text));
+    }
+    if hidden.0 > 0 {
+        lines.push(folded_line(hidden));
+    }
+    lines
+}
+
+fn folded_line((hidden, indent): (usize, usize)) -> Line {
+    let mut line = Line::plain(&" ".repeat(indent));
+    line.push(&format!("… {} more items", hidden), COMMENT_COLOR);
+    line
+}
+
+fn yaml_line(text: &str) -> Line {
+    let trimmed = text.trim_start();
+    let indent = &text[..text.len() - trimmed.len()];
+    if trimmed.starts_with('#') {
+        let mut line = Line::plain(indent);
+        line.push(trimmed, COMMENT_COLOR);
+        return line;
+    }
+    let (marker, rest) = match trimmed.strip_prefix("- ") {
+        Some(rest) => ("- ", rest),
+        None => ("", trimmed),
+    };
+    let mut line = Line::plain(&format!("{}{}", indent, marker));
+    match rest.find(':') {
+        Some(colon)
+            if !rest.starts_with(['"', '\'', '[', '{'])
+                && rest[colon + 1..]
+                    .chars()
+                    .next()
+                    .is_none_or(|char| char == ' ') =>
+        {
+            line.push(&rest[..colon], KEY_COLOR);
+            line.push(":", Color::Reset);
+            push_scalar(&mut line, &rest[colon + 1..]);
+        }
+        _ => push_scalar(&mut line, rest),
+    }
+    line
+}
+
+/// Colors the keys, section headers and comments of a TOML document.
+pub fn toml(text: &str) -> Vec<Line> {
+    text.lines()
+        .map(|text| {
+            let trimmed = text.trim_start();
+            let indent = &text[..text.len() - trimmed.len()];
+            let mut line = Line::plain(indent);
+            if trimmed.starts_with('#') {
+                line.push(trimmed, COMMENT_COLOR);
+            } else if trimmed.starts_with('[') {
+                line.push(trimmed, KEY_COLOR);
+            } else if let Some(equals) = trimmed.find('=') {
+                line.push(&trimmed[..equals], KEY_COLOR);
+                line.push("=", Color::Reset);
+                push_scalar(&mut line, &trimmed[equals + 1..]);
+            } else {
+                line.push(trimmed, Color::Reset);
+            }
+            line
+        })
+        .collect()
+}
+
+/// Pushes a value, colored by whether it looks like a string, number or literal.
+fn push_scalar(line: &mut Line, value: &str) {
+    let trimmed = value.trim();
+    let color = if trimmed.starts_with(['"', '\'']) {
+        STRING_COLOR
+    } else if trimmed.parse::<f64>().is_ok() {
+        NUMBER_COLOR
+    } else if matches!(trimmed, "true" | "false" | "null" | "~") {
+        LITERAL_COLOR
+    } else {
+        Color::Reset
+    };
+    line.push(value, color);
+}
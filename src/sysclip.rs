@@ -0,0 +1,120 @@
+//! Copying text to the system clipboard, through whichever clipboard tool is
+//! installed, or else the terminal (OSC 52), which also works over ssh. Text
+//! can be pasted back only through a tool, as few terminals answer OSC 52
+//! queries.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::graphics;
+
+const TOOLS: &[&[&str]] = &[
+    &["pbcopy"],
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["clip.exe"],
+];
+
+const PASTE_TOOLS: &[&[&str]] = &[
+    &["pbpaste"],
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-out"],
+    &["xsel", "--clipboard", "--output"],
+    &["powershell.exe", "-NoProfile", "-Command", "Get-Clipboard"],
+];
+
+/// Copies `text` with the first tool that works, or else by asking `terminal`.
+pub fn copy(text: &str, terminal: &mut impl Write) -> io::Result<()> {
+    for tool in TOOLS {
+        if copy_with(tool, text).is_ok() {
+            return Ok(());
+        }
+    }
+    write!(
+        terminal,
+        "\x1b]52;c;{}\x07",
+        graphics::base64(text.as_bytes())
+    )?;
+    terminal.flush()
+}
+
+fn copy_with(tool: &[&str], text: &str) -> io::Result<()> {
+    let mut child = Command::new(tool[0])
+        .args(&tool[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} failed", tool[0])))
+    }
+}
+
+/// The contents of the system clipboard, from the first tool that works.
+pub fn paste() -> io::Result<Vec<u8>> {
+    for tool in PASTE_TOOLS {
+        let Ok(output) = Command::new(tool[0])
+            .args(&tool[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+        else {
+            continue;
+        };
+        if output.status.success() {
+            return Ok(output.stdout);
+        }
+    }
+    Err(io::Error::other("no clipboard tool found"))
+}
+
+/// Returns `path` as a percent-encoded `file://` URI, like `file:///C:/My%20Files`
+/// on Windows.
+pub fn file_uri(path: &Path) -> String {
+    if cfg!(windows) {
+        let path = path.to_string_lossy().replace('\\', "/");
+        // canonicalized paths have a \\?\ prefix
+        let path = match path.strip_prefix("//?/") {
+            Some(rest) => match rest.strip_prefix("UNC/") {
+                Some(unc) => format!("//{}", unc),
+                None => rest.to_string(),
+            },
+            None => path,
+        };
+        // UNC paths (\\server\share) name the host
+        if let Some(rest) = path.strip_prefix("//") {
+            return format!("file://{}", percent_encode(rest.as_bytes()));
+        }
+        return format!("file:///{}", percent_encode(path.as_bytes()));
+    }
+    // names that aren't valid UTF-8 are encoded byte for byte
+    #[cfg(unix)]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()).to_vec();
+    #[cfg(not(unix))]
+    let bytes = path.to_string_lossy().as_bytes().to_vec();
+    format!("file://{}", percent_encode(&bytes))
+}
+
+pub fn percent_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for &byte in bytes {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else if byte == b':' && cfg!(windows) {
+            // the colon after the drive letter
+            encoded.push(':');
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
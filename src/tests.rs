@@ -0,0 +1,1081 @@
+//! Tests driving the whole program: keys are typed into it and the screen it
+//! draws in memory, and the files it leaves behind, are checked.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{
+    backend::TestBackend,
+    bookmarks::{self, Bookmark},
+    clipboard, dirstat, finder, gitlog, keymap, preview, properties, replay, shared,
+    theme::BorderStyle,
+    trash, Config, Fee, Mode,
+};
+
+const WIDTH: u16 = 60;
+const HEIGHT: u16 = 12;
+
+/// A directory for a test to play in, removed once it's done.
+struct Playground(PathBuf);
+impl Playground {
+    /// Makes a directory with the `files` and the directories (ending in `/`)
+    /// listed.
+    fn new(files: &[&str]) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "fee-test-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let root = std::env::temp_dir().join(name);
+        fs::create_dir_all(&root).unwrap();
+        for file in files {
+            match file.strip_suffix('/') {
+                Some(directory) => fs::create_dir_all(root.join(directory)).unwrap(),
+                None => fs::write(root.join(file), file).unwrap(),
+            }
+        }
+        Playground(root.canonicalize().unwrap())
+    }
+    fn path(&self, path: &str) -> PathBuf {
+        self.0.join(path)
+    }
+}
+impl Drop for Playground {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn key(code: KeyCode) -> Event {
+    Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+fn alt(code: KeyCode) -> Event {
+    Event::Key(KeyEvent::new(code, KeyModifiers::ALT))
+}
+
+fn control(char: char) -> Event {
+    Event::Key(KeyEvent::new(KeyCode::Char(char), KeyModifiers::CONTROL))
+}
+
+/// Typing `text`, a key per character.
+fn typed(text: &str) -> Vec<Event> {
+    text.chars().map(|char| key(KeyCode::Char(char))).collect()
+}
+
+fn config() -> Config {
+    let mut config = Config::default_config();
+    // previews are made in the background, so whether they're drawn yet varies
+    config.show_preview = false;
+    config.use_trash = false;
+    config
+}
+
+/// Runs fee in `directory` until it's typed all of `events`, returning it along
+/// with the last screen it drew.
+fn run(directory: &Path, config: Config, events: Vec<Event>) -> (Fee, Vec<String>) {
+    run_sized(directory, config, events, WIDTH, HEIGHT)
+}
+
+/// Like `run`, on a screen of another size.
+fn run_sized(
+    directory: &Path,
+    config: Config,
+    events: Vec<Event>,
+    width: u16,
+    height: u16,
+) -> (Fee, Vec<String>) {
+    let backend = TestBackend::new(width, height);
+    let mut fee = Fee::new(
+        directory.to_path_buf(),
+        config,
+        Box::new(backend.clone()),
+        Box::new(events.into_iter()),
+    );
+    fee.remote = None;
+    fee.graphics = None;
+    fee.listen().unwrap();
+    (fee, backend.last_frame())
+}
+
+/// Whether a row of `screen` starts with `text`.
+fn shows(screen: &[String], text: &str) -> bool {
+    screen.iter().any(|row| row.starts_with(text))
+}
+
+#[test]
+fn lists_directories_before_files() {
+    let playground = Playground::new(&["b.txt", "a.txt", "z/"]);
+    let (_, screen) = run(&playground.0, config(), vec![]);
+    assert_eq!(&screen[..3], ["z", "a.txt", "b.txt"]);
+}
+
+#[test]
+fn leaves_out_ignored_entries_while_showing_hidden_ones() {
+    let playground = Playground::new(&["kept.txt", "target/"]);
+    fs::write(playground.path(".gitignore"), "target/\n").unwrap();
+    let mut config = config();
+    config.respect_ignore_files = true;
+    let (fee, screen) = run(&playground.0, config, vec![]);
+    assert!(fee.view.show_hidden);
+    assert_eq!(&screen[..3], [".gitignore", "kept.txt", ""]);
+}
+
+#[test]
+fn shows_empty_directories() {
+    let playground = Playground::new(&[]);
+    let (_, screen) = run(&playground.0, config(), vec![]);
+    assert!(shows(&screen, "<empty directory>"));
+}
+
+#[test]
+fn enters_and_leaves_directories() {
+    let playground = Playground::new(&["inner/", "inner/file.txt", "outer.txt"]);
+    let (fee, screen) = run(&playground.0, config(), vec![key(KeyCode::Enter)]);
+    assert_eq!(fee.cwd, playground.path("inner"));
+    assert!(shows(&screen, "file.txt"));
+
+    let events = vec![key(KeyCode::Enter), key(KeyCode::Left)];
+    let (fee, screen) = run(&playground.0, config(), events);
+    assert_eq!(fee.cwd, playground.0);
+    assert!(shows(&screen, "outer.txt"));
+}
+
+#[test]
+fn moves_the_selection() {
+    let playground = Playground::new(&["a", "b", "c"]);
+    let events = vec![key(KeyCode::Down), key(KeyCode::Down)];
+    let (fee, _) = run(&playground.0, config(), events);
+    assert_eq!(fee.selected_item().unwrap().name, "c");
+
+    // going past the end wraps around
+    let (fee, _) = run(&playground.0, config(), vec![key(KeyCode::Up)]);
+    assert_eq!(fee.selected_item().unwrap().name, "c");
+}
+
+#[test]
+fn filters_the_listing() {
+    let playground = Playground::new(&["apple", "banana", "cherry"]);
+    let (_, screen) = run(&playground.0, config(), typed("/an"));
+    assert!(shows(&screen, "banana"));
+    assert!(!shows(&screen, "apple"));
+    assert!(!shows(&screen, "cherry"));
+
+    // the match stays selected once the filter is done with
+    let mut events = typed("/an");
+    events.push(key(KeyCode::Enter));
+    let (fee, screen) = run(&playground.0, config(), events);
+    assert_eq!(fee.selected_item().unwrap().name, "banana");
+    assert!(shows(&screen, "apple"));
+}
+
+#[test]
+fn goes_back_and_forward_through_history() {
+    let playground = Playground::new(&["one/", "two/"]);
+    let events = vec![key(KeyCode::Down), key(KeyCode::Enter), alt(KeyCode::Left)];
+    let (fee, _) = run(&playground.0, config(), events);
+    assert_eq!(fee.cwd, playground.0);
+    // what was selected is selected again
+    assert_eq!(fee.selected_item().unwrap().name, "two");
+
+    let events = vec![key(KeyCode::Enter), alt(KeyCode::Left), alt(KeyCode::Right)];
+    let (fee, _) = run(&playground.0, config(), events);
+    assert_eq!(fee.cwd, playground.path("one"));
+}
+
+#[test]
+fn goes_to_completed_paths() {
+    let playground = Playground::new(&["inner/", "inner/file.txt", "other/"]);
+    let mut events = vec![control('g')];
+    events.extend(typed("inn"));
+    events.push(key(KeyCode::Tab));
+    events.extend(typed("f"));
+    events.push(key(KeyCode::Tab));
+    events.push(key(KeyCode::Enter));
+    let (fee, _) = run(&playground.0, config(), events);
+    assert_eq!(fee.cwd, playground.path("inner"));
+    assert_eq!(fee.selected_item().unwrap().name, "file.txt");
+}
+
+/// Typing `line` on the command line, completing it with tab where it has a
+/// `\t`, and running it.
+fn command_line(line: &str) -> Vec<Event> {
+    let mut events = vec![key(KeyCode::Char(':'))];
+    for part in line.split_inclusive('\t') {
+        match part.strip_suffix('\t') {
+            Some(part) => {
+                events.extend(typed(part));
+                events.push(key(KeyCode::Tab));
+            }
+            None => events.extend(typed(part)),
+        }
+    }
+    events.push(key(KeyCode::Enter));
+    events
+}
+
+#[test]
+fn completes_the_choices_commands_take() {
+    let playground = Playground::new(&["inner/", "a.txt", "b.txt"]);
+    let mut config = config();
+    config.bookmarks = vec![Bookmark {
+        name: "inside".to_string(),
+        path: playground.path("inner").to_string_lossy().to_string(),
+        key: None,
+        group: None,
+    }];
+    let mut events = command_line("sort e\t");
+    events.push(key(KeyCode::Down));
+    events.push(key(KeyCode::Char(' ')));
+    events.extend(command_line("save-marks picked"));
+    events.extend(command_line("unmark"));
+    events.extend(command_line("restore-marks p\t"));
+    events.extend(command_line("jump ins\t"));
+    let (fee, _) = run(&playground.0, config, events);
+    assert_eq!(fee.view.sort_by.name(), "extension");
+    assert_eq!(fee.marks.len(), 1);
+    assert_eq!(fee.cwd, playground.path("inner"));
+}
+
+#[test]
+fn writes_the_last_directory_on_exit() {
+    let playground = Playground::new(&["inner/"]);
+    let chosen = playground.path("chosen");
+    let backend = TestBackend::new(WIDTH, HEIGHT);
+    let mut fee = Fee::new(
+        playground.0.clone(),
+        config(),
+        Box::new(backend),
+        Box::new(vec![key(KeyCode::Enter)].into_iter()),
+    );
+    fee.remote = None;
+    fee.choose_dir = Some(chosen.clone());
+    fee.listen().unwrap();
+    assert_eq!(
+        fs::read_to_string(chosen).unwrap(),
+        format!("{}\n", playground.path("inner").display())
+    );
+}
+
+#[test]
+fn creates_files() {
+    let playground = Playground::new(&[]);
+    let mut events = typed("nnew.txt");
+    events.push(key(KeyCode::Enter));
+    let (_, screen) = run(&playground.0, config(), events);
+    assert!(playground.path("new.txt").is_file());
+    assert!(shows(&screen, "new.txt"));
+}
+
+#[test]
+fn creates_files_only_inside_the_current_directory() {
+    let playground = Playground::new(&["inside/"]);
+    let outside = playground.path("outside.txt");
+    let mut events = typed(&format!("n{}", outside.display()));
+    events.push(key(KeyCode::Enter));
+    let (_, screen) = run(&playground.path("inside"), config(), events);
+    assert!(!outside.exists());
+    assert!(screen.last().unwrap().starts_with("invalid name"));
+}
+
+#[test]
+fn renames_files() {
+    let playground = Playground::new(&["old.txt"]);
+    // the cursor starts before the extension
+    let mut events = vec![key(KeyCode::Char('a'))];
+    events.extend((0.."old".len()).map(|_| key(KeyCode::Backspace)));
+    events.extend(typed("renamed"));
+    events.push(key(KeyCode::Enter));
+    run(&playground.0, config(), events);
+    assert!(!playground.path("old.txt").exists());
+    assert_eq!(
+        fs::read_to_string(playground.path("renamed.txt")).unwrap(),
+        "old.txt"
+    );
+}
+
+#[test]
+fn renames_with_patterns_that_backtrack_badly() {
+    let playground = Playground::new(&["aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa.txt"]);
+    let mut events = vec![key(KeyCode::Char('R')), control('r')];
+    events.extend(typed(r"^(a|a)*(\.txt)"));
+    events.push(key(KeyCode::Tab));
+    events.extend(typed("b$2"));
+    events.push(key(KeyCode::Enter));
+    run(&playground.0, config(), events);
+    assert!(playground.path("b.txt").exists());
+
+    let mut events = vec![key(KeyCode::Char('R')), control('r')];
+    events.extend(typed("(b"));
+    let (_, screen) = run(&playground.0, config(), events);
+    assert!(shows(&screen, "invalid regex: unclosed group"));
+}
+
+#[test]
+fn pads_numbers_no_wider_than_a_name_can_be() {
+    let playground = Playground::new(&["photo.jpg"]);
+    let mut events = vec![key(KeyCode::Char('R')), control('r'), control('r')];
+    events.extend(typed("{n:999999999}"));
+    events.push(key(KeyCode::Enter));
+    run(&playground.0, config(), events);
+    let renamed = format!("{:0255}", 1);
+    assert!(playground.path(&renamed).exists());
+}
+
+#[test]
+fn previews_json_nested_too_deep_as_text() {
+    let playground = Playground::new(&[]);
+    fs::write(playground.path("deep.json"), "[".repeat(200_000)).unwrap();
+    let (_, screen) = run(&playground.0, config(), typed("v"));
+    assert!(screen.iter().any(|row| row.contains("[[[[[[[[")));
+}
+
+#[test]
+fn previews_json_escaped_surrogate_pairs_as_one_character() {
+    let playground = Playground::new(&[]);
+    fs::write(
+        playground.path("smile.json"),
+        r#"["\ud83d\ude00 ok", "\ud83d alone"]"#,
+    )
+    .unwrap();
+    let (_, screen) = run(&playground.0, config(), typed("v"));
+    assert!(screen.iter().any(|row| row.contains("\"\u{1f600} ok\"")));
+    assert!(screen.iter().any(|row| row.contains("\"\u{fffd} alone\"")));
+}
+
+#[test]
+fn deletes_only_once_confirmed() {
+    let playground = Playground::new(&["doomed.txt"]);
+    run(&playground.0, config(), typed("Dn"));
+    assert!(playground.path("doomed.txt").exists());
+
+    let (_, screen) = run(&playground.0, config(), typed("Dy"));
+    assert!(!playground.path("doomed.txt").exists());
+    assert!(shows(&screen, "<empty directory>"));
+}
+
+#[cfg(unix)]
+#[test]
+fn refuses_to_copy_directories_into_themselves_through_links() {
+    let playground = Playground::new(&["real/", "real/dir/"]);
+    let link = playground.path("link");
+    std::os::unix::fs::symlink(playground.path("real"), &link).unwrap();
+    let mut events = typed(":copy dir");
+    events.push(key(KeyCode::Enter));
+    run(&link, config(), events);
+    assert!(!playground.path("real/dir/dir").exists());
+    assert!(fs::read_dir(playground.path("real/dir"))
+        .unwrap()
+        .next()
+        .is_none());
+}
+
+#[test]
+fn starts_over_copies_left_over_from_something_else() {
+    let playground = Playground::new(&[
+        "source/",
+        "source/kept.txt",
+        "target/",
+        "target/source.fee-part/",
+        "target/source.fee-part/stale.txt",
+    ]);
+    let mut events = typed(":copy target");
+    events.push(key(KeyCode::Enter));
+    run(&playground.0, config(), events);
+    let copied: Vec<_> = fs::read_dir(playground.path("target/source"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(copied, ["kept.txt"]);
+    assert_eq!(fs::read_dir(playground.path("target")).unwrap().count(), 1);
+}
+
+#[test]
+fn refuses_to_split_into_more_than_999_parts() {
+    let playground = Playground::new(&[]);
+    fs::write(playground.path("big.bin"), [0; 1000]).unwrap();
+    let mut events = typed(":split 1");
+    events.push(key(KeyCode::Enter));
+    let (_, screen) = run(&playground.0, config(), events);
+    assert_eq!(
+        screen.last().unwrap().trim_end(),
+        "error: 'big.bin' would be split into more than 999 parts"
+    );
+    assert_eq!(fs::read_dir(&playground.0).unwrap().count(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn goes_to_links_to_files_where_the_links_are() {
+    let playground = Playground::new(&["real/", "real/target.txt"]);
+    let link = playground.path("link.txt");
+    std::os::unix::fs::symlink(playground.path("real/target.txt"), &link).unwrap();
+    let mut events = typed(":cd ../link.txt");
+    events.push(key(KeyCode::Enter));
+    let (fee, _) = run(&playground.path("real"), config(), events);
+    assert_eq!(fee.cwd, playground.0);
+    assert_eq!(fee.selected_item().unwrap().path, link);
+}
+
+#[test]
+fn restores_saved_marks_where_they_were_moved() {
+    let playground = Playground::new(&["dir/", "dir/marked"]);
+    let mut events = vec![
+        key(KeyCode::Enter),
+        key(KeyCode::Char(' ')),
+        key(KeyCode::Left),
+    ];
+    events.extend(typed(":save-marks kept"));
+    events.push(key(KeyCode::Enter));
+    events.extend(typed(":unmark"));
+    events.push(key(KeyCode::Enter));
+    // renaming the directory takes what's inside along
+    events.push(key(KeyCode::Char('a')));
+    events.extend((0.."dir".len()).map(|_| key(KeyCode::Backspace)));
+    events.extend(typed("moved"));
+    events.push(key(KeyCode::Enter));
+    events.extend(typed(":restore-marks kept"));
+    events.push(key(KeyCode::Enter));
+    let (fee, _) = run(&playground.0, config(), events);
+    let marked: Vec<&PathBuf> = fee.marks.iter().collect();
+    assert_eq!(marked, [&playground.path("moved/marked")]);
+}
+
+#[test]
+fn copies_to_the_other_pane() {
+    let playground = Playground::new(&["target/", "file.txt"]);
+    let events = vec![
+        // the other pane starts out in the same directory, so it's sent into
+        // the target first
+        key(KeyCode::Char('T')),
+        key(KeyCode::Tab),
+        key(KeyCode::Enter),
+        key(KeyCode::Tab),
+        key(KeyCode::Down),
+        key(KeyCode::F(5)),
+    ];
+    run(&playground.0, config(), events);
+    assert!(playground.path("file.txt").is_file());
+    assert_eq!(
+        fs::read_to_string(playground.path("target/file.txt")).unwrap(),
+        "file.txt"
+    );
+}
+
+#[test]
+fn replays_keys() {
+    let playground = Playground::new(&["inner/", "inner/file.txt", "other/"]);
+    let steps = replay::parse("<down><enter><10ms><lt>").unwrap();
+    let events = replay::Replay::new(steps, Box::new(std::iter::empty()));
+    let backend = TestBackend::new(WIDTH, HEIGHT);
+    let mut fee = Fee::new(
+        playground.0.clone(),
+        config(),
+        Box::new(backend.clone()),
+        Box::new(events),
+    );
+    fee.remote = None;
+    fee.listen().unwrap();
+    assert_eq!(fee.cwd, playground.path("other"));
+
+    assert!(replay::parse("<c-p><a-left><f5><1.5s>").is_ok());
+    assert!(replay::parse("<nonsense>").is_err());
+    for wait in ["<-1s>", "<infs>", "<nans>", "<1e400s>"] {
+        assert!(replay::parse(wait).is_err(), "{}", wait);
+    }
+}
+
+#[test]
+fn shows_one_pane_when_too_narrow_for_two() {
+    let playground = Playground::new(&["notes.txt"]);
+    for border_style in [BorderStyle::Off, BorderStyle::Rounded] {
+        let mut config = config();
+        config.theme.border_style = border_style;
+        let events = vec![key(KeyCode::Char('T'))];
+        let (_, screen) = run_sized(&playground.0, config, events, 30, HEIGHT);
+        // listed where the pane's title would be
+        let first = if border_style == BorderStyle::Off {
+            0
+        } else {
+            1
+        };
+        assert!(screen[first].contains("notes.txt"), "{:?}", screen);
+    }
+}
+
+#[test]
+fn draws_on_screens_too_small_for_the_bottom_rows() {
+    let playground = Playground::new(&["notes.txt"]);
+    for height in 0..=4 {
+        let mut config = config();
+        config.status_bar.own_line = true;
+        config.accessible = height % 2 == 0;
+        let mut events = vec![key(KeyCode::Down)];
+        // with a completion menu above the command line
+        events.extend(typed(":s"));
+        events.push(key(KeyCode::Tab));
+        run_sized(&playground.0, config, events, WIDTH, height);
+    }
+}
+
+#[test]
+fn fills_in_the_status_bar_as_written() {
+    let playground = Playground::new(&["two  spaces.txt"]);
+    let mut config = config();
+    config.status_bar.own_line = true;
+    config.status_bar.left = "{mode} {name}  is {filter} or {unclosed".to_string();
+    let (_, screen) = run(&playground.0, config, vec![]);
+    assert!(
+        shows(&screen, "two  spaces.txt  is or {unclosed "),
+        "{:?}",
+        screen
+    );
+}
+
+#[test]
+fn writes_the_chosen_file_instead_of_opening_it() {
+    let playground = Playground::new(&["picked.txt"]);
+    let chosen = playground.path("chosen");
+    let backend = TestBackend::new(WIDTH, HEIGHT);
+    let mut fee = Fee::new(
+        playground.0.clone(),
+        config(),
+        Box::new(backend),
+        Box::new(vec![key(KeyCode::Enter)].into_iter()),
+    );
+    fee.remote = None;
+    fee.choose_file = Some(chosen.clone());
+    fee.listen().unwrap();
+    assert_eq!(
+        fs::read_to_string(chosen).unwrap(),
+        format!("{}\n", playground.path("picked.txt").display())
+    );
+}
+
+#[test]
+fn saves_bookmarks_in_the_profile_they_came_from() {
+    let playground = Playground::new(&["project/"]);
+    let profile = playground.path("work.json");
+    let work = Bookmark {
+        name: "work".to_string(),
+        path: "/work".to_string(),
+        key: Some('w'),
+        group: None,
+    };
+    let contents = serde_json::json!({ "theme": {}, "bookmarks": [work] });
+    fs::write(&profile, contents.to_string()).unwrap();
+    let mut config = config();
+    config.bookmarks = vec![work.clone()];
+    let mut fee = Fee::new(
+        playground.path("project"),
+        config,
+        Box::new(TestBackend::new(WIDTH, HEIGHT)),
+        Box::new(vec![key(KeyCode::Char('b'))].into_iter()),
+    );
+    fee.remote = None;
+    fee.bookmarks_file = Some(profile.clone());
+    fee.listen().unwrap();
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(profile).unwrap()).unwrap();
+    let saved: Vec<Bookmark> = serde_json::from_value(saved["bookmarks"].clone()).unwrap();
+    assert_eq!(saved, fee.config.bookmarks);
+    assert_eq!(saved.len(), 2);
+    assert_eq!(saved[0], work);
+    assert_eq!(saved[1].name, "project");
+}
+
+#[test]
+fn merges_bookmarks_saved_by_other_instances() {
+    let bookmark = |name: &str, key: char| Bookmark {
+        name: name.to_string(),
+        path: format!("/{}", name),
+        key: Some(key),
+        group: None,
+    };
+    let saved = [bookmark("kept", 'k'), bookmark("gone", 'g')];
+    // this instance removed one and bookmarked a directory as 1, while another
+    // bookmarked a different one as 1 too
+    let ours = [bookmark("kept", 'k'), bookmark("ours", '1')];
+    let theirs = [
+        bookmark("kept", 'k'),
+        bookmark("gone", 'g'),
+        bookmark("theirs", '1'),
+    ];
+    let merged = bookmarks::merge(&saved, &ours, &theirs);
+    assert_eq!(
+        merged,
+        [
+            bookmark("kept", 'k'),
+            bookmark("ours", '1'),
+            bookmark("theirs", '2')
+        ]
+    );
+}
+
+#[test]
+fn opens_files_with_associated_commands() {
+    let playground = Playground::new(&["notes.txt"]);
+    let mut config = config();
+    let copy = playground.path("opened").to_string_lossy().to_string();
+    config.associations.insert(
+        "*.txt".to_string(),
+        vec!["cp".to_string(), "$f".to_string(), copy],
+    );
+    config
+        .associations
+        .insert("png".to_string(), vec!["false".to_string()]);
+    run(&playground.0, config, vec![key(KeyCode::Enter)]);
+    assert_eq!(
+        fs::read_to_string(playground.path("opened")).unwrap(),
+        "notes.txt"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn opens_files_whose_names_arent_utf8() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let playground = Playground::new(&[]);
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    fs::write(playground.0.join(name), "latin-1").unwrap();
+    let mut config = config();
+    let copy = playground.path("opened").to_string_lossy().to_string();
+    config.text_editor_command = vec!["cp".to_string(), "$f".to_string(), copy];
+    run(&playground.0, config, vec![key(KeyCode::Enter)]);
+    assert_eq!(
+        fs::read_to_string(playground.path("opened")).unwrap(),
+        "latin-1"
+    );
+}
+
+#[test]
+fn opens_compressed_files_decompressed() {
+    let playground = Playground::new(&["notes.txt"]);
+    let compressed = std::process::Command::new("gzip")
+        .arg(playground.path("notes.txt"))
+        .status();
+    if !compressed.is_ok_and(|status| status.success()) {
+        return;
+    }
+    let mut config = config();
+    let copy = playground.path("opened").to_string_lossy().to_string();
+    config.associations.insert(
+        "*.txt".to_string(),
+        vec!["cp".to_string(), "$f".to_string(), copy],
+    );
+    run(&playground.0, config, vec![key(KeyCode::Enter)]);
+    assert_eq!(
+        fs::read_to_string(playground.path("opened")).unwrap(),
+        "notes.txt"
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn decompresses_into_a_private_directory() {
+    let playground = Playground::new(&["notes.txt"]);
+    let compressed = std::process::Command::new("gzip")
+        .arg(playground.path("notes.txt"))
+        .status();
+    if !compressed.is_ok_and(|status| status.success()) {
+        return;
+    }
+    let mut config = config();
+    let script = format!(
+        "stat -c '%n %a' \"$(dirname \"$1\")\" > '{}'",
+        playground.path("directory").display()
+    );
+    config.associations.insert(
+        "*.txt".to_string(),
+        ["sh", "-c", &script, "sh", "$f"].map(String::from).to_vec(),
+    );
+    run(&playground.0, config, vec![key(KeyCode::Enter)]);
+    let stat = fs::read_to_string(playground.path("directory")).unwrap();
+    let (directory, mode) = stat.trim().rsplit_once(' ').unwrap();
+    assert_eq!(mode, "700");
+    // removed once the editor's done with it
+    assert!(!Path::new(directory).exists());
+}
+
+#[test]
+fn asks_to_save_archives_left_by_changing_directory() {
+    let playground = Playground::new(&["notes.txt"]);
+    let packed = std::process::Command::new("tar")
+        .arg("-cf")
+        .arg(playground.path("notes.tar"))
+        .arg("-C")
+        .arg(&playground.0)
+        .arg("notes.txt")
+        .status();
+    if !packed.is_ok_and(|status| status.success()) {
+        return;
+    }
+    fs::remove_file(playground.path("notes.txt")).unwrap();
+    let mut events = vec![key(KeyCode::Enter)];
+    events.extend(typed(":touch new.txt"));
+    events.push(key(KeyCode::Enter));
+    // doesn't quit, since that'd lose the new file
+    events.push(control('c'));
+    events.extend(typed(&format!(":cd {}", playground.0.display())));
+    events.push(key(KeyCode::Enter));
+    let (_, screen) = run(&playground.0, config(), events.clone());
+    assert!(screen
+        .last()
+        .unwrap()
+        .starts_with("save changes to 'notes.tar'"));
+
+    events.push(key(KeyCode::Char('y')));
+    let (fee, _) = run(&playground.0, config(), events);
+    assert_eq!(fee.cwd, playground.0);
+    let listing = std::process::Command::new("tar")
+        .arg("-tf")
+        .arg(playground.path("notes.tar"))
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&listing.stdout).contains("new.txt"));
+}
+
+/// Adds up `directory` with `worker`, waiting for it to finish.
+fn directory_stat(worker: &dirstat::Worker, directory: &Path) -> Option<dirstat::Stat> {
+    worker.request(directory.to_path_buf(), None);
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_secs(10) {
+        if let Some((path, stat)) = worker.try_receive() {
+            assert_eq!(path, directory);
+            return stat;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("{} wasn't added up", directory.display());
+}
+
+#[test]
+fn adds_up_directories_again_once_modified() {
+    let playground = Playground::new(&["outer/", "outer/inner/", "outer/inner/a.txt"]);
+    let worker = dirstat::Worker::spawn();
+    let outer = playground.path("outer");
+    let stat = directory_stat(&worker, &outer).unwrap();
+    assert_eq!(
+        (stat.items, stat.size),
+        (1, "outer/inner/a.txt".len() as u64)
+    );
+
+    // only the inner directory is modified, so the outer one is read from the cache
+    fs::write(playground.path("outer/inner/b.txt"), "bb").unwrap();
+    let stat = directory_stat(&worker, &outer).unwrap();
+    assert_eq!(
+        (stat.items, stat.size),
+        (1, "outer/inner/a.txt".len() as u64 + 2)
+    );
+}
+
+#[test]
+fn gives_up_adding_up_directories_for_newer_ones() {
+    let playground = Playground::new(&["small/", "small/a.txt"]);
+    for number in 0..2000 {
+        fs::create_dir_all(playground.path(&format!("big/{}/inner", number))).unwrap();
+    }
+    let worker = dirstat::Worker::spawn();
+    directory_stat(&worker, &playground.path("small"));
+    worker.request(playground.path("big"), None);
+    std::thread::sleep(Duration::from_millis(10));
+    // asked for while the big one is being added up, it comes first, and the
+    // big one never does
+    let stat = directory_stat(&worker, &playground.path("small")).unwrap();
+    assert_eq!(stat.items, 1);
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(worker.try_receive().is_none());
+}
+
+#[test]
+fn tells_when_directories_cant_be_added_up() {
+    let playground = Playground::new(&[]);
+    let worker = dirstat::Worker::spawn();
+    assert!(directory_stat(&worker, &playground.path("gone")).is_none());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn writes_quickfix_lists_to_a_private_directory() {
+    let playground = Playground::new(&["notes.txt"]);
+    let mut config = config();
+    let script = format!(
+        "cp \"$1\" '{0}/list'; stat -c '%n %a' \"$(dirname \"$1\")\" > '{0}/directory'",
+        playground.0.display()
+    );
+    config.open_all_command = ["sh", "-c", &script, "sh", "$q"].map(String::from).to_vec();
+    let mut events = typed(" ");
+    events.extend(typed(":open-all"));
+    events.push(key(KeyCode::Enter));
+    run(&playground.0, config, events);
+    let list = fs::read_to_string(playground.path("list")).unwrap();
+    assert!(list.starts_with(&format!("{}:1:", playground.path("notes.txt").display())));
+    let stat = fs::read_to_string(playground.path("directory")).unwrap();
+    let (directory, mode) = stat.trim().rsplit_once(' ').unwrap();
+    assert_eq!(mode, "700");
+    assert!(!Path::new(directory).exists());
+}
+
+#[test]
+fn runs_shell_commands_on_the_selected_files() {
+    let playground = Playground::new(&["it's.txt", "other.txt"]);
+    let mut events = typed("!cp $f $d/copied");
+    events.push(key(KeyCode::Enter));
+    // its output stays up until a key is pressed
+    events.push(key(KeyCode::Char('x')));
+    let (_, screen) = run(&playground.0, config(), events);
+    assert_eq!(
+        fs::read_to_string(playground.path("copied")).unwrap(),
+        "it's.txt"
+    );
+    assert!(shows(&screen, "copied"));
+}
+
+#[cfg(unix)]
+#[test]
+fn leaves_shell_variables_alone_in_shell_commands() {
+    let playground = Playground::new(&["empty/"]);
+    let mut events = vec![];
+    for command in [
+        "!foo=bar; echo $foo > $d/../variable",
+        "!d=kept; echo $$d > $d/../dollar",
+        // nothing's selected, but that's still an argument
+        "!set -- $f; echo $# > $d/../arguments",
+    ] {
+        events.extend(typed(command));
+        events.push(key(KeyCode::Enter));
+        events.push(key(KeyCode::Char('x')));
+    }
+    run(&playground.path("empty"), config(), events);
+    let read = |name| fs::read_to_string(playground.path(name)).unwrap();
+    assert_eq!(read("variable"), "bar\n");
+    assert_eq!(read("dollar"), "kept\n");
+    assert_eq!(read("arguments"), "1\n");
+}
+
+#[test]
+fn runs_custom_commands_bound_to_keys() {
+    let playground = Playground::new(&["notes.txt"]);
+    let mut config = config();
+    let copy = playground.path("copied").to_string_lossy().to_string();
+    config.custom_commands.push(keymap::CustomCommand {
+        key: "<c-y>".to_string(),
+        command: vec!["cp".to_string(), "$f".to_string(), copy],
+        wait: false,
+    });
+    let (_, screen) = run(&playground.0, config, vec![control('y')]);
+    assert_eq!(
+        fs::read_to_string(playground.path("copied")).unwrap(),
+        "notes.txt"
+    );
+    assert!(shows(&screen, "copied"));
+}
+
+#[test]
+fn opens_a_subshell_in_the_current_directory() {
+    let playground = Playground::new(&["notes.txt"]);
+    let mut config = config();
+    config.shell = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        "pwd > shell-was-here".to_string(),
+    ];
+    run(&playground.0, config, vec![key(KeyCode::Char('$'))]);
+    let directory = fs::read_to_string(playground.path("shell-was-here")).unwrap();
+    assert_eq!(
+        Path::new(directory.trim()).canonicalize().unwrap(),
+        playground.0.canonicalize().unwrap()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn refuses_to_delete_without_permission() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // root may change anything
+    if unsafe { libc::geteuid() } == 0 {
+        return;
+    }
+    let playground = Playground::new(&["locked/", "locked/notes.txt"]);
+    let locked = playground.path("locked");
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+    // asked with sudo only once the usual confirmation is given
+    let (fee, screen) = run(&locked, config(), typed("Dy"));
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    assert!(playground.path("locked/notes.txt").exists());
+    assert!(screen.iter().any(|row| row.contains("permission denied")));
+    let Mode::Confirm(confirmation) = &fee.mode else {
+        panic!("not asked whether to use sudo");
+    };
+    assert!(confirmation
+        .question()
+        .ends_with("delete 'notes.txt' with sudo rm -rf? (y/n)"));
+}
+
+#[test]
+fn recalls_past_filters() {
+    let playground = Playground::new(&["apple", "banana", "cherry"]);
+    let mut events = typed("/che");
+    events.push(key(KeyCode::Enter));
+    events.push(key(KeyCode::Home));
+    events.push(key(KeyCode::Char('/')));
+    events.push(key(KeyCode::Up));
+    let (fee, screen) = run(&playground.0, config(), events);
+    assert_eq!(fee.selected_item().unwrap().name, "cherry");
+    assert!(!shows(&screen, "apple"));
+}
+
+#[test]
+fn keeps_overwritten_files_when_pasting_fails() {
+    let playground = Playground::new(&[
+        "a/",
+        "a/notes.txt",
+        "a/gone.txt",
+        "b/",
+        "b/notes.txt",
+        "b/gone.txt",
+    ]);
+    let clipboard = clipboard::Clipboard {
+        paths: vec![
+            playground.path("a/notes.txt"),
+            playground.path("a/gone.txt"),
+        ],
+        cut: false,
+    };
+    fs::remove_file(playground.path("a/gone.txt")).unwrap();
+    let mut paste = clipboard::Paste::new(clipboard, playground.path("b"), false);
+    paste.paste(Some(clipboard::Resolution::Overwrite));
+    paste.paste(Some(clipboard::Resolution::Overwrite));
+    assert_eq!(paste.errors.len(), 1);
+    let read = |path: &str| fs::read_to_string(playground.path(path)).unwrap();
+    assert_eq!(read("b/notes.txt"), "a/notes.txt");
+    // there was nothing to replace it with, so it's still there
+    assert_eq!(read("b/gone.txt"), "b/gone.txt");
+    assert_eq!(fs::read_dir(playground.path("b")).unwrap().count(), 2);
+}
+
+#[test]
+fn finds_last_commits_of_tracked_entries_only() {
+    let playground = Playground::new(&["src/", "src/main.rs", "notes.txt", "build/"]);
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=fee", "-c", "user.email=fee@example.com"])
+            .args(args)
+            .current_dir(&playground.0)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+        assert!(
+            status.is_ok_and(|status| status.success()),
+            "git isn't installed"
+        );
+    };
+    git(&["init", "-q"]);
+    git(&["add", "src"]);
+    git(&["commit", "-q", "-m", "start"]);
+    let worker = gitlog::Worker::spawn();
+    let names = ["src", "notes.txt", "build"].map(String::from).into();
+    worker.request(playground.0.clone(), names);
+    let started = Instant::now();
+    let commits = loop {
+        if let Some((directory, commits)) = worker.try_receive() {
+            assert_eq!(directory, playground.0);
+            break commits;
+        }
+        assert!(started.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    assert_eq!(commits.keys().collect::<Vec<_>>(), ["src"]);
+}
+
+#[test]
+fn stops_waiting_for_previewers_that_leave_something_running() {
+    let playground = Playground::new(&["paper.pdf"]);
+    let mut config = config();
+    config.preview_timeout_ms = 200;
+    // the backgrounded sleep keeps the output open after the shell exits
+    config.pdf_preview_command = ["sh", "-c", "echo paper; sleep 5 &"]
+        .map(String::from)
+        .to_vec();
+    let started = Instant::now();
+    preview::generate(&playground.path("paper.pdf"), 10, &config, false).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn sorts_files_found_later_in_with_earlier_matches() {
+    let mut files: Vec<String> = (0..1000).map(|number| format!("{}.txt", number)).collect();
+    files.extend([
+        "deep/".to_string(),
+        "deep/down/".to_string(),
+        "deep/down/7.txt".to_string(),
+    ]);
+    let files: Vec<&str> = files.iter().map(String::as_str).collect();
+    let playground = Playground::new(&files);
+    let mut finder = finder::Finder::new(playground.0.clone(), false, None);
+    finder.prompt.set_text("7.txt");
+    finder.update();
+    let started = Instant::now();
+    while !finder.done {
+        assert!(started.elapsed() < Duration::from_secs(10));
+        finder.receive();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    let merged = finder.matches.clone();
+    // the same as if everything had been found at once
+    finder.update();
+    assert_eq!(finder.matches, merged);
+    assert_eq!(finder.paths[finder.matches[0]], "7.txt");
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn trashes_files_under_free_names_with_their_paths_encoded() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let playground = Playground::new(&["a/", "a/my notes.txt", "b/", "b/my notes.txt"]);
+    let latin1 = playground.path("a").join(OsStr::from_bytes(b"caf\xe9.txt"));
+    fs::write(&latin1, "").unwrap();
+    for path in ["a/my notes.txt", "b/my notes.txt"] {
+        trash::trash(&playground.path(path)).unwrap();
+    }
+    trash::trash(&latin1).unwrap();
+    let info = shared::data_dir().unwrap().join("Trash").join("info");
+    let read = |name: &str| fs::read_to_string(info.join(name)).unwrap();
+    let root = playground.0.display();
+    assert!(read("my notes.txt.trashinfo").contains(&format!("Path={}/a/my%20notes.txt\n", root)));
+    assert!(
+        read("my notes (2).txt.trashinfo").contains(&format!("Path={}/b/my%20notes.txt\n", root))
+    );
+    let latin1_info = info.join(OsStr::from_bytes(b"caf\xe9.txt.trashinfo"));
+    assert!(fs::read_to_string(latin1_info)
+        .unwrap()
+        .contains("/a/caf%E9.txt\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn shows_times_in_local_time() {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = 2024 - 1900;
+    tm.tm_mon = 2;
+    tm.tm_mday = 5;
+    tm.tm_hour = 23;
+    tm.tm_min = 30;
+    tm.tm_isdst = -1;
+    // SAFETY: `tm` is a valid tm struct to read and normalise
+    let seconds = unsafe { libc::mktime(&mut tm) };
+    let time = std::time::UNIX_EPOCH + Duration::from_secs(seconds as u64);
+    assert_eq!(properties::local(time), Some((2024, 3, 5, 23, 30, 0)));
+    assert_eq!(properties::format_time(time), "2024-03-05 23:30:00");
+    assert_eq!(properties::format_short_time(time), "2024-03-05 23:30");
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use crate::highlight::SyntaxTheme;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Theme {
+    /// Style of the borders drawn around and between panes.
+    pub border_style: BorderStyle,
+    pub border_color: [u8; 3],
+    /// Colors of highlighted code in previews.
+    pub syntax_theme: SyntaxTheme,
+}
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            border_style: BorderStyle::Off,
+            border_color: [110, 110, 110],
+            syntax_theme: SyntaxTheme::Dark,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderStyle {
+    Rounded,
+    Ascii,
+    Off,
+}
+
+pub struct BorderChars {
+    pub horizontal: char,
+    pub vertical: char,
+    pub top_left: char,
+    pub top_right: char,
+    pub bottom_left: char,
+    pub bottom_right: char,
+    /// Where a separator meets the top border.
+    pub tee_down: char,
+    /// Where a separator meets the bottom border.
+    pub tee_up: char,
+    /// Where a separator meets the left border.
+    pub tee_right: char,
+    /// Where a separator meets the right border.
+    pub tee_left: char,
+}
+
+impl BorderStyle {
+    pub fn chars(self) -> Option<BorderChars> {
+        match self {
+            BorderStyle::Rounded => Some(BorderChars {
+                horizontal: '─',
+                vertical: '│',
+                top_left: '╭',
+                top_right: '╮',
+                bottom_left: '╰',
+                bottom_right: '╯',
+                tee_down: '┬',
+                tee_up: '┴',
+                tee_right: '├',
+                tee_left: '┤',
+            }),
+            BorderStyle::Ascii => Some(BorderChars {
+                horizontal: '-',
+                vertical: '|',
+                top_left: '+',
+                top_right: '+',
+                bottom_left: '+',
+                bottom_right: '+',
+                tee_down: '+',
+                tee_up: '+',
+                tee_right: '+',
+                tee_left: '+',
+            }),
+            BorderStyle::Off => None,
+        }
+    }
+}
+
+/// A named set of colors and borders to start from, offered by the setup wizard.
+pub struct Preset {
+    pub name: &'static str,
+    pub dir_color: [u8; 3],
+    pub file_color: [u8; 3],
+    pub border_style: BorderStyle,
+    pub border_color: [u8; 3],
+    pub syntax_theme: SyntaxTheme,
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "default",
+        dir_color: [59, 120, 255],
+        file_color: [46, 199, 219],
+        border_style: BorderStyle::Off,
+        border_color: [110, 110, 110],
+        syntax_theme: SyntaxTheme::Dark,
+    },
+    Preset {
+        name: "boxed",
+        dir_color: [59, 120, 255],
+        file_color: [46, 199, 219],
+        border_style: BorderStyle::Rounded,
+        border_color: [110, 110, 110],
+        syntax_theme: SyntaxTheme::Dark,
+    },
+    Preset {
+        name: "light (for light terminal backgrounds)",
+        dir_color: [0, 60, 180],
+        file_color: [0, 110, 110],
+        border_style: BorderStyle::Rounded,
+        border_color: [150, 150, 150],
+        syntax_theme: SyntaxTheme::Light,
+    },
+    Preset {
+        name: "mono",
+        dir_color: [230, 230, 230],
+        file_color: [160, 160, 160],
+        border_style: BorderStyle::Ascii,
+        border_color: [90, 90, 90],
+        syntax_theme: SyntaxTheme::Dark,
+    },
+];
@@ -0,0 +1,130 @@
+//! Moving files to the trash: the XDG trash on Linux and other unixes, `~/.Trash`
+//! on macOS and the Recycle Bin on Windows.
+
+use std::{io, path::Path};
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::{io::Write, path::PathBuf};
+
+/// Moves `path` to the trash. Files on another filesystem than the home trash
+/// go to the trash at the top of theirs, so they don't have to be copied.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn trash(path: &Path) -> io::Result<()> {
+    use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt, time::SystemTime};
+
+    use crate::{fileops, properties, shared, sysclip};
+
+    let home_trash = shared::data_dir()
+        .ok_or(io::Error::other("couldn't find the data directory"))?
+        .join("Trash");
+    // the trash at the top of a filesystem holds paths relative to the top
+    let (trash, original) = match top_trash(path, &home_trash) {
+        Some((trash, top)) => (trash, path.strip_prefix(top).unwrap_or(path)),
+        None => (home_trash, path),
+    };
+    fs::create_dir_all(trash.join("files"))?;
+    fs::create_dir_all(trash.join("info"))?;
+
+    let info_path = |name: &OsStr| {
+        let mut name = name.to_os_string();
+        name.push(".trashinfo");
+        trash.join("info").join(name)
+    };
+    let name = path.file_name().unwrap_or_default();
+    let destination = fileops::free_name(&trash.join("files"), name, |name| {
+        info_path(name).symlink_metadata().is_ok()
+    });
+    let (year, month, day, hour, minute, second) =
+        properties::local(SystemTime::now()).unwrap_or_default();
+    let info = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={:04}-{:02}-{:02}T{:02}:{:02}:{:02}\n",
+        sysclip::percent_encode(original.as_os_str().as_bytes()),
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second
+    );
+    // the info file goes first, so the name is claimed
+    let info_path = info_path(destination.file_name().unwrap_or_default());
+    fs::File::create_new(&info_path)?.write_all(info.as_bytes())?;
+    if let Err(error) = fileops::move_to(path, &destination) {
+        let _ = fs::remove_file(info_path);
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// The trash at the top of the filesystem `path` is on, `$topdir/.Trash-$uid`,
+/// and the top directory, unless it's the filesystem `home_trash` is on. Made
+/// if it's missing, but only used if it's the user's own.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn top_trash<'a>(path: &'a Path, home_trash: &Path) -> Option<(PathBuf, &'a Path)> {
+    use std::{
+        fs,
+        os::unix::fs::{DirBuilderExt, MetadataExt},
+    };
+
+    let device = path.symlink_metadata().ok()?.dev();
+    // the home trash may not have been made yet
+    let home_device = home_trash
+        .ancestors()
+        .find_map(|ancestor| ancestor.metadata().ok())?
+        .dev();
+    if device == home_device {
+        return None;
+    }
+    let top = path
+        .parent()?
+        .ancestors()
+        .take_while(|ancestor| {
+            ancestor
+                .metadata()
+                .is_ok_and(|metadata| metadata.dev() == device)
+        })
+        .last()?;
+    // SAFETY: getuid has no preconditions and can't fail
+    let uid = unsafe { libc::getuid() };
+    let trash = top.join(format!(".Trash-{}", uid));
+    let _ = fs::DirBuilder::new().mode(0o700).create(&trash);
+    let metadata = trash.symlink_metadata().ok()?;
+    (metadata.is_dir() && metadata.uid() == uid).then_some((trash, top))
+}
+
+#[cfg(target_os = "macos")]
+pub fn trash(path: &Path) -> io::Result<()> {
+    let trash = dirs::home_dir()
+        .ok_or(io::Error::other("couldn't find the home directory"))?
+        .join(".Trash");
+    let name = path.file_name().unwrap_or_default();
+    let destination = crate::fileops::free_name(&trash, name, |_| false);
+    crate::fileops::move_to(path, &destination)
+}
+
+#[cfg(windows)]
+pub fn trash(path: &Path) -> io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    let method = if path.is_dir() {
+        "DeleteDirectory"
+    } else {
+        "DeleteFile"
+    };
+    let script = format!(
+        "Add-Type -AssemblyName Microsoft.VisualBasic; \
+         [Microsoft.VisualBasic.FileIO.FileSystem]::{}('{}', 'OnlyErrorDialogs', 'SendToRecycleBin')",
+        method,
+        path.to_string_lossy().replace('\'', "''")
+    );
+    let status = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("couldn't move it to the Recycle Bin"))
+    }
+}